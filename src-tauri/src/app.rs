@@ -7,7 +7,17 @@ use crate::{
   exporter,
   importer,
   model::batch::{BatchManager, BatchOptions, BatchProgress},
+  model::calibration::CalibrationReport,
+  model::fusion::FusionPolicy,
+  model::prompt::PromptTemplate,
   model::provider::ProviderHealth,
+  rules::{
+    brand::BrandEntry,
+    preview::{PreviewOptions, PreviewReport},
+    stats::{RuleStats, StatsOptions},
+    url::UrlListEntry,
+    RulePack,
+  },
   selftest::SelftestRunner,
   status::{DbStatus, ProviderInfo, StatusSnapshot},
   settings::{AppSettings, SettingsStore},
@@ -82,14 +92,37 @@ pub fn run() {
       messages_meta,
       // manual review
       label_update_manual,
+      label_explain,
+      // brand registry
+      brands_list,
+      brands_upsert,
+      brands_delete,
+      brands_learn_from_reviewed,
+      // url allow/deny lists
+      url_lists_list,
+      url_lists_upsert,
+      url_lists_delete,
+      // prompt templates
+      prompt_templates_list,
+      prompt_template_save,
+      prompt_template_delete,
       // batch
       batch_start,
       batch_stop,
       batch_status,
       batch_retry_failed,
+      rules_preview,
+      rules_stats,
+      calibration_stats,
+      calibration_fit,
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|_app, event| {
+      if let tauri::RunEvent::Exit = event {
+        crate::model::llama_server::shutdown();
+      }
+    });
 }
 
 #[tauri::command]
@@ -99,6 +132,10 @@ pub fn settings_get(state: State<'_, AppState>) -> Result<AppSettings, String> {
 
 #[tauri::command]
 pub fn settings_set(state: State<'_, AppState>, settings: AppSettings) -> Result<(), String> {
+  // Reject settings whose rule pack does not compile (e.g. an invalid custom entity regex).
+  RulePack::from_settings(&settings)?;
+  PromptTemplate::resolve(&state.db, settings.provider.prompt_version.as_deref())?;
+  FusionPolicy::from_settings(&settings.fusion)?;
   state.settings.set(settings).map_err(to_string_err)
 }
 
@@ -131,6 +168,8 @@ pub fn status_snapshot(state: State<'_, AppState>) -> Result<StatusSnapshot, Str
     kind: settings.provider.kind,
     model_path: settings.provider.model_path,
     llama_cli_path: settings.provider.llama_cli_path,
+    llama_server_path: settings.provider.llama_server_path,
+    llama_server_port: settings.provider.llama_server_port,
     ollama_base_url: settings.provider.ollama_base_url,
     ollama_model: settings.provider.ollama_model,
     temperature: settings.provider.temperature,
@@ -193,6 +232,11 @@ pub fn messages_list(
   state.db.dao().messages_list(query).map_err(to_string_err)
 }
 
+#[tauri::command]
+pub fn label_explain(state: State<'_, AppState>, message_id: i64) -> Result<crate::db::dao::LabelExplain, String> {
+  state.db.dao().label_explain(message_id).map_err(to_string_err)
+}
+
 #[tauri::command]
 pub fn label_update_manual(
   state: State<'_, AppState>,
@@ -207,6 +251,62 @@ pub fn label_update_manual(
     .map_err(to_string_err)
 }
 
+#[tauri::command]
+pub fn brands_list(state: State<'_, AppState>) -> Result<Vec<BrandEntry>, String> {
+  state.db.dao().brands_list().map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn brands_upsert(state: State<'_, AppState>, entry: BrandEntry) -> Result<(), String> {
+  state.db.dao().brand_upsert(&entry).map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn brands_delete(state: State<'_, AppState>, alias: String) -> Result<(), String> {
+  state.db.dao().brand_delete(&alias).map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn brands_learn_from_reviewed(state: State<'_, AppState>) -> Result<i64, String> {
+  state.db.dao().brands_learn_from_reviewed().map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn url_lists_list(state: State<'_, AppState>) -> Result<Vec<UrlListEntry>, String> {
+  state.db.dao().url_lists_list().map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn url_lists_upsert(state: State<'_, AppState>, entry: UrlListEntry) -> Result<(), String> {
+  state.db.dao().url_list_upsert(&entry).map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn url_lists_delete(state: State<'_, AppState>, domain: String) -> Result<(), String> {
+  state.db.dao().url_list_delete(&domain).map_err(to_string_err)
+}
+
+/// The built-in template first, then the stored versions.
+#[tauri::command]
+pub fn prompt_templates_list(state: State<'_, AppState>) -> Result<Vec<PromptTemplate>, String> {
+  let mut out = vec![PromptTemplate::builtin()];
+  out.extend(state.db.dao().prompt_templates_list().map_err(to_string_err)?);
+  Ok(out)
+}
+
+#[tauri::command]
+pub fn prompt_template_save(state: State<'_, AppState>, template: PromptTemplate) -> Result<(), String> {
+  state.db.dao().prompt_template_save(&template).map_err(to_string_err)
+}
+
+#[tauri::command]
+pub fn prompt_template_delete(state: State<'_, AppState>, version: String) -> Result<(), String> {
+  if state.settings.get().provider.prompt_version.as_deref() == Some(version.trim()) {
+    return Err(format!("prompt version {version} is active; select another one first"));
+  }
+  state.db.dao().prompt_template_delete(&version).map_err(to_string_err)
+}
+
 #[tauri::command]
 pub fn batch_start(state: State<'_, AppState>, app: AppHandle, options: BatchOptions) -> Result<(), String> {
   state.batch.start(options, app).map_err(to_string_err)
@@ -228,6 +328,37 @@ pub fn batch_retry_failed(state: State<'_, AppState>) -> Result<(), String> {
   state.batch.retry_failed().map_err(to_string_err)
 }
 
+/// Dry-run the rule pack built from `settings` (the saved settings when omitted) over stored messages.
+#[tauri::command]
+pub fn rules_preview(
+  state: State<'_, AppState>,
+  options: PreviewOptions,
+  settings: Option<AppSettings>,
+) -> Result<PreviewReport, String> {
+  let settings = settings.unwrap_or_else(|| state.settings.get().clone());
+  let pack = RulePack::load(&settings, &state.db)?;
+  crate::rules::preview::preview(&state.db, &pack, &options).map_err(to_string_err)
+}
+
+/// Per-rule hit counts and precision from the stored traces, plus what the rules miss.
+#[tauri::command]
+pub fn rules_stats(state: State<'_, AppState>, options: Option<StatsOptions>) -> Result<RuleStats, String> {
+  crate::rules::stats::rule_stats(&state.db, &options.unwrap_or_default()).map_err(to_string_err)
+}
+
+/// Reliability diagrams and ECE per confidence source, raw and through the stored curves.
+#[tauri::command]
+pub fn calibration_stats(state: State<'_, AppState>) -> Result<CalibrationReport, String> {
+  crate::model::calibration::calibration_report(&state.db).map_err(to_string_err)
+}
+
+/// Refit the calibration curves from reviewed labels; the next batch applies them.
+#[tauri::command]
+pub fn calibration_fit(state: State<'_, AppState>) -> Result<CalibrationReport, String> {
+  let settings = state.settings.get().calibration.clone();
+  crate::model::calibration::calibration_fit(&state.db, &settings).map_err(to_string_err)
+}
+
 fn to_string_err<E: std::fmt::Display>(e: E) -> String {
   e.to_string()
 }
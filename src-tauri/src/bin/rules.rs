@@ -0,0 +1,332 @@
+//! Rule tooling without the UI.
+//!
+//! ```text
+//! rules test [fixtures.jsonl] [--db smsto.sqlite3] [--settings settings.json]
+//! rules export-fixtures --db smsto.sqlite3 --out fixtures.jsonl
+//! rules preview --db smsto.sqlite3 [--settings settings.json] [--mode all] [--sample 2000] [--examples 3]
+//! rules stats --db smsto.sqlite3 [--top 30]
+//! rules bench [--messages 1000000] [--brands 0] [--db smsto.sqlite3]
+//! rules calibration --db smsto.sqlite3 [--settings settings.json] [--fit]
+//! ```
+//!
+//! `test` runs the bundled fixtures when no file is given, against the brand registry and URL
+//! lists of `--db` (a throwaway database with the seed data otherwise). It exits 1 on failures.
+//! `preview` shows what this build's rules (with `--settings`) would change in `--db`, without writing.
+//! `stats` reports per-rule hits and precision from the traces stored in `--db`.
+//! `bench` measures single-threaded rule throughput on synthetic messages (build with `--release`);
+//! `--brands` adds that many synthetic registry aliases to show how the keyword automaton scales.
+//! `calibration` prints ECE and reliability bins per confidence source; `--fit` refits the curves
+//! first with the method from `--settings`.
+
+use std::{fs, path::PathBuf, process::ExitCode, time::Instant};
+
+use sms_tagging_officer::{
+  db::Db,
+  model::{
+    calibration::{self, ReliabilityBin},
+    schema::INDUSTRIES,
+  },
+  exporter::{self, ExportOptions},
+  rules::{
+    brand::BrandEntry,
+    fixtures,
+    preview::{self, PreviewOptions},
+    run_rules_with,
+    stats::{self, StatsOptions},
+    RulePack,
+  },
+  selftest,
+  settings::{AppSettings, SettingsStore},
+};
+
+const USAGE: &str = "usage:
+  rules test [fixtures.jsonl] [--db path] [--settings path]
+  rules export-fixtures --db path --out path
+  rules preview --db path [--settings path] [--mode all|unlabeled|needs_review] [--sample n] [--examples n]
+  rules stats --db path [--top n]
+  rules bench [--messages n] [--brands n] [--db path]
+  rules calibration --db path [--settings path] [--fit]";
+
+fn main() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let result = match args.first().map(String::as_str) {
+    Some("test") => cmd_test(&args[1..]),
+    Some("export-fixtures") => cmd_export_fixtures(&args[1..]),
+    Some("preview") => cmd_preview(&args[1..]),
+    Some("stats") => cmd_stats(&args[1..]),
+    Some("bench") => cmd_bench(&args[1..]),
+    Some("calibration") => cmd_calibration(&args[1..]),
+    _ => Err(USAGE.to_string()),
+  };
+  match result {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("{e}");
+      ExitCode::from(2)
+    }
+  }
+}
+
+fn cmd_test(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let text = match opts.positional.first() {
+    Some(path) => fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?,
+    None => selftest::BUNDLED_FIXTURES.to_string(),
+  };
+  let cases = fixtures::parse_fixtures(&text)?;
+
+  let db = open_db(opts.db.clone())?;
+  let pack = RulePack::load(&load_settings(&opts)?, &db)?;
+
+  let report = fixtures::run_fixtures(&pack, &cases);
+  for line in report.failure_lines() {
+    println!("{line}");
+  }
+  println!(
+    "{} fixtures: {} passed, {} failed ({} deferred to the model)",
+    report.total, report.passed, report.failed, report.deferred
+  );
+  Ok(if report.ok() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+fn cmd_export_fixtures(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let db_path = opts.db.ok_or("export-fixtures needs --db")?;
+  let out = opts.out.ok_or("export-fixtures needs --out")?;
+  let db = open_db(Some(db_path))?;
+  let written = exporter::execute(
+    &db,
+    out.clone(),
+    ExportOptions {
+      only_reviewed: true,
+      format: "fixtures".to_string(),
+    },
+  )?;
+  println!("exported {written} fixtures -> {}", out.display());
+  Ok(ExitCode::SUCCESS)
+}
+
+fn cmd_preview(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let db = open_db(Some(opts.db.clone().ok_or("preview needs --db")?))?;
+  let pack = RulePack::load(&load_settings(&opts)?, &db)?;
+  let mut options = PreviewOptions::default();
+  if let Some(mode) = opts.mode {
+    options.mode = mode;
+  }
+  options.sample = opts.sample;
+  if let Some(n) = opts.examples {
+    options.examples = n;
+  }
+
+  let r = preview::preview(&db, &pack, &options)?;
+  println!(
+    "{}: scanned {} of {} candidates; {} would change, {} unchanged, {} manual kept ({} disagree)",
+    r.rules_version, r.scanned, r.candidates, r.changed, r.unchanged, r.manual_kept, r.manual_disagree
+  );
+  for t in &r.transitions {
+    let mark = if t.from == t.to { " " } else { "*" };
+    println!("{mark} {:>6}  {} -> {}", t.count, t.from, t.to);
+    if t.from != t.to {
+      for e in &t.examples {
+        println!("          #{} {} [{}]", e.message_id, e.content, e.reason.as_deref().unwrap_or("-"));
+      }
+    }
+  }
+  Ok(ExitCode::SUCCESS)
+}
+
+fn cmd_stats(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let db = open_db(Some(opts.db.clone().ok_or("stats needs --db")?))?;
+  let mut options = StatsOptions::default();
+  if let Some(n) = opts.top {
+    options.top = n;
+  }
+
+  let s = stats::rule_stats(&db, &options)?;
+  println!(
+    "{} traced, {} untraced labels; {} fell through to the model",
+    s.traced, s.untraced, s.fell_through
+  );
+  println!("{:<24} {:>8} {:>8} {:>9} {:>10}", "rule", "matched", "won", "reviewed", "precision");
+  for r in &s.rules {
+    let precision = r.precision.map(|p| format!("{:.1}%", p * 100.0)).unwrap_or_else(|| "-".to_string());
+    println!("{:<24} {:>8} {:>8} {:>9} {:>10}", r.rule, r.matched, r.won, r.reviewed, precision);
+  }
+  if !s.never_fired.is_empty() {
+    println!("never fired: {}", s.never_fired.join(", "));
+  }
+  println!("top n-grams among fell-through messages:");
+  for g in &s.top_unmatched_ngrams {
+    println!("  {:>6} {:>6.1}%  {}", g.messages, g.share * 100.0, g.ngram);
+  }
+  Ok(ExitCode::SUCCESS)
+}
+
+fn cmd_calibration(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let db = open_db(Some(opts.db.clone().ok_or("calibration needs --db")?))?;
+  let report = if opts.fit {
+    calibration::calibration_fit(&db, &load_settings(&opts)?.calibration)?
+  } else {
+    calibration::calibration_report(&db)?
+  };
+  if report.sources.is_empty() {
+    println!("no reviewed predictions yet");
+  }
+  for s in &report.sources {
+    let fitted = match (&s.method, s.calibrated_ece) {
+      (Some(m), Some(e)) => format!("{m}, calibrated ECE {e:.4}"),
+      _ => "no curve".to_string(),
+    };
+    println!(
+      "{}: {} samples, accuracy {:.1}%, mean confidence {:.1}%, ECE {:.4} ({fitted})",
+      s.source,
+      s.samples,
+      s.accuracy * 100.0,
+      s.mean_confidence * 100.0,
+      s.ece
+    );
+    print_bins("raw", &s.bins);
+    if !s.calibrated_bins.is_empty() {
+      print_bins("calibrated", &s.calibrated_bins);
+    }
+  }
+  Ok(ExitCode::SUCCESS)
+}
+
+fn print_bins(title: &str, bins: &[ReliabilityBin]) {
+  println!("  {title}:");
+  for b in bins {
+    println!(
+      "    [{:.1}, {:.1})  n={:<6} confidence {:>5.1}%  accuracy {:>5.1}%",
+      b.lo,
+      b.hi,
+      b.count,
+      b.mean_confidence * 100.0,
+      b.accuracy * 100.0
+    );
+  }
+}
+
+fn cmd_bench(args: &[String]) -> Result<ExitCode, String> {
+  let opts = Opts::parse(args)?;
+  let total = opts.messages.unwrap_or(1_000_000);
+  let db = open_db(opts.db.clone())?;
+  let mut brands = db.dao().brands_list()?;
+  brands.extend((0..opts.brands.unwrap_or(0)).map(|i| BrandEntry {
+    alias: format!("测试品牌{i:06}"),
+    brand: format!("测试品牌{i:06}"),
+    industry: INDUSTRIES[i % INDUSTRIES.len()].to_string(),
+    org_type: None,
+    source: "manual".to_string(),
+  }));
+  let started = Instant::now();
+  let pack = RulePack::from_settings(&load_settings(&opts)?)?
+    .with_brands(brands)
+    .with_url_lists(db.dao().url_lists_list()?);
+  println!(
+    "pack: {} keywords in one automaton, built in {:.1} ms",
+    pack.keywords.len(),
+    started.elapsed().as_secs_f64() * 1000.0
+  );
+
+  // The fixture messages with their digits shifted, so codes and amounts vary between copies.
+  let templates: Vec<String> = fixtures::parse_fixtures(selftest::BUNDLED_FIXTURES)?
+    .into_iter()
+    .map(|(_, f)| f.content)
+    .collect();
+  let messages: Vec<String> = (0..templates.len() * 10)
+    .map(|i| {
+      let shift = (i / templates.len()) as u32;
+      templates[i % templates.len()]
+        .chars()
+        .map(|c| c.to_digit(10).map_or(c, |d| char::from_digit((d + shift) % 10, 10).unwrap_or(c)))
+        .collect()
+    })
+    .collect();
+
+  let started = Instant::now();
+  let mut found = 0usize;
+  for i in 0..total {
+    found += pack.keywords.scan(&messages[i % messages.len()]).len();
+  }
+  report("keyword scan", total, started, found);
+
+  let started = Instant::now();
+  let mut strong = 0usize;
+  for i in 0..total {
+    strong += run_rules_with(&pack, &messages[i % messages.len()], None, None).strong_hit as usize;
+  }
+  report("full rules", total, started, strong);
+  Ok(ExitCode::SUCCESS)
+}
+
+fn report(what: &str, total: usize, started: Instant, checksum: usize) {
+  let secs = started.elapsed().as_secs_f64();
+  println!(
+    "{what:<13} {total} messages in {secs:.2} s: {:.0} msg/s, {:.2} µs/msg (checksum {checksum})",
+    total as f64 / secs.max(1e-9),
+    secs * 1e6 / total.max(1) as f64
+  );
+}
+
+fn load_settings(opts: &Opts) -> Result<AppSettings, String> {
+  match &opts.settings {
+    Some(path) => Ok(SettingsStore::load(path.clone())?.get().clone()),
+    None => Ok(AppSettings::default()),
+  }
+}
+
+// Without `--db`, a fresh in-memory database: migrations seed the default brands and lists, and
+// nothing is left behind when the command exits.
+fn open_db(path: Option<PathBuf>) -> Result<Db, String> {
+  let db = Db::open(path.unwrap_or_else(|| PathBuf::from(":memory:")))?;
+  db.migrate()?;
+  Ok(db)
+}
+
+#[derive(Default)]
+struct Opts {
+  positional: Vec<String>,
+  db: Option<PathBuf>,
+  settings: Option<PathBuf>,
+  out: Option<PathBuf>,
+  mode: Option<String>,
+  sample: Option<usize>,
+  examples: Option<usize>,
+  top: Option<usize>,
+  messages: Option<usize>,
+  brands: Option<usize>,
+  fit: bool,
+}
+
+impl Opts {
+  fn parse(args: &[String]) -> Result<Self, String> {
+    let mut opts = Opts::default();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+      let mut value = || it.next().cloned().ok_or(format!("{a} needs a value\n{USAGE}"));
+      match a.as_str() {
+        "--db" => opts.db = Some(value()?.into()),
+        "--settings" => opts.settings = Some(value()?.into()),
+        "--out" => opts.out = Some(value()?.into()),
+        "--mode" => opts.mode = Some(value()?),
+        "--sample" => opts.sample = Some(number(a, &value()?)?),
+        "--examples" => opts.examples = Some(number(a, &value()?)?),
+        "--top" => opts.top = Some(number(a, &value()?)?),
+        "--messages" => opts.messages = Some(number(a, &value()?)?),
+        "--brands" => opts.brands = Some(number(a, &value()?)?),
+        "--fit" => opts.fit = true,
+        s if s.starts_with("--") => return Err(format!("unknown option {s}\n{USAGE}")),
+        s => opts.positional.push(s.to_string()),
+      }
+    }
+    Ok(opts)
+  }
+}
+
+fn number(flag: &str, v: &str) -> Result<usize, String> {
+  v.parse().map_err(|_| format!("{flag} expects a number, got {v}"))
+}
@@ -1,10 +1,16 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
 use sms_tagging_officer::selftest;
 
-fn main() -> Result<(), String> {
+fn main() -> ExitCode {
   let out_dir = PathBuf::from("tools");
-  let r = selftest::run(out_dir)?;
+  let r = match selftest::run(out_dir) {
+    Ok(r) => r,
+    Err(e) => {
+      eprintln!("[selftest] failed: {e}");
+      return ExitCode::FAILURE;
+    }
+  };
   println!("[selftest] db: {}", r.db_path.display());
   println!("[selftest] inserted messages: {}", r.inserted);
   println!("[selftest] labeled messages: {}", r.labeled);
@@ -18,6 +24,16 @@ fn main() -> Result<(), String> {
     r.written_csv,
     r.csv_path.display()
   );
-  Ok(())
+  println!(
+    "[selftest] rule fixtures: {}/{} passed ({} deferred to the model)",
+    r.fixtures.passed, r.fixtures.total, r.fixtures.deferred
+  );
+  if !r.fixtures.ok() {
+    for line in r.fixtures.failure_lines() {
+      eprintln!("[selftest] {line}");
+    }
+    return ExitCode::FAILURE;
+  }
+  ExitCode::SUCCESS
 }
 
@@ -1,10 +1,23 @@
 use std::collections::HashMap;
 
+use once_cell::sync::Lazy;
 use regex::Regex;
 use rusqlite::{params, params_from_iter, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
-use crate::model::schema::{LabelOutput, MessageRow};
+use crate::{
+  model::{
+    prompt::{PromptTemplate, BUILTIN_PROMPT_VERSION},
+    schema::{
+      EntitySource, FewShotExample, LabelOutput, MessageRow, RiskAssessment, RuleTrace, UrlListKind, INDUSTRIES,
+    },
+  },
+  rules::{
+    brand::{extract_signature, normalize_alias, BrandEntry},
+    normalize::normalize_text,
+    url::{find_urls, normalize_domain, UrlListEntry, UrlLists},
+  },
+};
 
 use super::Db;
 
@@ -70,45 +83,58 @@ impl<'a> Dao<'a> {
     Ok(content)
   }
 
+  /// `(sender, received_at)` of a message, the context the rules resolve against.
+  pub fn get_message_meta(&self, message_id: i64) -> Result<(Option<String>, Option<String>), String> {
+    let conn = self.db.conn();
+    conn
+      .query_row(
+        "SELECT sender, received_at FROM messages WHERE id=?1",
+        params![message_id],
+        |r| Ok((r.get(0)?, r.get(1)?)),
+      )
+      .map_err(|e| e.to_string())
+  }
+
   pub fn get_label(&self, message_id: i64) -> Result<Option<LabelOutput>, String> {
     let conn = self.db.conn();
     let row = conn
       .query_row(
-        "SELECT industry, sms_type, confidence, needs_review, reasons_json, signals_json, rules_version, model_version, schema_version, entities_json FROM labels WHERE message_id=?1",
+        &format!("SELECT {LABEL_COLUMNS} FROM labels l WHERE l.message_id=?1"),
         params![message_id],
-        |r| {
-          let reasons_json: String = r.get(4)?;
-          let signals_json: String = r.get(5)?;
-          let entities_json: String = r.get(9)?;
-          Ok(LabelOutput {
-            industry: r.get(0)?,
-            sms_type: r.get(1)?,
-            confidence: r.get(2)?,
-            needs_review: (r.get::<_, i32>(3)? != 0),
-            reasons: serde_json::from_str(&reasons_json).unwrap_or_default(),
-            signals: serde_json::from_str(&signals_json).unwrap_or_default(),
-            rules_version: r.get(6)?,
-            model_version: r.get(7)?,
-            schema_version: r.get(8)?,
-            entities: serde_json::from_str(&entities_json).unwrap_or_default(),
-          })
-        },
+        |r| label_from_row(r, 0),
       )
       .optional()
       .map_err(|e| e.to_string())?;
     Ok(row)
   }
 
+  /// Whether the message's label was set by a reviewer (`is_manual=1`).
+  pub fn label_is_manual(&self, message_id: i64) -> Result<bool, String> {
+    let conn = self.db.conn();
+    let manual: Option<i32> = conn
+      .query_row(
+        "SELECT is_manual FROM labels WHERE message_id=?1",
+        params![message_id],
+        |r| r.get(0),
+      )
+      .optional()
+      .map_err(|e| e.to_string())?;
+    Ok(manual == Some(1))
+  }
+
   pub fn upsert_label_auto(&self, message_id: i64, label: &LabelOutput) -> Result<(), String> {
     let conn = self.db.conn();
     let reasons_json = serde_json::to_string(&label.reasons).map_err(|e| e.to_string())?;
     let signals_json = serde_json::to_string(&label.signals).map_err(|e| e.to_string())?;
     let entities_json = serde_json::to_string(&label.entities).map_err(|e| e.to_string())?;
+    let secondary_types_json = serde_json::to_string(&label.secondary_types).map_err(|e| e.to_string())?;
+    let entity_spans_json = serde_json::to_string(&label.entity_spans).map_err(|e| e.to_string())?;
+    let (risk_score, risk_json) = risk_columns(label.risk.as_ref())?;
 
     conn
       .execute(
-        "INSERT INTO labels(message_id, industry, sms_type, confidence, needs_review, reasons_json, signals_json, rules_version, model_version, schema_version, entities_json, updated_by, is_manual)
-         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,'system',0)
+        "INSERT INTO labels(message_id, industry, sms_type, confidence, needs_review, reasons_json, signals_json, rules_version, model_version, schema_version, entities_json, secondary_types_json, entity_spans_json, risk_score, risk_json, prompt_version, fusion_policy, updated_by, is_manual)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,'system',0)
          ON CONFLICT(message_id) DO UPDATE SET
            industry=excluded.industry,
            sms_type=excluded.sms_type,
@@ -120,6 +146,12 @@ impl<'a> Dao<'a> {
            model_version=excluded.model_version,
            schema_version=excluded.schema_version,
            entities_json=excluded.entities_json,
+           secondary_types_json=excluded.secondary_types_json,
+           entity_spans_json=excluded.entity_spans_json,
+           risk_score=excluded.risk_score,
+           risk_json=excluded.risk_json,
+           prompt_version=excluded.prompt_version,
+           fusion_policy=excluded.fusion_policy,
            updated_by='system',
            updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now')),
            is_manual=0",
@@ -134,30 +166,137 @@ impl<'a> Dao<'a> {
           label.rules_version,
           label.model_version,
           label.schema_version,
-          entities_json
+          entities_json,
+          secondary_types_json,
+          entity_spans_json,
+          risk_score,
+          risk_json,
+          label.prompt_version,
+          label.fusion_policy
         ],
       )
       .map_err(|e| e.to_string())?;
     Ok(())
   }
 
+  pub fn rule_trace_upsert(&self, message_id: i64, trace: &RuleTrace) -> Result<(), String> {
+    let trace_json = serde_json::to_string(trace).map_err(|e| e.to_string())?;
+    let conn = self.db.conn();
+    conn
+      .execute(
+        "INSERT INTO rule_traces(message_id, trace_json) VALUES (?1,?2)
+         ON CONFLICT(message_id) DO UPDATE SET
+           trace_json=excluded.trace_json,
+           updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now'))",
+        params![message_id, trace_json],
+      )
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  /// Visit every stored trace with the message's current label, in message order.
+  pub fn rule_traces_scan(&self, mut f: impl FnMut(TracedLabel)) -> Result<(), String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare(
+        "SELECT t.message_id, t.trace_json, l.industry, l.sms_type, COALESCE(l.is_manual=1 AND l.needs_review=0, 0), m.content
+         FROM rule_traces t JOIN messages m ON m.id=t.message_id LEFT JOIN labels l ON l.message_id=t.message_id
+         ORDER BY t.message_id ASC",
+      )
+      .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+      let trace_json: String = r.get(1).map_err(|e| e.to_string())?;
+      f(TracedLabel {
+        message_id: r.get(0).map_err(|e| e.to_string())?,
+        trace: serde_json::from_str(&trace_json).map_err(|e| e.to_string())?,
+        industry: r.get(2).map_err(|e| e.to_string())?,
+        sms_type: r.get(3).map_err(|e| e.to_string())?,
+        reviewed: r.get::<_, i32>(4).map_err(|e| e.to_string())? != 0,
+        content: r.get(5).map_err(|e| e.to_string())?,
+      });
+    }
+    Ok(())
+  }
+
+  /// Labels stored without a trace (written before traces existed), grouped by the winning
+  /// rule recorded in `signals.rule` (`None`: no strong rule).
+  pub fn untraced_rule_counts(&self) -> Result<Vec<(Option<String>, i64)>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare(
+        "SELECT json_extract(l.signals_json,'$.rule') AS rule, COUNT(1) FROM labels l
+         WHERE NOT EXISTS (SELECT 1 FROM rule_traces t WHERE t.message_id=l.message_id)
+         GROUP BY rule",
+      )
+      .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
+    let mut out = vec![];
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+      out.push((r.get(0).map_err(|e| e.to_string())?, r.get(1).map_err(|e| e.to_string())?));
+    }
+    Ok(out)
+  }
+
+  /// The stored label with the rule trace of its last automatic run. The trace is kept
+  /// after a manual review: it explains what the rules decided, not the reviewer.
+  pub fn label_explain(&self, message_id: i64) -> Result<LabelExplain, String> {
+    let content = self.get_message_content(message_id)?;
+    let label = self.get_label(message_id)?;
+    let conn = self.db.conn();
+    let trace_json: Option<String> = conn
+      .query_row(
+        "SELECT trace_json FROM rule_traces WHERE message_id=?1",
+        params![message_id],
+        |r| r.get(0),
+      )
+      .optional()
+      .map_err(|e| e.to_string())?;
+    let trace = match trace_json {
+      Some(j) => Some(serde_json::from_str(&j).map_err(|e| e.to_string())?),
+      None => None,
+    };
+    Ok(LabelExplain {
+      message_id,
+      content,
+      label,
+      trace,
+    })
+  }
+
   pub fn label_update_manual(
     &self,
     message_id: i64,
     operator: &str,
-    new_label: LabelOutput,
+    mut new_label: LabelOutput,
   ) -> Result<(), String> {
     let before = self.get_label(message_id)?;
 
+    // Entity values the reviewer typed or changed become manual spans.
+    let content = self.get_message_content(message_id)?;
+    new_label
+      .entity_spans
+      .merge_flat(&new_label.entities, &content, EntitySource::Manual);
+    new_label.entity_spans.fill_derived(&mut new_label.entities);
+    // Risk is computed by the rules; a review edit keeps it unless the payload carries one.
+    if new_label.risk.is_none() {
+      new_label.risk = before.as_ref().and_then(|b| b.risk.clone());
+    }
+    // A reviewed label is the reviewer's, whatever fusion produced the one they started from.
+    new_label.fusion_policy = "n/a".to_string();
+
     let reasons_json = serde_json::to_string(&new_label.reasons).map_err(|e| e.to_string())?;
     let signals_json = serde_json::to_string(&new_label.signals).map_err(|e| e.to_string())?;
     let entities_json = serde_json::to_string(&new_label.entities).map_err(|e| e.to_string())?;
+    let secondary_types_json = serde_json::to_string(&new_label.secondary_types).map_err(|e| e.to_string())?;
+    let entity_spans_json = serde_json::to_string(&new_label.entity_spans).map_err(|e| e.to_string())?;
+    let (risk_score, risk_json) = risk_columns(new_label.risk.as_ref())?;
 
     let conn = self.db.conn();
     conn
       .execute(
-        "INSERT INTO labels(message_id, industry, sms_type, confidence, needs_review, reasons_json, signals_json, rules_version, model_version, schema_version, entities_json, updated_by, is_manual)
-         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,1)
+        "INSERT INTO labels(message_id, industry, sms_type, confidence, needs_review, reasons_json, signals_json, rules_version, model_version, schema_version, entities_json, secondary_types_json, entity_spans_json, risk_score, risk_json, prompt_version, fusion_policy, updated_by, is_manual)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,1)
          ON CONFLICT(message_id) DO UPDATE SET
            industry=excluded.industry,
            sms_type=excluded.sms_type,
@@ -169,6 +308,12 @@ impl<'a> Dao<'a> {
            model_version=excluded.model_version,
            schema_version=excluded.schema_version,
            entities_json=excluded.entities_json,
+           secondary_types_json=excluded.secondary_types_json,
+           entity_spans_json=excluded.entity_spans_json,
+           risk_score=excluded.risk_score,
+           risk_json=excluded.risk_json,
+           prompt_version=excluded.prompt_version,
+           fusion_policy=excluded.fusion_policy,
            updated_by=excluded.updated_by,
            updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now')),
            is_manual=1",
@@ -184,6 +329,12 @@ impl<'a> Dao<'a> {
           new_label.model_version,
           new_label.schema_version,
           entities_json,
+          secondary_types_json,
+          entity_spans_json,
+          risk_score,
+          risk_json,
+          new_label.prompt_version,
+          new_label.fusion_policy,
           operator
         ],
       )
@@ -202,6 +353,10 @@ impl<'a> Dao<'a> {
       )
       .map_err(|e| e.to_string())?;
 
+    if !new_label.needs_review {
+      learn_brand(&conn, &content, &new_label).map_err(|e| e.to_string())?;
+    }
+
     Ok(())
   }
 
@@ -214,7 +369,12 @@ impl<'a> Dao<'a> {
       args.push(industry.into());
     }
     if let Some(sms_type) = query.sms_type.clone().flatten() {
-      where_sql.push("l.sms_type = ?".to_string());
+      // Match the primary type or any secondary type.
+      where_sql.push(
+        "(l.sms_type = ? OR EXISTS (SELECT 1 FROM json_each(l.secondary_types_json) st WHERE json_extract(st.value, '$.type') = ?))"
+          .to_string(),
+      );
+      args.push(sms_type.clone().into());
       args.push(sms_type.into());
     }
     if let Some(needs_review) = query.needs_review {
@@ -229,6 +389,14 @@ impl<'a> Dao<'a> {
       where_sql.push("l.confidence <= ?".to_string());
       args.push(conf_max.into());
     }
+    if let Some(risk_min) = query.risk_min {
+      where_sql.push("l.risk_score >= ?".to_string());
+      args.push(risk_min.into());
+    }
+    if let Some(risk_max) = query.risk_max {
+      where_sql.push("l.risk_score <= ?".to_string());
+      args.push(risk_max.into());
+    }
     if let Some(has_url) = query.has_url {
       where_sql.push("m.has_url = ?".to_string());
       args.push((if has_url { 1 } else { 0 }).into());
@@ -241,6 +409,10 @@ impl<'a> Dao<'a> {
       where_sql.push("m.has_amount = ?".to_string());
       args.push((if has_amount { 1 } else { 0 }).into());
     }
+    if let Some(lang) = query.lang.clone().filter(|l| !l.is_empty()) {
+      where_sql.push("json_extract(l.signals_json, '$.lang') = ?".to_string());
+      args.push(lang.into());
+    }
     if let Some(q) = query.q.clone().flatten() {
       where_sql.push("(m.content LIKE ? OR m.sender LIKE ? OR m.source LIKE ?)".to_string());
       let like = format!("%{}%", q);
@@ -267,7 +439,7 @@ impl<'a> Dao<'a> {
 
     let list_sql = format!(
       "SELECT m.id, m.content, m.received_at, m.sender, m.phone, m.source, m.has_url, m.has_amount, m.has_verification_code,
-              l.industry, l.sms_type, l.confidence, l.needs_review, l.reasons_json, l.signals_json, l.rules_version, l.model_version, l.schema_version, l.entities_json
+              {LABEL_COLUMNS}
        FROM messages m
        LEFT JOIN labels l ON l.message_id=m.id
        {where_clause}
@@ -287,23 +459,10 @@ impl<'a> Dao<'a> {
     let mut rows: Vec<MessageRow> = vec![];
     while let Some(r) = rows_iter.next().map_err(|e| e.to_string())? {
       let industry_opt: Option<String> = r.get::<_, Option<String>>(9).map_err(|e| e.to_string())?;
-      let label_opt: Option<LabelOutput> = industry_opt.map(|industry| {
-        let reasons_json: String = r.get(13).unwrap_or_else(|_| "[]".to_string());
-        let signals_json: String = r.get(14).unwrap_or_else(|_| "{}".to_string());
-        let entities_json: String = r.get(18).unwrap_or_else(|_| "{}".to_string());
-        LabelOutput {
-          industry,
-          sms_type: r.get(10).unwrap_or_else(|_| "其他".to_string()),
-          confidence: r.get(11).unwrap_or(0.0),
-          needs_review: r.get::<_, Option<i32>>(12).unwrap_or(Some(1)).unwrap_or(1) != 0,
-          reasons: serde_json::from_str(&reasons_json).unwrap_or_default(),
-          signals: serde_json::from_str(&signals_json).unwrap_or_default(),
-          rules_version: r.get(15).unwrap_or_else(|_| "rules_v1".to_string()),
-          model_version: r.get(16).unwrap_or_else(|_| "n/a".to_string()),
-          schema_version: r.get(17).unwrap_or_else(|_| "schema_v1".to_string()),
-          entities: serde_json::from_str(&entities_json).unwrap_or_default(),
-        }
-      });
+      let label_opt: Option<LabelOutput> = match industry_opt {
+        Some(_) => Some(label_from_row(r, 9).map_err(|e| e.to_string())?),
+        None => None,
+      };
 
       rows.push(MessageRow {
         id: r.get(0).map_err(|e| e.to_string())?,
@@ -371,6 +530,317 @@ impl<'a> Dao<'a> {
     }
     Ok(ids)
   }
+
+  pub fn brands_list(&self) -> Result<Vec<BrandEntry>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare("SELECT alias, brand, industry, org_type, source FROM brand_registry ORDER BY brand ASC, alias ASC")
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], |r| {
+        Ok(BrandEntry {
+          alias: r.get(0)?,
+          brand: r.get(1)?,
+          industry: r.get(2)?,
+          org_type: r.get(3)?,
+          source: r.get(4)?,
+        })
+      })
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  /// Insert or replace a registry entry by hand; it is marked `manual` and never overwritten by learning.
+  pub fn brand_upsert(&self, entry: &BrandEntry) -> Result<(), String> {
+    let alias = normalize_alias(&entry.alias);
+    if alias.is_empty() || entry.brand.trim().is_empty() {
+      return Err("alias and brand are required".to_string());
+    }
+    if !INDUSTRIES.contains(&entry.industry.as_str()) {
+      return Err(format!("unknown industry: {}", entry.industry));
+    }
+    let org_type = entry.org_type.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    self
+      .db
+      .conn()
+      .execute(
+        "INSERT INTO brand_registry(alias, brand, industry, org_type, source) VALUES (?1,?2,?3,?4,'manual')
+         ON CONFLICT(alias) DO UPDATE SET
+           brand=excluded.brand,
+           industry=excluded.industry,
+           org_type=excluded.org_type,
+           source='manual',
+           updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now'))",
+        params![alias, entry.brand.trim(), entry.industry, org_type],
+      )
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  pub fn brand_delete(&self, alias: &str) -> Result<(), String> {
+    self
+      .db
+      .conn()
+      .execute("DELETE FROM brand_registry WHERE alias=?1", params![normalize_alias(alias)])
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  pub fn url_lists_list(&self) -> Result<Vec<UrlListEntry>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare("SELECT domain, list, note, source FROM url_lists ORDER BY list ASC, domain ASC")
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], |r| {
+        let list: String = r.get(1)?;
+        Ok(UrlListEntry {
+          domain: r.get(0)?,
+          list: if list == "deny" { UrlListKind::Deny } else { UrlListKind::Allow },
+          note: r.get(2)?,
+          source: r.get(3)?,
+        })
+      })
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  pub fn url_list_upsert(&self, entry: &UrlListEntry) -> Result<(), String> {
+    let domain = normalize_domain(&entry.domain);
+    if domain.is_empty() || !domain.contains('.') {
+      return Err(format!("invalid domain: {}", entry.domain));
+    }
+    let list = match entry.list {
+      UrlListKind::Allow => "allow",
+      UrlListKind::Deny => "deny",
+    };
+    let note = entry.note.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    self
+      .db
+      .conn()
+      .execute(
+        "INSERT INTO url_lists(domain, list, note, source) VALUES (?1,?2,?3,'manual')
+         ON CONFLICT(domain) DO UPDATE SET
+           list=excluded.list,
+           note=excluded.note,
+           source='manual',
+           updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now'))",
+        params![domain, list, note],
+      )
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  pub fn url_list_delete(&self, domain: &str) -> Result<(), String> {
+    self
+      .db
+      .conn()
+      .execute("DELETE FROM url_lists WHERE domain=?1", params![normalize_domain(domain)])
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  /// Every message with a reviewer-confirmed label (`is_manual=1`, `needs_review=0`), in message order.
+  pub fn reviewed_examples(&self) -> Result<Vec<FewShotExample>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare(
+        "SELECT m.id, m.content, l.industry, l.sms_type FROM labels l JOIN messages m ON m.id=l.message_id
+         WHERE l.is_manual=1 AND l.needs_review=0 ORDER BY m.id ASC",
+      )
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], |r| {
+        Ok(FewShotExample {
+          message_id: r.get(0)?,
+          content: r.get(1)?,
+          industry: r.get(2)?,
+          sms_type: r.get(3)?,
+        })
+      })
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  /// Record each source's raw label for the message (see `calibration::label_source`), replacing
+  /// what the same source said on an earlier run.
+  pub fn label_predictions_record(
+    &self,
+    message_id: i64,
+    predictions: &[(String, &LabelOutput)],
+  ) -> Result<(), String> {
+    let conn = self.db.conn();
+    for (source, label) in predictions {
+      conn
+        .execute(
+          "INSERT INTO label_predictions(message_id, source, industry, sms_type, confidence) VALUES (?1,?2,?3,?4,?5)
+           ON CONFLICT(message_id, source) DO UPDATE SET
+             industry=excluded.industry,
+             sms_type=excluded.sms_type,
+             confidence=excluded.confidence,
+             updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now'))",
+          params![message_id, source, label.industry, label.sms_type, label.confidence],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+  }
+
+  /// `(source, raw confidence, industry and type both kept by the reviewer)` for every recorded
+  /// prediction on a reviewer-confirmed message.
+  pub fn calibration_samples(&self) -> Result<Vec<(String, f64, bool)>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare(
+        "SELECT p.source, p.confidence, p.industry=l.industry AND p.sms_type=l.sms_type
+         FROM label_predictions p JOIN labels l ON l.message_id=p.message_id
+         WHERE l.is_manual=1 AND l.needs_review=0 ORDER BY p.source, p.message_id",
+      )
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], |r| Ok((r.get(0)?, r.get(1)?, r.get::<_, i32>(2)? != 0)))
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  /// `(source, curve_json)` of every fitted curve.
+  pub fn calibration_curves(&self) -> Result<Vec<(String, String)>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare("SELECT source, curve_json FROM calibration_curves ORDER BY source")
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], |r| Ok((r.get(0)?, r.get(1)?)))
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  /// Swap the stored curves for `(source, curve_json, samples)`; sources not listed lose theirs.
+  pub fn calibration_curves_replace(&self, curves: &[(String, String, i64)]) -> Result<(), String> {
+    let mut conn = self.db.conn();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM calibration_curves", params![])
+      .map_err(|e| e.to_string())?;
+    for (source, json, samples) in curves {
+      tx.execute(
+        "INSERT INTO calibration_curves(source, curve_json, samples) VALUES (?1,?2,?3)",
+        params![source, json, samples],
+      )
+      .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+  }
+
+  /// Stored templates, oldest first (the built-in one is not in the table).
+  pub fn prompt_templates_list(&self) -> Result<Vec<PromptTemplate>, String> {
+    let conn = self.db.conn();
+    let mut stmt = conn
+      .prepare("SELECT version, name, body, note, batch_body FROM prompt_templates ORDER BY created_at ASC, version ASC")
+      .map_err(|e| e.to_string())?;
+    let rows = stmt
+      .query_map(params![], prompt_template_from_row)
+      .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+  }
+
+  pub fn prompt_template_get(&self, version: &str) -> Result<Option<PromptTemplate>, String> {
+    self
+      .db
+      .conn()
+      .query_row(
+        "SELECT version, name, body, note, batch_body FROM prompt_templates WHERE version=?1",
+        params![version.trim()],
+        prompt_template_from_row,
+      )
+      .optional()
+      .map_err(|e| e.to_string())
+  }
+
+  /// Save a new template version. Versions are immutable: saving an existing one only updates its
+  /// name and note, and fails if either body differs.
+  pub fn prompt_template_save(&self, template: &PromptTemplate) -> Result<(), String> {
+    template.validate()?;
+    let version = template.version.trim();
+    if version == BUILTIN_PROMPT_VERSION {
+      return Err(format!("{BUILTIN_PROMPT_VERSION} is built in"));
+    }
+    let batch_body = template.batch_body.as_deref().filter(|b| !b.trim().is_empty());
+    if let Some(existing) = self.prompt_template_get(version)? {
+      if existing.body != template.body || existing.batch_body != batch_body.map(str::to_string) {
+        return Err(format!("prompt version {version} already exists; save the change as a new version"));
+      }
+    }
+    let note = template.note.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    self
+      .db
+      .conn()
+      .execute(
+        "INSERT INTO prompt_templates(version, name, body, note, batch_body) VALUES (?1,?2,?3,?4,?5)
+         ON CONFLICT(version) DO UPDATE SET name=excluded.name, note=excluded.note",
+        params![version, template.name.trim(), template.body, note, batch_body],
+      )
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  /// Labels keep the version id after the template is gone.
+  pub fn prompt_template_delete(&self, version: &str) -> Result<(), String> {
+    self
+      .db
+      .conn()
+      .execute("DELETE FROM prompt_templates WHERE version=?1", params![version.trim()])
+      .map_err(|e| e.to_string())?;
+    Ok(())
+  }
+
+  /// Backfill the registry from every reviewed manual label, oldest first so the latest review wins.
+  /// Returns the number of entries inserted or updated.
+  pub fn brands_learn_from_reviewed(&self) -> Result<i64, String> {
+    let conn = self.db.conn();
+    let sql = format!(
+      "SELECT m.content, {LABEL_COLUMNS} FROM labels l JOIN messages m ON m.id=l.message_id
+       WHERE l.is_manual=1 AND l.needs_review=0 ORDER BY l.updated_at ASC"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
+    let mut learned = 0i64;
+    while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+      let content: String = r.get(0).map_err(|e| e.to_string())?;
+      let label = label_from_row(r, 1).map_err(|e| e.to_string())?;
+      if learn_brand(&conn, &content, &label).map_err(|e| e.to_string())? {
+        learned += 1;
+      }
+    }
+    Ok(learned)
+  }
+}
+
+// A reviewed label teaches the registry its message's signature. Seed and manual entries are
+// never overwritten; learned ones follow the latest review.
+fn learn_brand(conn: &rusqlite::Connection, content: &str, label: &LabelOutput) -> rusqlite::Result<bool> {
+  let Some(sig) = extract_signature(&normalize_text(content)) else {
+    return Ok(false);
+  };
+  if label.industry == "其他" {
+    return Ok(false);
+  }
+  let brand = label
+    .entities
+    .brand
+    .as_deref()
+    .map(str::trim)
+    .filter(|b| !b.is_empty())
+    .unwrap_or(&sig.text);
+  let changed = conn.execute(
+    "INSERT INTO brand_registry(alias, brand, industry, source) VALUES (?1,?2,?3,'learned')
+     ON CONFLICT(alias) DO UPDATE SET
+       brand=excluded.brand,
+       industry=excluded.industry,
+       updated_at=(strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+     WHERE brand_registry.source='learned' AND (brand_registry.brand<>excluded.brand OR brand_registry.industry<>excluded.industry)",
+    params![normalize_alias(&sig.text), brand, label.industry],
+  )?;
+  Ok(changed > 0)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -380,32 +850,112 @@ pub struct ListQuery {
   pub needs_review: Option<bool>,
   pub conf_min: Option<f64>,
   pub conf_max: Option<f64>,
+  #[serde(default)]
+  pub risk_min: Option<f64>,
+  #[serde(default)]
+  pub risk_max: Option<f64>,
   pub has_url: Option<bool>,
   pub has_verification_code: Option<bool>,
   pub has_amount: Option<bool>,
+  /// `zh` / `en` / `mixed`, matched against `signals.lang`.
+  #[serde(default)]
+  pub lang: Option<String>,
   pub q: Option<Option<String>>,
   pub limit: i64,
   pub offset: i64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelExplain {
+  pub message_id: i64,
+  pub content: String,
+  pub label: Option<LabelOutput>,
+  pub trace: Option<RuleTrace>,
+}
+
+/// A stored rule trace and the message's current label, see [`Dao::rule_traces_scan`].
+#[derive(Debug, Clone)]
+pub struct TracedLabel {
+  pub message_id: i64,
+  pub trace: RuleTrace,
+  pub industry: Option<String>,
+  pub sms_type: Option<String>,
+  /// Confirmed by a reviewer (`is_manual=1`, `needs_review=0`).
+  pub reviewed: bool,
+  pub content: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListResult {
   pub total: i64,
   pub rows: Vec<MessageRow>,
 }
 
+/// Columns read by [`label_from_row`], in order. Expects the labels table aliased as `l`.
+pub const LABEL_COLUMNS: &str = "l.industry, l.sms_type, l.confidence, l.needs_review, l.reasons_json, l.signals_json, l.rules_version, l.model_version, l.schema_version, l.entities_json, l.secondary_types_json, l.entity_spans_json, l.risk_score, l.risk_json, l.prompt_version, l.fusion_policy";
+
+/// Build a `LabelOutput` from [`LABEL_COLUMNS`] starting at column `base`.
+pub fn label_from_row(r: &rusqlite::Row<'_>, base: usize) -> rusqlite::Result<LabelOutput> {
+  let reasons_json: String = r.get(base + 4)?;
+  let signals_json: String = r.get(base + 5)?;
+  let entities_json: String = r.get(base + 9)?;
+  let secondary_types_json: String = r.get(base + 10)?;
+  let entity_spans_json: String = r.get(base + 11)?;
+  let risk_score: Option<f64> = r.get(base + 12)?;
+  let risk_json: String = r.get(base + 13)?;
+  Ok(LabelOutput {
+    industry: r.get(base)?,
+    sms_type: r.get(base + 1)?,
+    secondary_types: serde_json::from_str(&secondary_types_json).unwrap_or_default(),
+    confidence: r.get(base + 2)?,
+    needs_review: r.get::<_, i32>(base + 3)? != 0,
+    reasons: serde_json::from_str(&reasons_json).unwrap_or_default(),
+    signals: serde_json::from_str(&signals_json).unwrap_or_default(),
+    rules_version: r.get(base + 6)?,
+    model_version: r.get(base + 7)?,
+    prompt_version: r.get(base + 14)?,
+    fusion_policy: r.get(base + 15)?,
+    schema_version: r.get(base + 8)?,
+    entities: serde_json::from_str(&entities_json).unwrap_or_default(),
+    entity_spans: serde_json::from_str(&entity_spans_json).unwrap_or_default(),
+    risk: risk_score.map(|score| RiskAssessment {
+      score,
+      contributions: serde_json::from_str(&risk_json).unwrap_or_default(),
+    }),
+  })
+}
+
+fn prompt_template_from_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<PromptTemplate> {
+  Ok(PromptTemplate {
+    version: r.get(0)?,
+    name: r.get(1)?,
+    body: r.get(2)?,
+    note: r.get(3)?,
+    batch_body: r.get(4)?,
+    builtin: false,
+  })
+}
+
+fn risk_columns(risk: Option<&RiskAssessment>) -> Result<(Option<f64>, String), String> {
+  let contributions = risk.map(|r| r.contributions.as_slice()).unwrap_or_default();
+  let json = serde_json::to_string(contributions).map_err(|e| e.to_string())?;
+  Ok((risk.map(|r| r.score), json))
+}
+
 fn compute_flags(content: &str) -> (bool, bool, bool) {
-  let url_re = Regex::new(r"https?://\S+|www\.[^\s]+\.[^\s]+" ).unwrap();
-  let amount_re = Regex::new(r"(￥|¥|RMB|CNY)\s*\d+(?:[\.,]\d+)?|\d+(?:[\.,]\d+)?\s*(元|块|人民币)" ).unwrap();
-  let code_re = Regex::new(r"\b\d{4,8}\b" ).unwrap();
+  // Same canonical text the rules see, so `１２３４５６` / `驗證碼` set the flags too.
+  let content = normalize_text(content);
+  let content = content.as_str();
 
-  let has_url = url_re.is_match(content);
-  let has_amount = amount_re.is_match(content);
-  let has_code = code_re.is_match(content) && (content.contains("验证码") || content.contains("校验码") || content.contains("动态码") || content.contains("OTP"));
+  let has_url = !find_urls(content, &UrlLists::default()).is_empty();
+  let has_amount = !crate::rules::amount::find_amounts(content).is_empty();
+  let has_code = CODE_FLAG_RE.is_match(content) && crate::rules::strong::CODE_KEYWORDS.iter().any(|k| content.contains(k));
 
   (has_url, has_amount, has_code)
 }
 
+static CODE_FLAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?-u:\b)\d{4,8}(?-u:\b)").unwrap());
+
 fn compute_diff(before: Option<&LabelOutput>, after: &LabelOutput) -> String {
   let mut diff: HashMap<String, serde_json::Value> = HashMap::new();
   diff.insert(
@@ -416,6 +966,10 @@ fn compute_diff(before: Option<&LabelOutput>, after: &LabelOutput) -> String {
     "sms_type".to_string(),
     serde_json::json!({"before": before.map(|b| b.sms_type.clone()), "after": after.sms_type}),
   );
+  diff.insert(
+    "secondary_types".to_string(),
+    serde_json::json!({"before": before.map(|b| &b.secondary_types), "after": &after.secondary_types}),
+  );
   diff.insert(
     "needs_review".to_string(),
     serde_json::json!({"before": before.map(|b| b.needs_review), "after": after.needs_review}),
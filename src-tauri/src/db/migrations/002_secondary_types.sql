@@ -0,0 +1,2 @@
+-- schema_v2: multi-label sms_type (secondary types with per-type scores)
+ALTER TABLE labels ADD COLUMN secondary_types_json TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- schema_v3: entity values as lists of {value, start, end, source}
+ALTER TABLE labels ADD COLUMN entity_spans_json TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,51 @@
+-- Brand registry: 【signature】 or alias -> canonical brand, industry, organization type
+-- source: seed (shipped) | manual (edited by a user) | learned (from reviewed labels)
+CREATE TABLE IF NOT EXISTS brand_registry (
+  alias TEXT PRIMARY KEY,
+  brand TEXT NOT NULL,
+  industry TEXT NOT NULL,
+  org_type TEXT NULL,
+  source TEXT NOT NULL DEFAULT 'manual',
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_brand_registry_brand ON brand_registry(brand);
+
+INSERT OR IGNORE INTO brand_registry(alias, brand, industry, org_type, source) VALUES
+  ('中国银行', '中国银行', '金融', '银行', 'seed'),
+  ('工商银行', '工商银行', '金融', '银行', 'seed'),
+  ('中国工商银行', '工商银行', '金融', '银行', 'seed'),
+  ('建设银行', '建设银行', '金融', '银行', 'seed'),
+  ('中国建设银行', '建设银行', '金融', '银行', 'seed'),
+  ('农业银行', '农业银行', '金融', '银行', 'seed'),
+  ('中国农业银行', '农业银行', '金融', '银行', 'seed'),
+  ('招商银行', '招商银行', '金融', '银行', 'seed'),
+  ('交通银行', '交通银行', '金融', '银行', 'seed'),
+  ('邮储银行', '邮储银行', '金融', '银行', 'seed'),
+  ('中国邮政储蓄银行', '邮储银行', '金融', '银行', 'seed'),
+  ('平安银行', '平安银行', '金融', '银行', 'seed'),
+  ('兴业银行', '兴业银行', '金融', '银行', 'seed'),
+  ('中信银行', '中信银行', '金融', '银行', 'seed'),
+  ('浦发银行', '浦发银行', '金融', '银行', 'seed'),
+  ('光大银行', '光大银行', '金融', '银行', 'seed'),
+  ('民生银行', '民生银行', '金融', '银行', 'seed'),
+  ('中国平安', '中国平安', '金融', '保险', 'seed'),
+  ('平安保险', '中国平安', '金融', '保险', 'seed'),
+  ('中国人寿', '中国人寿', '金融', '保险', 'seed'),
+  ('支付宝', '支付宝', '互联网', '支付', 'seed'),
+  ('微信支付', '微信', '互联网', '支付', 'seed'),
+  ('微信', '微信', '互联网', '社交', 'seed'),
+  ('京东', '京东', '互联网', '电商', 'seed'),
+  ('淘宝', '淘宝', '互联网', '电商', 'seed'),
+  ('拼多多', '拼多多', '互联网', '电商', 'seed'),
+  ('美团', '美团', '互联网', '本地生活', 'seed'),
+  ('饿了么', '饿了么', '互联网', '本地生活', 'seed'),
+  ('顺丰', '顺丰', '通用', '物流', 'seed'),
+  ('顺丰速运', '顺丰', '通用', '物流', 'seed'),
+  ('京东物流', '京东物流', '通用', '物流', 'seed'),
+  ('菜鸟', '菜鸟', '通用', '物流', 'seed'),
+  ('中国移动', '中国移动', '渠道', '运营商', 'seed'),
+  ('中国联通', '中国联通', '渠道', '运营商', 'seed'),
+  ('中国电信', '中国电信', '渠道', '运营商', 'seed'),
+  ('国家反诈中心', '国家反诈中心', '政务', '政府机关', 'seed'),
+  ('12345', '12345政务服务热线', '政务', '政府机关', 'seed');
@@ -0,0 +1,31 @@
+-- URL allow/deny lists by domain (a domain also covers its subdomains)
+-- list: allow (official domains) | deny (known phishing); source: seed | manual
+CREATE TABLE IF NOT EXISTS url_lists (
+  domain TEXT PRIMARY KEY,
+  list TEXT NOT NULL CHECK (list IN ('allow', 'deny')),
+  note TEXT NULL,
+  source TEXT NOT NULL DEFAULT 'manual',
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+INSERT OR IGNORE INTO url_lists(domain, list, note, source) VALUES
+  ('boc.cn', 'allow', '中国银行', 'seed'),
+  ('bankofchina.com', 'allow', '中国银行', 'seed'),
+  ('icbc.com.cn', 'allow', '工商银行', 'seed'),
+  ('ccb.com', 'allow', '建设银行', 'seed'),
+  ('abchina.com', 'allow', '农业银行', 'seed'),
+  ('cmbchina.com', 'allow', '招商银行', 'seed'),
+  ('bankcomm.com', 'allow', '交通银行', 'seed'),
+  ('psbc.com', 'allow', '邮储银行', 'seed'),
+  ('pingan.com', 'allow', '平安', 'seed'),
+  ('cib.com.cn', 'allow', '兴业银行', 'seed'),
+  ('citicbank.com', 'allow', '中信银行', 'seed'),
+  ('spdb.com.cn', 'allow', '浦发银行', 'seed'),
+  ('cebbank.com', 'allow', '光大银行', 'seed'),
+  ('cmbc.com.cn', 'allow', '民生银行', 'seed'),
+  ('95516.com', 'allow', '中国银联', 'seed'),
+  ('alipay.com', 'allow', '支付宝', 'seed'),
+  ('10086.cn', 'allow', '中国移动', 'seed'),
+  ('10010.com', 'allow', '中国联通', 'seed'),
+  ('189.cn', 'allow', '中国电信', 'seed'),
+  ('gov.cn', 'allow', '政府网站', 'seed');
@@ -0,0 +1,4 @@
+-- schema_v3 (no bump): optional rule-based phishing risk score with itemized contributions
+ALTER TABLE labels ADD COLUMN risk_score REAL NULL;
+ALTER TABLE labels ADD COLUMN risk_json TEXT NOT NULL DEFAULT '[]';
+CREATE INDEX IF NOT EXISTS idx_labels_risk_score ON labels(risk_score);
@@ -0,0 +1,7 @@
+-- Strong-rule evaluation trace of the latest automatic labeling, one row per message (label_explain)
+CREATE TABLE IF NOT EXISTS rule_traces (
+  message_id INTEGER PRIMARY KEY,
+  trace_json TEXT NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE
+);
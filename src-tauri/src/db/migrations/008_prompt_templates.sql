@@ -0,0 +1,11 @@
+-- Versioned prompt templates (the built-in one is compiled in) and the version behind each label
+CREATE TABLE IF NOT EXISTS prompt_templates (
+  version TEXT PRIMARY KEY,
+  name TEXT NOT NULL DEFAULT '',
+  body TEXT NOT NULL,
+  note TEXT NULL,
+  created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+ALTER TABLE labels ADD COLUMN prompt_version TEXT NOT NULL DEFAULT 'n/a';
+CREATE INDEX IF NOT EXISTS idx_labels_prompt_version ON labels(prompt_version);
@@ -0,0 +1,2 @@
+-- Optional multi-message variant of a prompt template (Provider::classify_many)
+ALTER TABLE prompt_templates ADD COLUMN batch_body TEXT NULL;
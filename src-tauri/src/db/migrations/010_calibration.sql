@@ -0,0 +1,48 @@
+-- Raw (uncalibrated) label of each source per message from its latest automatic run.
+-- source: rule:<strong rule> | rule:script:<name> | model:<model_version>
+CREATE TABLE IF NOT EXISTS label_predictions (
+  message_id INTEGER NOT NULL,
+  source TEXT NOT NULL,
+  industry TEXT NOT NULL,
+  sms_type TEXT NOT NULL,
+  confidence REAL NOT NULL,
+  updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  PRIMARY KEY (message_id, source),
+  FOREIGN KEY(message_id) REFERENCES messages(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_label_predictions_source ON label_predictions(source);
+
+-- Calibration curve per source, refit from reviewed labels on demand.
+CREATE TABLE IF NOT EXISTS calibration_curves (
+  source TEXT PRIMARY KEY,
+  curve_json TEXT NOT NULL,
+  samples INTEGER NOT NULL,
+  fitted_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+);
+
+-- Messages reviewed before predictions were recorded: the automatic label the first review replaced.
+-- That label is the fused one; after a rule/model conflict its confidence was penalized and either
+-- side may have produced it, so those rows are left out.
+INSERT OR IGNORE INTO label_predictions(message_id, source, industry, sms_type, confidence)
+SELECT message_id, source, industry, sms_type, confidence FROM (
+  SELECT a.message_id,
+    CASE
+      WHEN json_extract(a.before_json, '$.model_version') NOT IN ('n/a', 'error')
+        THEN 'model:' || json_extract(a.before_json, '$.model_version')
+      WHEN json_extract(a.before_json, '$.signals.script') IS NOT NULL
+        THEN 'rule:script:' || json_extract(a.before_json, '$.signals.script')
+      WHEN json_extract(a.before_json, '$.signals.rule') IS NOT NULL
+        THEN 'rule:' || json_extract(a.before_json, '$.signals.rule')
+    END AS source,
+    json_extract(a.before_json, '$.industry') AS industry,
+    json_extract(a.before_json, '$.type') AS sms_type,
+    json_extract(a.before_json, '$.confidence') AS confidence
+  FROM audit_logs a
+  WHERE a.before_json IS NOT NULL AND a.before_json != 'null'
+    AND a.id = (SELECT MIN(b.id) FROM audit_logs b WHERE b.message_id = a.message_id)
+    AND NOT EXISTS (
+      SELECT 1 FROM json_each(a.before_json, '$.reasons') r WHERE r.value LIKE 'fusion_conflict%'
+    )
+)
+WHERE source IS NOT NULL AND industry IS NOT NULL AND sms_type IS NOT NULL AND confidence IS NOT NULL;
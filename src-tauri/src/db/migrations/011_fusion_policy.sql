@@ -0,0 +1,2 @@
+-- Fusion policy that produced each automatic label ('n/a' for manual and older labels)
+ALTER TABLE labels ADD COLUMN fusion_policy TEXT NOT NULL DEFAULT 'n/a';
@@ -5,6 +5,19 @@ use rusqlite::{Connection, OpenFlags};
 
 pub mod dao;
 
+const MIGRATIONS: &[(i64, &str)] = &[
+  (2, include_str!("./migrations/002_secondary_types.sql")),
+  (3, include_str!("./migrations/003_entity_spans.sql")),
+  (4, include_str!("./migrations/004_brand_registry.sql")),
+  (5, include_str!("./migrations/005_url_lists.sql")),
+  (6, include_str!("./migrations/006_risk.sql")),
+  (7, include_str!("./migrations/007_rule_traces.sql")),
+  (8, include_str!("./migrations/008_prompt_templates.sql")),
+  (9, include_str!("./migrations/009_prompt_batch_body.sql")),
+  (10, include_str!("./migrations/010_calibration.sql")),
+  (11, include_str!("./migrations/011_fusion_policy.sql")),
+];
+
 pub struct Db {
   path: PathBuf,
   conn: Mutex<Connection>,
@@ -35,8 +48,26 @@ impl Db {
   }
 
   pub fn migrate(&self) -> Result<(), String> {
+    let conn = self.conn.lock();
     let sql = include_str!("./migrations/001_init.sql");
-    self.conn.lock().execute_batch(sql).map_err(|e| e.to_string())
+    conn.execute_batch(sql).map_err(|e| e.to_string())?;
+
+    // Incremental migrations are tracked with `PRAGMA user_version` (001 is idempotent and always runs).
+    let current: i64 = conn
+      .query_row("PRAGMA user_version", [], |r| r.get(0))
+      .map_err(|e| e.to_string())?;
+    for (version, sql) in MIGRATIONS {
+      if *version <= current {
+        continue;
+      }
+      conn
+        .execute_batch(&format!("BEGIN;\n{sql}\nPRAGMA user_version = {version};\nCOMMIT;"))
+        .map_err(|e| {
+          let _ = conn.execute_batch("ROLLBACK;");
+          format!("migration {version} failed: {e}")
+        })?;
+    }
+    Ok(())
   }
 
   pub fn dao(&self) -> dao::Dao<'_> {
@@ -3,12 +3,16 @@ use std::{fs::File, io::Write, path::PathBuf};
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 
-use crate::{db::Db, model::schema::LabelOutput};
+use crate::{
+  db::{dao::{label_from_row, LABEL_COLUMNS}, Db},
+  model::schema::{MoneyDirection, SMS_TYPES},
+  rules::fixtures::Fixture,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportOptions {
   pub only_reviewed: bool,
-  pub format: String, // csv/jsonl
+  pub format: String, // csv/jsonl/fixtures
 }
 
 pub fn execute(db: &Db, path: PathBuf, options: ExportOptions) -> Result<i64, String> {
@@ -16,7 +20,9 @@ pub fn execute(db: &Db, path: PathBuf, options: ExportOptions) -> Result<i64, St
   match fmt.as_str() {
     "csv" => export_csv(db, path, options.only_reviewed),
     "jsonl" => export_jsonl(db, path, options.only_reviewed),
-    _ => Err("unsupported export format (csv/jsonl)".to_string()),
+    // Fixtures are ground truth, so only reviewed labels qualify.
+    "fixtures" => export_fixtures(db, path),
+    _ => Err("unsupported export format (csv/jsonl/fixtures)".to_string()),
   }
 }
 
@@ -24,35 +30,18 @@ fn export_jsonl(db: &Db, path: PathBuf, only_reviewed: bool) -> Result<i64, Stri
   let mut file = File::create(path).map_err(|e| e.to_string())?;
 
   let sql = if only_reviewed {
-    "SELECT l.reasons_json, l.signals_json, l.entities_json, l.industry, l.sms_type, l.confidence, l.needs_review, l.rules_version, l.model_version, l.schema_version
-     FROM labels l WHERE l.needs_review=0 ORDER BY l.message_id ASC"
+    format!("SELECT {LABEL_COLUMNS} FROM labels l WHERE l.needs_review=0 ORDER BY l.message_id ASC")
   } else {
-    "SELECT l.reasons_json, l.signals_json, l.entities_json, l.industry, l.sms_type, l.confidence, l.needs_review, l.rules_version, l.model_version, l.schema_version
-     FROM labels l ORDER BY l.message_id ASC"
+    format!("SELECT {LABEL_COLUMNS} FROM labels l ORDER BY l.message_id ASC")
   };
 
   let conn = db.conn();
-  let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+  let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
   let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
 
   let mut written = 0i64;
   while let Some(r) = rows.next().map_err(|e| e.to_string())? {
-    let reasons_json: String = r.get(0).map_err(|e| e.to_string())?;
-    let signals_json: String = r.get(1).map_err(|e| e.to_string())?;
-    let entities_json: String = r.get(2).map_err(|e| e.to_string())?;
-
-    let label = LabelOutput {
-      industry: r.get(3).map_err(|e| e.to_string())?,
-      sms_type: r.get(4).map_err(|e| e.to_string())?,
-      confidence: r.get(5).map_err(|e| e.to_string())?,
-      needs_review: r.get::<_, i32>(6).map_err(|e| e.to_string())? != 0,
-      reasons: serde_json::from_str(&reasons_json).unwrap_or_default(),
-      signals: serde_json::from_str(&signals_json).unwrap_or_default(),
-      rules_version: r.get(7).map_err(|e| e.to_string())?,
-      model_version: r.get(8).map_err(|e| e.to_string())?,
-      schema_version: r.get(9).map_err(|e| e.to_string())?,
-      entities: serde_json::from_str(&entities_json).unwrap_or_default(),
-    };
+    let label = label_from_row(r, 0).map_err(|e| e.to_string())?;
 
     let line = serde_json::to_string(&label).map_err(|e| e.to_string())?;
     file.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
@@ -63,73 +52,179 @@ fn export_jsonl(db: &Db, path: PathBuf, only_reviewed: bool) -> Result<i64, Stri
   Ok(written)
 }
 
+/// Reviewed labels as rule fixtures (see `rules::fixtures`), one JSON object per line.
+fn export_fixtures(db: &Db, path: PathBuf) -> Result<i64, String> {
+  let mut file = File::create(path).map_err(|e| e.to_string())?;
+
+  let sql = format!(
+    "SELECT m.id, m.content, m.sender, m.received_at, {LABEL_COLUMNS} FROM labels l JOIN messages m ON m.id=l.message_id
+     WHERE l.needs_review=0 ORDER BY l.message_id ASC"
+  );
+
+  let conn = db.conn();
+  let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+  let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
+
+  let mut written = 0i64;
+  while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+    let id: i64 = r.get(0).map_err(|e| e.to_string())?;
+    let label = label_from_row(r, 4).map_err(|e| e.to_string())?;
+    let fixture = Fixture::from_label(
+      format!("message-{id}"),
+      r.get(1).map_err(|e| e.to_string())?,
+      r.get(2).map_err(|e| e.to_string())?,
+      r.get(3).map_err(|e| e.to_string())?,
+      &label,
+    );
+
+    let line = serde_json::to_string(&fixture).map_err(|e| e.to_string())?;
+    file.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+    file.write_all(b"\n").map_err(|e| e.to_string())?;
+    written += 1;
+  }
+
+  Ok(written)
+}
+
 fn export_csv(db: &Db, path: PathBuf, only_reviewed: bool) -> Result<i64, String> {
   let mut wtr = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
 
-  wtr
-    .write_record([
-      "industry",
-      "type",
+  let mut header: Vec<String> = ["industry", "type"].iter().map(|s| s.to_string()).collect();
+  // Multi-hot encoding: one 0/1 column per type, covering primary and secondary types.
+  header.extend(SMS_TYPES.iter().map(|t| format!("type_{t}")));
+  header.extend(
+    [
+      "secondary_types",
       "confidence",
       "needs_review",
+      "risk_score",
+      "risk_contributions",
       "brand",
       "verification_code",
       "amount",
+      "amount_currency",
+      "amount_direction",
       "balance",
+      "balance_currency",
       "account_suffix",
       "time_text",
+      "deadline",
+      "valid_until",
       "url",
       "phone_in_text",
       "rules_version",
       "model_version",
+      "prompt_version",
+      "fusion_policy",
       "schema_version",
       "reasons",
-    ])
-    .map_err(|e| e.to_string())?;
+    ]
+    .iter()
+    .map(|s| s.to_string()),
+  );
+  // Custom entities get one `extra.<name>` column each, for every name seen in the exported labels.
+  let extra_names = extra_entity_names(db, only_reviewed)?;
+  header.extend(extra_names.iter().map(|n| format!("extra.{n}")));
+  header.push("entity_spans".to_string());
+  wtr.write_record(&header).map_err(|e| e.to_string())?;
 
   let sql = if only_reviewed {
-    "SELECT l.industry, l.sms_type, l.confidence, l.needs_review, l.entities_json, l.rules_version, l.model_version, l.schema_version, l.reasons_json
-     FROM labels l WHERE l.needs_review=0 ORDER BY l.message_id ASC"
+    format!("SELECT {LABEL_COLUMNS} FROM labels l WHERE l.needs_review=0 ORDER BY l.message_id ASC")
   } else {
-    "SELECT l.industry, l.sms_type, l.confidence, l.needs_review, l.entities_json, l.rules_version, l.model_version, l.schema_version, l.reasons_json
-     FROM labels l ORDER BY l.message_id ASC"
+    format!("SELECT {LABEL_COLUMNS} FROM labels l ORDER BY l.message_id ASC")
   };
 
   let conn = db.conn();
-  let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+  let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
   let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
 
   let mut written = 0i64;
   while let Some(r) = rows.next().map_err(|e| e.to_string())? {
-    let entities_json: String = r.get(4).map_err(|e| e.to_string())?;
-    let reasons_json: String = r.get(8).map_err(|e| e.to_string())?;
-    let entities: crate::model::schema::Entities = serde_json::from_str(&entities_json).unwrap_or_default();
-
-    wtr
-      .write_record([
-        r.get::<_, String>(0).unwrap_or_else(|_| "".to_string()),
-        r.get::<_, String>(1).unwrap_or_else(|_| "".to_string()),
-        format!("{:.4}", r.get::<_, f64>(2).unwrap_or(0.0)),
-        (r.get::<_, i32>(3).unwrap_or(1) != 0).to_string(),
-        entities.brand.unwrap_or_default(),
-        entities.verification_code.unwrap_or_default(),
-        entities.amount.map(|v| v.to_string()).unwrap_or_default(),
-        entities.balance.map(|v| v.to_string()).unwrap_or_default(),
-        entities.account_suffix.unwrap_or_default(),
-        entities.time_text.unwrap_or_default(),
-        entities.url.unwrap_or_default(),
-        entities.phone_in_text.unwrap_or_default(),
-        r.get::<_, String>(5).unwrap_or_else(|_| "".to_string()),
-        r.get::<_, String>(6).unwrap_or_else(|_| "".to_string()),
-        r.get::<_, String>(7).unwrap_or_else(|_| "".to_string()),
-        serde_json::from_str::<Vec<String>>(&reasons_json)
-          .unwrap_or_default()
-          .join(" | "),
-      ])
-      .map_err(|e| e.to_string())?;
+    let label = label_from_row(r, 0).map_err(|e| e.to_string())?;
+    let all_types = label.all_types();
+    let entities = label.entities.clone();
+
+    let mut record: Vec<String> = vec![label.industry.clone(), label.sms_type.clone()];
+    record.extend(
+      SMS_TYPES
+        .iter()
+        .map(|t| if all_types.contains(t) { "1" } else { "0" }.to_string()),
+    );
+    record.extend([
+      label
+        .secondary_types
+        .iter()
+        .map(|t| format!("{}:{:.2}", t.sms_type, t.score))
+        .collect::<Vec<_>>()
+        .join(" | "),
+      format!("{:.4}", label.confidence),
+      label.needs_review.to_string(),
+      label.risk.as_ref().map(|r| format!("{:.4}", r.score)).unwrap_or_default(),
+      label
+        .risk
+        .as_ref()
+        .map(|r| {
+          r.contributions
+            .iter()
+            .map(|c| format!("{}:+{:.2}", c.signal, c.weight))
+            .collect::<Vec<_>>()
+            .join(" | ")
+        })
+        .unwrap_or_default(),
+      entities.brand.unwrap_or_default(),
+      entities.verification_code.unwrap_or_default(),
+      entities.amount.map(|v| v.to_string()).unwrap_or_default(),
+      entities.amount_detail.as_ref().map(|m| m.currency.clone()).unwrap_or_default(),
+      entities
+        .amount_detail
+        .as_ref()
+        .and_then(|m| m.direction)
+        .map(|d| match d {
+          MoneyDirection::Debit => "debit",
+          MoneyDirection::Credit => "credit",
+        })
+        .unwrap_or_default()
+        .to_string(),
+      entities.balance.map(|v| v.to_string()).unwrap_or_default(),
+      entities.balance_detail.as_ref().map(|m| m.currency.clone()).unwrap_or_default(),
+      entities.account_suffix.unwrap_or_default(),
+      entities.time_text.unwrap_or_default(),
+      entities.deadline.unwrap_or_default(),
+      entities.valid_until.unwrap_or_default(),
+      entities.url.unwrap_or_default(),
+      entities.phone_in_text.unwrap_or_default(),
+      label.rules_version.clone(),
+      label.model_version.clone(),
+      label.prompt_version.clone(),
+      label.fusion_policy.clone(),
+      label.schema_version.clone(),
+      label.reasons.join(" | "),
+    ]);
+    record.extend(extra_names.iter().map(|n| match entities.extra.get(n) {
+      None | Some(serde_json::Value::Null) => String::new(),
+      Some(serde_json::Value::String(s)) => s.clone(),
+      Some(v) => v.to_string(),
+    }));
+    record.push(serde_json::to_string(&label.entity_spans).unwrap_or_else(|_| "{}".to_string()));
+    wtr.write_record(&record).map_err(|e| e.to_string())?;
     written += 1;
   }
 
   wtr.flush().map_err(|e| e.to_string())?;
   Ok(written)
 }
+
+fn extra_entity_names(db: &Db, only_reviewed: bool) -> Result<Vec<String>, String> {
+  let sql = format!(
+    "SELECT DISTINCT e.key FROM labels l, json_each(l.entities_json, '$.extra') e {} ORDER BY e.key",
+    if only_reviewed { "WHERE l.needs_review=0" } else { "" }
+  );
+  let conn = db.conn();
+  let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+  let mut rows = stmt.query(params![]).map_err(|e| e.to_string())?;
+  let mut names = vec![];
+  while let Some(r) = rows.next().map_err(|e| e.to_string())? {
+    names.push(r.get::<_, String>(0).map_err(|e| e.to_string())?);
+  }
+  Ok(names)
+}
@@ -18,11 +18,16 @@ use tauri::{AppHandle, Manager};
 use crate::{
   db::Db,
   model::{
-    fusion::{self, FusionInput},
+    consistency::SelfConsistencyProvider,
+    calibration::{label_source, Calibrator},
+    fewshot::FewShotIndex,
+    fusion::{self, FusionInput, FusionPolicy},
+    llama_server,
+    prompt::PromptTemplate,
     provider::{self, Provider},
     schema::{ClassifyPayload, LabelOutput},
   },
-  rules,
+  rules::{self, RulePack, RuleResult},
   settings::SettingsStore,
 };
 
@@ -34,6 +39,28 @@ pub struct BatchOptions {
   pub max_retries: i32,
   pub id_min: Option<i64>,
   pub id_max: Option<i64>,
+  /// Messages per model call for providers whose prompt template has a batch body; 1 disables.
+  #[serde(default = "default_batch_size")]
+  pub batch_size: usize,
+  /// Self-consistency samples per message; above 1 the majority of that many answers is kept and
+  /// the agreement ratio becomes the confidence.
+  #[serde(default = "default_samples")]
+  pub samples: usize,
+  /// Provider temperature while sampling (the settings value is used when `samples` is 1).
+  #[serde(default = "default_sample_temperature")]
+  pub sample_temperature: f32,
+}
+
+fn default_batch_size() -> usize {
+  8
+}
+
+fn default_samples() -> usize {
+  1
+}
+
+fn default_sample_temperature() -> f32 {
+  0.7
 }
 
 impl Default for BatchOptions {
@@ -45,6 +72,9 @@ impl Default for BatchOptions {
       max_retries: 1,
       id_min: None,
       id_max: None,
+      batch_size: default_batch_size(),
+      samples: default_samples(),
+      sample_temperature: default_sample_temperature(),
     }
   }
 }
@@ -123,6 +153,19 @@ impl BatchManager {
   }
 
   pub fn start(self: &Arc<Self>, options: BatchOptions, app: AppHandle) -> Result<(), String> {
+    // Compile the rule pack up front so a bad custom regex fails the start instead of every message.
+    let pack = Arc::new(RulePack::load(&self.settings.get(), &self.db)?);
+    let (few_shot, prompt, calibrator, fusion) = {
+      let s = self.settings.get();
+      let p = &s.provider;
+      (
+        Arc::new(FewShotIndex::load(&self.db, p.few_shot_k, p.few_shot_token_budget)?),
+        PromptTemplate::resolve(&self.db, p.prompt_version.as_deref())?,
+        Arc::new(Calibrator::load(&self.db, &s.calibration)?),
+        Arc::new(FusionPolicy::from_settings(&s.fusion)?),
+      )
+    };
+
     {
       let mut inner = self.inner.lock();
       if inner.progress.running {
@@ -151,13 +194,23 @@ impl BatchManager {
 
     let mgr = Arc::clone(self);
     thread::spawn(move || {
-      mgr.run_loop(options, app);
+      mgr.run_loop(options, pack, few_shot, prompt, calibrator, fusion, app);
     });
 
     Ok(())
   }
 
-  fn run_loop(self: Arc<Self>, options: BatchOptions, app: AppHandle) {
+  #[allow(clippy::too_many_arguments)]
+  fn run_loop(
+    self: Arc<Self>,
+    options: BatchOptions,
+    pack: Arc<RulePack>,
+    few_shot: Arc<FewShotIndex>,
+    prompt: PromptTemplate,
+    calibrator: Arc<Calibrator>,
+    fusion: Arc<FusionPolicy>,
+    app: AppHandle,
+  ) {
     let stop = { self.inner.lock().stop.clone() };
     let started = Instant::now();
 
@@ -172,9 +225,19 @@ impl BatchManager {
     let worker_n = options.concurrency.clamp(1, 8);
     let timeout = Duration::from_millis(options.timeout_ms.max(1000));
     let max_retries = options.max_retries.max(0);
+    let batch_size = options.batch_size.clamp(1, 32);
 
     // Snapshot provider (per worker) from settings at start.
-    let settings_snapshot = self.settings.get().clone();
+    let mut settings_snapshot = self.settings.get().clone();
+    // The mock provider answers the same every time; sampling it would only fake agreement.
+    let samples = match provider::parse_kind(&settings_snapshot.provider.kind) {
+      provider::ProviderKind::Mock => 1,
+      _ => options.samples.clamp(1, 16),
+    };
+    if samples > 1 {
+      // Identical samples would agree trivially; sampling needs a temperature above zero.
+      settings_snapshot.provider.temperature = options.sample_temperature.max(0.1);
+    }
 
     for _ in 0..worker_n {
       let rx_job = rx_job.clone();
@@ -182,7 +245,12 @@ impl BatchManager {
       let db = self.db.clone();
       let log_dir = self.log_dir.clone();
       let stop2 = stop.clone();
-      let provider_res = provider::build_provider(&settings_snapshot);
+      let provider_res = provider::build_provider(&settings_snapshot, &prompt)
+        .map(|p| SelfConsistencyProvider::wrap(p, samples));
+      let pack = pack.clone();
+      let few_shot = few_shot.clone();
+      let calibrator = calibrator.clone();
+      let fusion = fusion.clone();
 
       let rule_strong_hits2 = rule_strong_hits.clone();
       let model_calls2 = model_calls.clone();
@@ -209,30 +277,50 @@ impl BatchManager {
           }
         };
 
+        // Only group messages when one call can answer several of them.
+        let group_size = match &provider {
+          Some(p) if p.supports_batch() => batch_size,
+          _ => 1,
+        };
+
         loop {
-          let id = {
+          let ids = {
             let guard = rx_job.lock();
-            match guard.recv() {
-              Ok(v) => v,
+            let mut ids = match guard.recv() {
+              Ok(v) => vec![v],
               Err(_) => break,
+            };
+            while ids.len() < group_size {
+              match guard.try_recv() {
+                Ok(v) => ids.push(v),
+                Err(_) => break,
+              }
             }
+            ids
           };
 
           if stop2.load(Ordering::SeqCst) {
-            let _ = tx_res.send((id, Err("stopped".to_string())));
+            for id in ids {
+              let _ = tx_res.send((id, Err("stopped".to_string())));
+            }
             continue;
           }
 
-          let res = process_one(
+          for (id, res) in process_group(
             &db,
+            &pack,
+            &few_shot,
+            &calibrator,
+            &fusion,
             provider.as_deref(),
             &log_dir,
             Some(&hook),
-            id,
+            &ids,
             timeout,
             max_retries,
-          );
-          let _ = tx_res.send((id, res));
+          ) {
+            let _ = tx_res.send((id, res));
+          }
         }
       });
     }
@@ -306,6 +394,9 @@ impl BatchManager {
       inner.progress.model_failures = model_failures.load(Ordering::Relaxed);
     }
 
+    // A managed llama-server holds the model in memory; free it until the next batch.
+    llama_server::shutdown();
+
     self.emit_progress(&app);
   }
 
@@ -330,18 +421,109 @@ impl BatchManager {
   }
 }
 
-fn process_one(
+type ProgressHook<'a> = Option<&'a (dyn Fn(BatchProgressDelta) + Send + Sync)>;
+
+// Rules have run and the model payload is ready; the label is written by `finish_one`.
+struct Prepared {
+  message_id: i64,
+  rule: RuleResult,
+  payload: ClassifyPayload,
+  /// False when the fusion policy would keep the rule label whatever the model said.
+  consult_model: bool,
+}
+
+/// Label `ids`. Strong rule hits are finished without the model; the rest go to the provider in
+/// one `classify_many` call when there are several, and any message the batched answer misses is
+/// retried on its own.
+#[allow(clippy::too_many_arguments)]
+fn process_group(
   db: &Db,
+  pack: &RulePack,
+  few_shot: &FewShotIndex,
+  calibrator: &Calibrator,
+  fusion: &FusionPolicy,
   provider: Option<&dyn Provider>,
   log_dir: &PathBuf,
-  progress_hook: Option<&(dyn Fn(BatchProgressDelta) + Send + Sync)>,
-  message_id: i64,
+  progress_hook: ProgressHook<'_>,
+  ids: &[i64],
   timeout: Duration,
   max_retries: i32,
-) -> Result<(), String> {
+) -> Vec<(i64, Result<(), String>)> {
+  let mut out = Vec::with_capacity(ids.len());
+  let mut model_bound: Vec<Prepared> = vec![];
+  for &id in ids {
+    match prepare_one(db, pack, few_shot, fusion, progress_hook, id) {
+      Ok(p) if !p.consult_model => {
+        out.push((id, finish_one(db, calibrator, fusion, log_dir, progress_hook, p, Ok(None))))
+      }
+      Ok(p) => model_bound.push(p),
+      Err(e) => out.push((id, Err(e))),
+    }
+  }
+  if model_bound.is_empty() {
+    return out;
+  }
+
+  if let Some(h) = progress_hook {
+    for _ in &model_bound {
+      h(BatchProgressDelta::ModelCall);
+    }
+  }
+  let Some(provider) = provider else {
+    for p in model_bound {
+      let id = p.message_id;
+      out.push((id, finish_one(db, calibrator, fusion, log_dir, progress_hook, p, Err("provider unavailable".to_string()))));
+    }
+    return out;
+  };
+
+  let batched: Vec<Option<LabelOutput>> = if model_bound.len() > 1 && provider.supports_batch() {
+    let payloads: Vec<ClassifyPayload> = model_bound.iter().map(|p| p.payload.clone()).collect();
+    provider
+      .classify_many(&payloads, timeout * payloads.len() as u32)
+      .into_iter()
+      .zip(&model_bound)
+      .map(|(r, p)| match r {
+        Ok(v) => Some(v),
+        Err(e) => {
+          let _ = append_log(
+            log_dir,
+            &format!("message_id={} batched classify failed, retrying alone: {e}", p.message_id),
+          );
+          None
+        }
+      })
+      .collect()
+  } else {
+    vec![None; model_bound.len()]
+  };
+
+  for (p, label) in model_bound.into_iter().zip(batched) {
+    let model = match label {
+      Some(v) => Ok(v),
+      None => classify_with_retries(provider, &p.payload, timeout, max_retries),
+    };
+    let id = p.message_id;
+    out.push((id, finish_one(db, calibrator, fusion, log_dir, progress_hook, p, model.map(Some))));
+  }
+  out
+}
+
+fn prepare_one(
+  db: &Db,
+  pack: &RulePack,
+  few_shot: &FewShotIndex,
+  fusion: &FusionPolicy,
+  progress_hook: ProgressHook<'_>,
+  message_id: i64,
+) -> Result<Prepared, String> {
   let content = db.dao().get_message_content(message_id)?;
+  let (sender, received_at) = db.dao().get_message_meta(message_id)?;
 
-  let rule = rules::run_rules(&content, None);
+  let rule = rules::run_rules_traced(pack, &content, sender.as_deref(), received_at.as_deref());
+  if let Some(trace) = &rule.trace {
+    db.dao().rule_trace_upsert(message_id, trace)?;
+  }
 
   if let Some(h) = progress_hook {
     if rule.strong_hit {
@@ -349,74 +531,120 @@ fn process_one(
     }
   }
 
+  let consult_model = fusion.consults_model(&rule);
   let payload = ClassifyPayload {
     message_id,
     content: content.clone(),
     entities: rule.entities.clone(),
+    entity_spans: rule.entity_spans.clone(),
     signals: rule.signals.clone(),
+    extra_entity_fields: pack.extra_entity_fields(),
+    // Retrieval only pays off when the model is actually asked.
+    examples: if consult_model {
+      few_shot.select(message_id, &content)
+    } else {
+      vec![]
+    },
   };
 
-  let rule_label = rule.label;
-
-  let model_label: Option<LabelOutput> = if rule.strong_hit {
-    None
-  } else {
-    if let Some(h) = progress_hook {
-      h(BatchProgressDelta::ModelCall);
-    }
+  Ok(Prepared {
+    message_id,
+    rule,
+    payload,
+    consult_model,
+  })
+}
 
-    let provider = provider.ok_or_else(|| "provider unavailable".to_string());
-    let provider = match provider {
-      Ok(p) => p,
+fn classify_with_retries(
+  provider: &dyn Provider,
+  payload: &ClassifyPayload,
+  timeout: Duration,
+  max_retries: i32,
+) -> Result<LabelOutput, String> {
+  let mut last_err: Option<String> = None;
+  for attempt in 0..=max_retries {
+    match provider.classify(payload, timeout) {
+      Ok(v) => return Ok(v),
       Err(e) => {
-        if let Some(h) = progress_hook {
-          h(BatchProgressDelta::ModelFailure);
-        }
-        let fallback = LabelOutput::error_fallback(rule.entities.clone(), rule.signals.clone(), &e);
-        let _ = db.dao().upsert_label_auto(message_id, &fallback);
-        let _ = append_log(log_dir, &format!("message_id={message_id} provider unavailable: {e}"));
-        return Err(e);
-      }
-    };
-
-    let mut got: Option<LabelOutput> = None;
-    let mut last_err: Option<String> = None;
-
-    for attempt in 0..=max_retries {
-      match provider.classify(&payload, timeout) {
-        Ok(v) => {
-          got = Some(v);
-          last_err = None;
-          break;
-        }
-        Err(e) => {
-          last_err = Some(e);
-          if attempt < max_retries {
-            thread::sleep(Duration::from_millis(120));
-          }
+        last_err = Some(e);
+        if attempt < max_retries {
+          thread::sleep(Duration::from_millis(120));
         }
       }
     }
+  }
+  Err(last_err.unwrap_or_else(|| "unknown provider error".to_string()))
+}
+
+/// `model` is `Ok(None)` for strong rule hits the fusion policy settles alone. A model error
+/// stores the error fallback label and fails the message, unless a rule label can stand in.
+fn finish_one(
+  db: &Db,
+  calibrator: &Calibrator,
+  fusion: &FusionPolicy,
+  log_dir: &PathBuf,
+  progress_hook: ProgressHook<'_>,
+  prepared: Prepared,
+  model: Result<Option<LabelOutput>, String>,
+) -> Result<(), String> {
+  let Prepared { message_id, rule, .. } = prepared;
 
-    if got.is_none() {
-      let e = last_err.unwrap_or_else(|| "unknown provider error".to_string());
+  let model_label = match model {
+    Ok(v) => v,
+    Err(e) => {
       if let Some(h) = progress_hook {
         h(BatchProgressDelta::ModelFailure);
       }
-      let fallback = LabelOutput::error_fallback(rule.entities.clone(), rule.signals.clone(), &e);
-      let _ = db.dao().upsert_label_auto(message_id, &fallback);
       let _ = append_log(log_dir, &format!("message_id={message_id} classify failed: {e}"));
-      return Err(e);
+      if rule.label.is_some() {
+        // The policy wanted a second opinion on a rule hit; the rule alone still labels it.
+        None
+      } else {
+        let fallback = LabelOutput::error_fallback(
+          rule.entities.clone(),
+          rule.entity_spans.clone(),
+          rule.signals.clone(),
+          &e,
+        );
+        let fallback = LabelOutput {
+          risk: Some(rule.risk.clone()),
+          ..fallback
+        };
+        let _ = db.dao().upsert_label_auto(message_id, &fallback);
+        return Err(e);
+      }
     }
-
-    got
   };
 
-  let fused = fusion::fuse(FusionInput {
-    rule: rule_label,
-    model: model_label,
-    rule_strong_hit: rule.strong_hit,
-  });
+  // Raw labels are what calibration is fitted on; fusion compares the calibrated ones.
+  let mut rule_label = rule.label;
+  let mut model_label = model_label;
+  let predictions: Vec<(String, &LabelOutput)> = rule_label
+    .iter()
+    .chain(model_label.iter())
+    .filter_map(|l| label_source(l).map(|s| (s, l)))
+    .collect();
+  db.dao().label_predictions_record(message_id, &predictions)?;
+  for l in rule_label.iter_mut().chain(model_label.iter_mut()) {
+    calibrator.calibrate(l);
+  }
+
+  let mut fused = fusion::fuse(
+    FusionInput {
+      rule: rule_label,
+      model: model_label,
+      rule_strong_hit: rule.strong_hit,
+    },
+    fusion,
+  );
+  // Risk comes from the rules alone, whichever side produced the type.
+  fused.risk = Some(rule.risk);
+  rules::risk::flag_high_risk(&mut fused);
+  for e in rule.script_errors {
+    if !fused.reasons.contains(&e) {
+      fused.reasons.push(e);
+    }
+  }
 
   db.dao().upsert_label_auto(message_id, &fused)?;
   Ok(())
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{db::Db, model::schema::LabelOutput};
+
+/// Calibration settings (`calibration` in settings.json).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationSettings {
+  /// Apply fitted curves to rule and model confidences before fusion.
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  /// `isotonic` or `platt`; used by the next fit.
+  #[serde(default = "default_method")]
+  pub method: String,
+  /// Sources with fewer reviewed predictions keep their raw confidence.
+  #[serde(default = "default_min_samples")]
+  pub min_samples: usize,
+}
+
+fn default_enabled() -> bool {
+  true
+}
+
+fn default_method() -> String {
+  "isotonic".to_string()
+}
+
+fn default_min_samples() -> usize {
+  30
+}
+
+impl Default for CalibrationSettings {
+  fn default() -> Self {
+    Self {
+      enabled: default_enabled(),
+      method: default_method(),
+      min_samples: default_min_samples(),
+    }
+  }
+}
+
+/// Maps a raw confidence to the observed probability that the label is right.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Curve {
+  /// Pool-adjacent-violators fit: non-decreasing accuracies at the mean confidence of each block,
+  /// interpolated linearly in between and held flat outside.
+  Isotonic { xs: Vec<f64>, ys: Vec<f64> },
+  /// `sigmoid(a · logit(confidence) + b)`.
+  Platt { a: f64, b: f64 },
+}
+
+impl Curve {
+  /// `samples` are `(raw confidence, label was right)`.
+  pub fn fit(method: &str, samples: &[(f64, bool)]) -> Result<Self, String> {
+    match method {
+      "isotonic" => Ok(fit_isotonic(samples)),
+      "platt" => Ok(fit_platt(samples)),
+      other => Err(format!("unknown calibration method: {other}")),
+    }
+  }
+
+  pub fn method(&self) -> &'static str {
+    match self {
+      Curve::Isotonic { .. } => "isotonic",
+      Curve::Platt { .. } => "platt",
+    }
+  }
+
+  pub fn apply(&self, confidence: f64) -> f64 {
+    let c = confidence.clamp(0.0, 1.0);
+    match self {
+      Curve::Isotonic { xs, ys } => {
+        let (Some(&first), Some(&last)) = (xs.first(), xs.last()) else {
+          return c;
+        };
+        if c <= first {
+          return ys[0];
+        }
+        if c >= last {
+          return ys[ys.len() - 1];
+        }
+        let i = xs.partition_point(|&x| x <= c);
+        let (x0, x1, y0, y1) = (xs[i - 1], xs[i], ys[i - 1], ys[i]);
+        y0 + (y1 - y0) * (c - x0) / (x1 - x0)
+      }
+      Curve::Platt { a, b } => sigmoid(a * logit(c) + b),
+    }
+  }
+}
+
+/// Where a label's confidence came from, as recorded in `label_predictions`. Model labels are keyed
+/// by model version; rule labels by the script or strong rule that decided them.
+pub fn label_source(label: &LabelOutput) -> Option<String> {
+  if label.model_version != "n/a" {
+    return (label.model_version != "error").then(|| format!("model:{}", label.model_version));
+  }
+  if let Some(script) = label.signals.get("script").and_then(|v| v.as_str()) {
+    return Some(format!("rule:script:{script}"));
+  }
+  label
+    .signals
+    .get("rule")
+    .and_then(|v| v.as_str())
+    .map(|rule| format!("rule:{rule}"))
+}
+
+/// Fitted curves by source, loaded once per batch.
+#[derive(Default)]
+pub struct Calibrator {
+  curves: HashMap<String, Curve>,
+}
+
+impl Calibrator {
+  /// Empty (every confidence passes through) when calibration is disabled.
+  pub fn load(db: &Db, settings: &CalibrationSettings) -> Result<Self, String> {
+    if !settings.enabled {
+      return Ok(Self::default());
+    }
+    let mut curves = HashMap::new();
+    for (source, json) in db.dao().calibration_curves()? {
+      let curve: Curve = serde_json::from_str(&json).map_err(|e| format!("calibration curve {source}: {e}"))?;
+      curves.insert(source, curve);
+    }
+    Ok(Self { curves })
+  }
+
+  /// Replace the label's confidence with its source's calibrated one; the raw value is kept in
+  /// `signals.calibration`.
+  pub fn calibrate(&self, label: &mut LabelOutput) {
+    let Some(source) = label_source(label) else {
+      return;
+    };
+    let Some(curve) = self.curves.get(&source) else {
+      return;
+    };
+    let raw = label.confidence;
+    label.confidence = round4(curve.apply(raw));
+    label.signals.insert(
+      "calibration".to_string(),
+      serde_json::json!({ "source": source, "method": curve.method(), "raw": raw }),
+    );
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReliabilityBin {
+  pub lo: f64,
+  pub hi: f64,
+  pub count: i64,
+  pub mean_confidence: f64,
+  pub accuracy: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceCalibration {
+  pub source: String,
+  /// Reviewed predictions of this source.
+  pub samples: i64,
+  pub accuracy: f64,
+  pub mean_confidence: f64,
+  /// Expected calibration error of the raw confidences.
+  pub ece: f64,
+  pub bins: Vec<ReliabilityBin>,
+  /// Method of the stored curve; `None` when the source has none.
+  pub method: Option<String>,
+  /// The same samples through the stored curve. Measured on the data it was fitted on, so it
+  /// flatters the curve.
+  pub calibrated_ece: Option<f64>,
+  pub calibrated_bins: Vec<ReliabilityBin>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationReport {
+  pub bins: usize,
+  /// Most samples first.
+  pub sources: Vec<SourceCalibration>,
+}
+
+const BINS: usize = 10;
+
+/// Reliability diagrams and ECE per source against reviewed labels, raw and through the stored
+/// curves.
+pub fn calibration_report(db: &Db) -> Result<CalibrationReport, String> {
+  let mut curves = HashMap::new();
+  for (source, json) in db.dao().calibration_curves()? {
+    if let Ok(curve) = serde_json::from_str::<Curve>(&json) {
+      curves.insert(source, curve);
+    }
+  }
+  let mut sources: Vec<SourceCalibration> = grouped_samples(db)?
+    .into_iter()
+    .map(|(source, samples)| {
+      let curve = curves.get(&source);
+      let calibrated: Option<Vec<(f64, bool)>> =
+        curve.map(|c| samples.iter().map(|&(x, y)| (c.apply(x), y)).collect());
+      let n = samples.len() as f64;
+      SourceCalibration {
+        samples: samples.len() as i64,
+        accuracy: round4(samples.iter().filter(|s| s.1).count() as f64 / n),
+        mean_confidence: round4(samples.iter().map(|s| s.0).sum::<f64>() / n),
+        ece: ece(&samples),
+        bins: reliability(&samples),
+        method: curve.map(|c| c.method().to_string()),
+        calibrated_ece: calibrated.as_deref().map(ece),
+        calibrated_bins: calibrated.as_deref().map(reliability).unwrap_or_default(),
+        source,
+      }
+    })
+    .collect();
+  sources.sort_by(|a, b| b.samples.cmp(&a.samples).then_with(|| a.source.cmp(&b.source)));
+  Ok(CalibrationReport { bins: BINS, sources })
+}
+
+/// Refit every source with at least `min_samples` reviewed predictions, replacing all stored
+/// curves, and report the result.
+pub fn calibration_fit(db: &Db, settings: &CalibrationSettings) -> Result<CalibrationReport, String> {
+  let mut fitted = vec![];
+  for (source, samples) in grouped_samples(db)? {
+    if samples.len() < settings.min_samples.max(1) {
+      continue;
+    }
+    let curve = Curve::fit(&settings.method, &samples)?;
+    let json = serde_json::to_string(&curve).map_err(|e| e.to_string())?;
+    fitted.push((source, json, samples.len() as i64));
+  }
+  db.dao().calibration_curves_replace(&fitted)?;
+  calibration_report(db)
+}
+
+fn grouped_samples(db: &Db) -> Result<HashMap<String, Vec<(f64, bool)>>, String> {
+  let mut out: HashMap<String, Vec<(f64, bool)>> = HashMap::new();
+  for (source, confidence, correct) in db.dao().calibration_samples()? {
+    out.entry(source).or_default().push((confidence, correct));
+  }
+  Ok(out)
+}
+
+/// Equal-width bins over [0, 1]; empty bins are left out.
+pub fn reliability(samples: &[(f64, bool)]) -> Vec<ReliabilityBin> {
+  let mut acc = [(0i64, 0.0f64, 0i64); BINS];
+  for &(c, y) in samples {
+    let i = ((c.clamp(0.0, 1.0) * BINS as f64) as usize).min(BINS - 1);
+    acc[i].0 += 1;
+    acc[i].1 += c;
+    acc[i].2 += y as i64;
+  }
+  acc
+    .iter()
+    .enumerate()
+    .filter(|(_, (n, _, _))| *n > 0)
+    .map(|(i, &(n, sum, right))| ReliabilityBin {
+      lo: i as f64 / BINS as f64,
+      hi: (i + 1) as f64 / BINS as f64,
+      count: n,
+      mean_confidence: round4(sum / n as f64),
+      accuracy: round4(right as f64 / n as f64),
+    })
+    .collect()
+}
+
+/// Sample-weighted mean gap between confidence and accuracy over the reliability bins.
+pub fn ece(samples: &[(f64, bool)]) -> f64 {
+  if samples.is_empty() {
+    return 0.0;
+  }
+  let total = samples.len() as f64;
+  let gap: f64 = reliability(samples)
+    .iter()
+    .map(|b| b.count as f64 / total * (b.accuracy - b.mean_confidence).abs())
+    .sum();
+  round4(gap)
+}
+
+fn fit_isotonic(samples: &[(f64, bool)]) -> Curve {
+  let mut sorted: Vec<(f64, f64)> = samples.iter().map(|&(x, y)| (x.clamp(0.0, 1.0), y as u8 as f64)).collect();
+  sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+  // Equal confidences are collapsed into one (x, sum y, count) group before pooling, so the fit
+  // does not depend on the order of samples within a tie.
+  let mut ties: Vec<(f64, f64, f64)> = vec![];
+  for (x, y) in sorted {
+    match ties.last_mut() {
+      Some(last) if last.0 == x => {
+        last.1 += y;
+        last.2 += 1.0;
+      }
+      _ => ties.push((x, y, 1.0)),
+    }
+  }
+
+  // Blocks of (sum x, sum y, count).
+  let mut blocks: Vec<(f64, f64, f64)> = vec![];
+  for (x, y, n) in ties {
+    blocks.push((x * n, y, n));
+    // Pool while the accuracy would go down.
+    while blocks.len() > 1 {
+      let (b, a) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+      if a.1 / a.2 <= b.1 / b.2 {
+        break;
+      }
+      blocks.pop();
+      let last = blocks.last_mut().expect("two blocks");
+      last.0 += b.0;
+      last.1 += b.1;
+      last.2 += b.2;
+    }
+  }
+  Curve::Isotonic {
+    xs: blocks.iter().map(|b| round4(b.0 / b.2)).collect(),
+    ys: blocks.iter().map(|b| round4(b.1 / b.2)).collect(),
+  }
+}
+
+// Newton's method on the log loss, with Platt's smoothed targets so a source that was always right
+// does not get a curve pinned at 1.0. The small ridge keeps the step defined when every raw
+// confidence is the same (as for a strong rule).
+fn fit_platt(samples: &[(f64, bool)]) -> Curve {
+  let positives = samples.iter().filter(|s| s.1).count() as f64;
+  let negatives = samples.len() as f64 - positives;
+  let (t_pos, t_neg) = ((positives + 1.0) / (positives + 2.0), 1.0 / (negatives + 2.0));
+  let data: Vec<(f64, f64)> = samples
+    .iter()
+    .map(|&(c, y)| (logit(c), if y { t_pos } else { t_neg }))
+    .collect();
+
+  let (mut a, mut b) = (1.0f64, 0.0f64);
+  const RIDGE: f64 = 1e-6;
+  for _ in 0..100 {
+    let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (RIDGE * a, RIDGE * b, RIDGE, 0.0, RIDGE);
+    for &(x, t) in &data {
+      let p = sigmoid(a * x + b);
+      let w = (p * (1.0 - p)).max(1e-12);
+      ga += (p - t) * x;
+      gb += p - t;
+      haa += w * x * x;
+      hab += w * x;
+      hbb += w;
+    }
+    let det = haa * hbb - hab * hab;
+    if det.abs() < 1e-18 {
+      break;
+    }
+    let da = (hbb * ga - hab * gb) / det;
+    let db = (haa * gb - hab * ga) / det;
+    a -= da;
+    b -= db;
+    if da.abs() < 1e-9 && db.abs() < 1e-9 {
+      break;
+    }
+  }
+  Curve::Platt { a, b }
+}
+
+fn logit(c: f64) -> f64 {
+  let c = c.clamp(1e-4, 1.0 - 1e-4);
+  (c / (1.0 - c)).ln()
+}
+
+fn sigmoid(z: f64) -> f64 {
+  1.0 / (1.0 + (-z).exp())
+}
+
+fn round4(v: f64) -> f64 {
+  (v * 10000.0).round() / 10000.0
+}
@@ -0,0 +1,276 @@
+use std::{thread, time::Duration};
+
+use crate::{
+  model::{
+    prompt::PromptTemplate,
+    provider::{build_provider, parse_kind, Provider, ProviderKind},
+    schema::{ClassifyPayload, LabelOutput},
+  },
+  settings::{AppSettings, ProviderMember},
+};
+
+/// Members of a `chain` or `vote` provider, built from the shared provider settings with each
+/// member's kind and model overrides. Members that fail to build are skipped with their error, so
+/// a missing llama-cli does not take down the rest of a chain.
+fn build_members(settings: &AppSettings, prompt: &PromptTemplate) -> Result<Vec<Box<dyn Provider>>, String> {
+  let mut members = vec![];
+  let mut errors = vec![];
+  for member in &settings.provider.members {
+    match build_member(settings, member, prompt) {
+      Ok(p) => members.push(p),
+      Err(e) => errors.push(format!("{}: {e}", member.kind)),
+    }
+  }
+  if members.is_empty() {
+    return Err(if errors.is_empty() {
+      "no provider members configured".to_string()
+    } else {
+      format!("no provider member available ({})", errors.join("; "))
+    });
+  }
+  Ok(members)
+}
+
+fn build_member(
+  settings: &AppSettings,
+  member: &ProviderMember,
+  prompt: &PromptTemplate,
+) -> Result<Box<dyn Provider>, String> {
+  build_provider(&member_settings(settings, member)?, prompt)
+}
+
+/// The settings a single member runs with.
+pub fn member_settings(settings: &AppSettings, member: &ProviderMember) -> Result<AppSettings, String> {
+  if matches!(parse_kind(&member.kind), ProviderKind::Chain | ProviderKind::Vote) {
+    return Err(format!("{} cannot be a member of another composite provider", member.kind));
+  }
+  let mut s = settings.clone();
+  s.provider.kind = member.kind.clone();
+  s.provider.members = vec![];
+  if member.model_path.is_some() {
+    s.provider.model_path = member.model_path.clone();
+  }
+  if member.ollama_model.is_some() {
+    s.provider.ollama_model = member.ollama_model.clone();
+  }
+  Ok(s)
+}
+
+/// Ordered failover: each message goes to the first member, and whatever it fails on (error,
+/// timeout, unparseable output) moves on to the next.
+pub struct ChainProvider {
+  members: Vec<Box<dyn Provider>>,
+}
+
+impl ChainProvider {
+  pub fn build(settings: &AppSettings, prompt: &PromptTemplate) -> Result<Self, String> {
+    Ok(Self {
+      members: build_members(settings, prompt)?,
+    })
+  }
+}
+
+impl Provider for ChainProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    self
+      .classify_many(std::slice::from_ref(payload), timeout)
+      .pop()
+      .unwrap_or_else(|| Err("empty chain result".to_string()))
+  }
+
+  fn model_version(&self) -> String {
+    let versions: Vec<String> = self.members.iter().map(|m| m.model_version()).collect();
+    format!("chain({})", versions.join(">"))
+  }
+
+  fn supports_batch(&self) -> bool {
+    self.members.iter().any(|m| m.supports_batch())
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    let mut out: Vec<Result<LabelOutput, String>> = payloads.iter().map(|_| Err(String::new())).collect();
+    let mut failures: Vec<Vec<String>> = vec![vec![]; payloads.len()];
+    let mut pending: Vec<usize> = (0..payloads.len()).collect();
+
+    for member in &self.members {
+      if pending.is_empty() {
+        break;
+      }
+      let version = member.model_version();
+      let batch: Vec<ClassifyPayload> = pending.iter().map(|&i| payloads[i].clone()).collect();
+      let mut still_pending = vec![];
+      for (i, r) in pending.into_iter().zip(member.classify_many(&batch, timeout)) {
+        match r {
+          Ok(mut label) => {
+            // Keep a trace of who failed before the member that answered.
+            label.reasons.extend(
+              failures[i]
+                .drain(..)
+                .map(|e| format!("provider_failover:{e}")),
+            );
+            out[i] = Ok(label);
+          }
+          Err(e) => {
+            failures[i].push(format!("{version}: {e}"));
+            still_pending.push(i);
+          }
+        }
+      }
+      pending = still_pending;
+    }
+
+    for i in pending {
+      out[i] = Err(format!("all providers failed: {}", failures[i].join("; ")));
+    }
+    out
+  }
+}
+
+/// Ask every member and combine the answers: `majority` picks the (industry, type) most members
+/// gave, `confidence` the one with the highest confidence averaged over all answering members.
+/// Any disagreement marks the label for review.
+pub struct VoteProvider {
+  members: Vec<Box<dyn Provider>>,
+  strategy: VoteStrategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VoteStrategy {
+  Majority,
+  Confidence,
+}
+
+impl VoteProvider {
+  pub fn build(settings: &AppSettings, prompt: &PromptTemplate) -> Result<Self, String> {
+    let strategy = match settings.provider.vote_strategy.as_str() {
+      "majority" => VoteStrategy::Majority,
+      "confidence" => VoteStrategy::Confidence,
+      other => return Err(format!("unknown vote strategy: {other}")),
+    };
+    Ok(Self {
+      members: build_members(settings, prompt)?,
+      strategy,
+    })
+  }
+}
+
+impl Provider for VoteProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    self
+      .classify_many(std::slice::from_ref(payload), timeout)
+      .pop()
+      .unwrap_or_else(|| Err("empty vote result".to_string()))
+  }
+
+  fn model_version(&self) -> String {
+    let versions: Vec<String> = self.members.iter().map(|m| m.model_version()).collect();
+    format!("vote({})", versions.join(","))
+  }
+
+  fn supports_batch(&self) -> bool {
+    self.members.iter().any(|m| m.supports_batch())
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    // Members run side by side so a vote costs the slowest member, not the sum.
+    let answers: Vec<(String, Vec<Result<LabelOutput, String>>)> = thread::scope(|s| {
+      let handles: Vec<_> = self
+        .members
+        .iter()
+        .map(|m| s.spawn(move || (m.model_version(), m.classify_many(payloads, timeout))))
+        .collect();
+      handles
+        .into_iter()
+        .map(|h| h.join().unwrap_or_else(|_| ("?".to_string(), vec![])))
+        .collect()
+    });
+
+    (0..payloads.len())
+      .map(|i| {
+        let ballots: Vec<(&str, Result<&LabelOutput, &str>)> = answers
+          .iter()
+          .map(|(version, results)| {
+            let r = match results.get(i) {
+              Some(Ok(label)) => Ok(label),
+              Some(Err(e)) => Err(e.as_str()),
+              None => Err("member panicked"),
+            };
+            (version.as_str(), r)
+          })
+          .collect();
+        self.tally(&ballots)
+      })
+      .collect()
+  }
+}
+
+impl VoteProvider {
+  fn tally(&self, ballots: &[(&str, Result<&LabelOutput, &str>)]) -> Result<LabelOutput, String> {
+    let votes: Vec<(&str, &LabelOutput)> = ballots
+      .iter()
+      .filter_map(|(v, r)| r.ok().map(|l| (*v, l)))
+      .collect();
+    if votes.is_empty() {
+      let errors: Vec<String> = ballots
+        .iter()
+        .filter_map(|(v, r)| r.err().map(|e| format!("{v}: {e}")))
+        .collect();
+      return Err(format!("all providers failed: {}", errors.join("; ")));
+    }
+
+    // (industry, type) -> (votes, summed confidence, first voter), in first-seen order.
+    let mut groups: Vec<((&str, &str), usize, f64, usize)> = vec![];
+    for (idx, (_, l)) in votes.iter().enumerate() {
+      let key = (l.industry.as_str(), l.sms_type.as_str());
+      match groups.iter_mut().find(|g| g.0 == key) {
+        Some(g) => {
+          g.1 += 1;
+          g.2 += l.confidence;
+        }
+        None => groups.push((key, 1, l.confidence, idx)),
+      }
+    }
+    let best = groups
+      .iter()
+      .enumerate()
+      .max_by(|(ia, a), (ib, b)| {
+        let primary = match self.strategy {
+          VoteStrategy::Majority => a.1.cmp(&b.1),
+          VoteStrategy::Confidence => std::cmp::Ordering::Equal,
+        };
+        // Ties go to the higher total confidence, then to the earlier member.
+        primary.then(a.2.total_cmp(&b.2)).then(ib.cmp(ia))
+      })
+      .map(|(_, g)| *g)
+      .expect("at least one vote");
+
+    // The most confident member of the winning side supplies entities and signals.
+    let (_, base) = votes
+      .iter()
+      .filter(|(_, l)| (l.industry.as_str(), l.sms_type.as_str()) == best.0)
+      .max_by(|a, b| a.1.confidence.total_cmp(&b.1.confidence))
+      .copied()
+      .unwrap_or(votes[best.3]);
+    let mut label = base.clone();
+    label.model_version = self.model_version();
+    label.confidence = match self.strategy {
+      VoteStrategy::Majority => best.2 / best.1 as f64,
+      VoteStrategy::Confidence => best.2 / votes.len() as f64,
+    };
+
+    if groups.len() > 1 {
+      label.needs_review = true;
+      let detail: Vec<String> = votes
+        .iter()
+        .map(|(v, l)| format!("{v}={}/{}", l.industry, l.sms_type))
+        .collect();
+      label.reasons.push(format!("vote_disagreement:{}", detail.join(",")));
+    }
+    for (v, r) in ballots {
+      if let Err(e) = r {
+        label.reasons.push(format!("vote_member_failed:{v}: {e}"));
+      }
+    }
+    Ok(label)
+  }
+}
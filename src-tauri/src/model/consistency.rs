@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use crate::model::{
+  provider::Provider,
+  schema::{ClassifyPayload, LabelOutput},
+};
+
+// Below this share of samples agreeing on the majority label, a person should look.
+const LOW_AGREEMENT: f64 = 0.6;
+
+/// Self-consistency: ask the wrapped provider `samples` times (at a non-zero temperature, set on
+/// the provider) and keep the majority (industry, type). Confidence is the share of samples that
+/// agree with it, replacing the model's self-reported value; failed samples count as dissent.
+pub struct SelfConsistencyProvider {
+  inner: Box<dyn Provider>,
+  samples: usize,
+}
+
+impl SelfConsistencyProvider {
+  /// `inner` unchanged when `samples` is 1 or less.
+  pub fn wrap(inner: Box<dyn Provider>, samples: usize) -> Box<dyn Provider> {
+    if samples <= 1 {
+      return inner;
+    }
+    Box::new(Self { inner, samples })
+  }
+
+  fn tally(&self, answers: Vec<Result<LabelOutput, String>>) -> Result<LabelOutput, String> {
+    let mut errors = vec![];
+    // (industry, type) -> (votes, first label with it), in first-seen order.
+    let mut groups: Vec<(usize, LabelOutput)> = vec![];
+    for answer in answers {
+      match answer {
+        Ok(label) => match groups
+          .iter_mut()
+          .find(|(_, g)| g.industry == label.industry && g.sms_type == label.sms_type)
+        {
+          Some((n, _)) => *n += 1,
+          None => groups.push((1, label)),
+        },
+        Err(e) => errors.push(e),
+      }
+    }
+    // `max_by_key` keeps the last maximum; iterate in reverse so ties go to the first seen.
+    let Some((votes, mut label)) = groups.iter().rev().max_by_key(|(n, _)| *n).cloned() else {
+      return Err(format!("all {} samples failed: {}", self.samples, errors.join("; ")));
+    };
+
+    let agreement = votes as f64 / self.samples as f64;
+    label.confidence = agreement;
+    label.reasons.push(format!("self_consistency:{votes}/{}", self.samples));
+    if agreement < LOW_AGREEMENT {
+      label.needs_review = true;
+      label.reasons.push("self_consistency:low_agreement".to_string());
+    }
+    Ok(label)
+  }
+}
+
+impl Provider for SelfConsistencyProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    let answers = (0..self.samples).map(|_| self.inner.classify(payload, timeout)).collect();
+    self.tally(answers)
+  }
+
+  fn model_version(&self) -> String {
+    self.inner.model_version()
+  }
+
+  fn supports_batch(&self) -> bool {
+    self.inner.supports_batch()
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    let mut per_payload: Vec<Vec<Result<LabelOutput, String>>> = payloads.iter().map(|_| vec![]).collect();
+    for _ in 0..self.samples {
+      for (slot, r) in per_payload.iter_mut().zip(self.inner.classify_many(payloads, timeout)) {
+        slot.push(r);
+      }
+    }
+    per_payload.into_iter().map(|answers| self.tally(answers)).collect()
+  }
+}
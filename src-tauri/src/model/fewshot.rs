@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{db::Db, model::schema::FewShotExample, rules::normalize::normalize_text};
+
+// Features are hashed into this many buckets; collisions only add a little noise to the scores.
+const BUCKETS: u64 = 1 << 20;
+// Below this cosine an example shares little more than boilerplate with the message.
+const MIN_SIMILARITY: f32 = 0.1;
+
+/// Reviewed messages indexed for lexical retrieval: TF-IDF over hashed character bigrams
+/// (digits folded to `0`, ASCII lower-cased), compared by cosine similarity. Built once per batch.
+#[derive(Default)]
+pub struct FewShotIndex {
+  /// Examples per prompt; 0 disables retrieval.
+  pub k: usize,
+  /// Upper bound on the estimated tokens all examples of one prompt may take.
+  pub token_budget: usize,
+  entries: Vec<(FewShotExample, Vec<(u32, f32)>)>,
+  idf: HashMap<u32, f32>,
+}
+
+impl FewShotIndex {
+  pub fn load(db: &Db, k: usize, token_budget: usize) -> Result<Self, String> {
+    if k == 0 || token_budget == 0 {
+      return Ok(Self::default());
+    }
+    Ok(Self::new(db.dao().reviewed_examples()?, k, token_budget))
+  }
+
+  pub fn new(examples: Vec<FewShotExample>, k: usize, token_budget: usize) -> Self {
+    let counts: Vec<HashMap<u32, f32>> = examples.iter().map(|e| term_counts(&e.content)).collect();
+    let mut df: HashMap<u32, f32> = HashMap::new();
+    for c in &counts {
+      for &t in c.keys() {
+        *df.entry(t).or_default() += 1.0;
+      }
+    }
+    let n = examples.len() as f32;
+    let idf: HashMap<u32, f32> = df
+      .into_iter()
+      .map(|(t, d)| (t, ((n + 1.0) / (d + 1.0)).ln() + 1.0))
+      .collect();
+    let entries = examples
+      .into_iter()
+      .zip(counts)
+      .map(|(e, c)| (e, weigh(c, &idf)))
+      .collect();
+    Self {
+      k,
+      token_budget,
+      entries,
+      idf,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Up to `k` most similar examples whose rendered lines fit the token budget together, most
+  /// similar first. `message_id` itself is never returned, so re-labeling a reviewed message
+  /// does not show the model its own answer.
+  pub fn select(&self, message_id: i64, content: &str) -> Vec<FewShotExample> {
+    if self.k == 0 || self.entries.is_empty() {
+      return vec![];
+    }
+    let query = weigh(term_counts(content), &self.idf);
+    let mut scored: Vec<(f32, usize)> = self
+      .entries
+      .iter()
+      .enumerate()
+      .filter(|(_, (e, _))| e.message_id != message_id)
+      .map(|(i, (_, v))| (cosine(&query, v), i))
+      .filter(|(s, _)| *s >= MIN_SIMILARITY)
+      .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut out: Vec<FewShotExample> = vec![];
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut budget = self.token_budget;
+    for (_, i) in scored {
+      if out.len() >= self.k {
+        break;
+      }
+      let example = &self.entries[i].0;
+      if !seen.insert(example.content.as_str()) {
+        continue;
+      }
+      let cost = estimate_tokens(&super::prompt::render_example(example));
+      if cost > budget {
+        continue;
+      }
+      budget -= cost;
+      out.push(example.clone());
+    }
+    out
+  }
+}
+
+/// Rough token count for local models: one per CJK character, one per four other characters.
+pub fn estimate_tokens(text: &str) -> usize {
+  let (mut cjk, mut other) = (0usize, 0usize);
+  for c in text.chars() {
+    if c.is_ascii() {
+      other += 1;
+    } else {
+      cjk += 1;
+    }
+  }
+  cjk + other.div_ceil(4)
+}
+
+fn term_counts(content: &str) -> HashMap<u32, f32> {
+  let chars: Vec<char> = normalize_text(content)
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .map(|c| match c {
+      '0'..='9' => '0',
+      c => c.to_ascii_lowercase(),
+    })
+    .collect();
+  let mut counts: HashMap<u32, f32> = HashMap::new();
+  // Bigrams of digits and punctuation alone (`00`, `:0`) say nothing about the kind of message.
+  for w in chars.windows(2).filter(|w| w.iter().any(|c| c.is_alphabetic())) {
+    *counts.entry(bucket(w)).or_default() += 1.0;
+  }
+  if chars.len() == 1 {
+    *counts.entry(bucket(&chars)).or_default() += 1.0;
+  }
+  counts
+}
+
+// Sublinear TF times IDF, scaled to unit length. Terms no example contains get no weight.
+fn weigh(counts: HashMap<u32, f32>, idf: &HashMap<u32, f32>) -> Vec<(u32, f32)> {
+  let mut v: Vec<(u32, f32)> = counts
+    .into_iter()
+    .filter_map(|(t, c)| idf.get(&t).map(|w| (t, (1.0 + c.ln()) * w)))
+    .collect();
+  let norm = v.iter().map(|(_, w)| w * w).sum::<f32>().sqrt();
+  if norm > 0.0 {
+    for (_, w) in &mut v {
+      *w /= norm;
+    }
+  }
+  v.sort_by_key(|(t, _)| *t);
+  v
+}
+
+fn cosine(a: &[(u32, f32)], b: &[(u32, f32)]) -> f32 {
+  let (mut i, mut j, mut dot) = (0, 0, 0.0);
+  while i < a.len() && j < b.len() {
+    match a[i].0.cmp(&b[j].0) {
+      std::cmp::Ordering::Less => i += 1,
+      std::cmp::Ordering::Greater => j += 1,
+      std::cmp::Ordering::Equal => {
+        dot += a[i].1 * b[j].1;
+        i += 1;
+        j += 1;
+      }
+    }
+  }
+  dot
+}
+
+// FNV-1a over the characters.
+fn bucket(chars: &[char]) -> u32 {
+  let mut h: u64 = 0xcbf29ce484222325;
+  for c in chars {
+    for b in (*c as u32).to_le_bytes() {
+      h ^= b as u64;
+      h = h.wrapping_mul(0x100000001b3);
+    }
+  }
+  (h % BUCKETS) as u32
+}
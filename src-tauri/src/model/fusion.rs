@@ -1,4 +1,9 @@
-use crate::model::schema::LabelOutput;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  model::schema::{is_allowed_pair, LabelOutput},
+  rules::RuleResult,
+};
 
 #[derive(Debug, Clone)]
 pub struct FusionInput {
@@ -7,45 +12,312 @@ pub struct FusionInput {
   pub rule_strong_hit: bool,
 }
 
-pub fn fuse(input: FusionInput) -> LabelOutput {
-  match (input.rule, input.model) {
+/// Fusion settings (`fusion` in settings.json).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FusionSettings {
+  /// `default`, `rule_wins`, `model_wins` or `weighted`; recorded on every fused label.
+  #[serde(default = "default_policy")]
+  pub policy: String,
+  /// Per-field weights used by `weighted`.
+  #[serde(default)]
+  pub weights: FieldWeights,
+  /// Types decided by a fixed side whatever the policy, e.g. 营销推广 → model.
+  #[serde(default)]
+  pub type_overrides: Vec<TypeOverride>,
+  /// Fused labels below this confidence need review; 0 turns the check off.
+  #[serde(default)]
+  pub review_below: f64,
+  /// On a rule/model conflict the confidence is multiplied by `conflict_penalty` and capped at
+  /// `conflict_cap`.
+  #[serde(default = "default_conflict_factor")]
+  pub conflict_penalty: f64,
+  #[serde(default = "default_conflict_factor")]
+  pub conflict_cap: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FieldWeights {
+  #[serde(default)]
+  pub industry: SideWeights,
+  #[serde(default, rename = "type")]
+  pub sms_type: SideWeights,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SideWeights {
+  #[serde(default = "default_weight")]
+  pub rule: f64,
+  #[serde(default = "default_weight")]
+  pub model: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypeOverride {
+  #[serde(rename = "type")]
+  pub sms_type: String,
+  /// `rule` or `model`.
+  pub winner: String,
+}
+
+fn default_policy() -> String {
+  "default".to_string()
+}
+
+fn default_conflict_factor() -> f64 {
+  0.85
+}
+
+fn default_weight() -> f64 {
+  1.0
+}
+
+impl Default for FusionSettings {
+  fn default() -> Self {
+    Self {
+      policy: default_policy(),
+      weights: FieldWeights::default(),
+      type_overrides: vec![],
+      review_below: 0.0,
+      conflict_penalty: default_conflict_factor(),
+      conflict_cap: default_conflict_factor(),
+    }
+  }
+}
+
+impl Default for SideWeights {
+  fn default() -> Self {
+    Self {
+      rule: default_weight(),
+      model: default_weight(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+  /// Strong rule first, else the more confident side (the original behaviour).
+  Default,
+  RuleWins,
+  /// The model unless a strong rule hit.
+  ModelWins,
+  /// Industry and type picked separately by `weight × confidence`.
+  Weighted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+  Rule,
+  Model,
+}
+
+/// A validated [`FusionSettings`].
+#[derive(Debug, Clone)]
+pub struct FusionPolicy {
+  id: String,
+  strategy: Strategy,
+  weights: FieldWeights,
+  overrides: Vec<(String, Side)>,
+  review_below: f64,
+  conflict_penalty: f64,
+  conflict_cap: f64,
+}
+
+impl Default for FusionPolicy {
+  fn default() -> Self {
+    Self::from_settings(&FusionSettings::default()).expect("default fusion settings are valid")
+  }
+}
+
+impl FusionPolicy {
+  pub fn from_settings(settings: &FusionSettings) -> Result<Self, String> {
+    let strategy = match settings.policy.as_str() {
+      "default" => Strategy::Default,
+      "rule_wins" => Strategy::RuleWins,
+      "model_wins" => Strategy::ModelWins,
+      "weighted" => Strategy::Weighted,
+      other => return Err(format!("unknown fusion policy: {other}")),
+    };
+    let overrides = settings
+      .type_overrides
+      .iter()
+      .map(|o| match o.winner.as_str() {
+        "rule" => Ok((o.sms_type.clone(), Side::Rule)),
+        "model" => Ok((o.sms_type.clone(), Side::Model)),
+        other => Err(format!("unknown fusion override winner for {}: {other}", o.sms_type)),
+      })
+      .collect::<Result<Vec<_>, String>>()?;
+    let w = &settings.weights;
+    if [w.industry.rule, w.industry.model, w.sms_type.rule, w.sms_type.model]
+      .iter()
+      .any(|v| !v.is_finite() || *v < 0.0)
+    {
+      return Err("fusion weights must be non-negative".to_string());
+    }
+    if !(0.0..=1.0).contains(&settings.conflict_penalty) || !(0.0..=1.0).contains(&settings.conflict_cap) {
+      return Err("fusion conflict penalty and cap must be between 0 and 1".to_string());
+    }
+    Ok(Self {
+      id: settings.policy.clone(),
+      strategy,
+      weights: settings.weights.clone(),
+      overrides,
+      review_below: settings.review_below,
+      conflict_penalty: settings.conflict_penalty,
+      conflict_cap: settings.conflict_cap,
+    })
+  }
+
+  /// Whether the model is asked about this message. Only policies that can overrule a strong rule
+  /// (`weighted`, or a `model` override for the rule's type) pay for a model call on a rule hit.
+  pub fn consults_model(&self, rule: &RuleResult) -> bool {
+    let Some(label) = rule.label.as_ref().filter(|_| rule.strong_hit) else {
+      return true;
+    };
+    self.strategy == Strategy::Weighted || self.override_for(&label.sms_type) == Some(Side::Model)
+  }
+
+  fn override_for(&self, sms_type: &str) -> Option<Side> {
+    self.overrides.iter().find(|(t, _)| t == sms_type).map(|(_, s)| *s)
+  }
+
+  fn penalize(&self, label: &mut LabelOutput) {
+    label.needs_review = true;
+    label.confidence = (label.confidence * self.conflict_penalty).min(self.conflict_cap);
+  }
+}
+
+pub fn fuse(input: FusionInput, policy: &FusionPolicy) -> LabelOutput {
+  let mut out = match (input.rule, input.model) {
     (Some(rule), None) => rule,
-    (None, Some(model)) => model,
-    (Some(rule), Some(model)) => {
-      let rule_industry = rule.industry.clone();
-      let rule_type = rule.sms_type.clone();
-      let model_industry = model.industry.clone();
-      let model_type = model.sms_type.clone();
-
-      // If strong rule hit, prefer rule; if conflict, mark needs_review.
-      let mut out = if input.rule_strong_hit {
-        rule
-      } else if model.confidence >= rule.confidence {
-        model
-      } else {
-        rule
-      };
-
-      // Conflict detection
-      let conflict = (rule_industry != model_industry) || (rule_type != model_type);
-      if conflict {
-        out.needs_review = true;
-        out.confidence = (out.confidence * 0.85).min(0.85);
-        out.reasons.push("fusion_conflict".to_string());
+    (None, Some(mut model)) => {
+      if model.has_illegal_pair() {
+        policy.penalize(&mut model);
+        model.reasons.push("fusion_conflict:disallowed_pair".to_string());
+      }
+      model
+    }
+    (Some(rule), Some(model)) => fuse_both(rule, model, input.rule_strong_hit, policy),
+    (None, None) => {
+      return LabelOutput {
+        industry: "其他".to_string(),
+        sms_type: "其他".to_string(),
+        secondary_types: vec![],
+        entities: Default::default(),
+        entity_spans: Default::default(),
+        confidence: 0.4,
+        needs_review: true,
+        reasons: vec!["no_rule_no_model".to_string()],
+        signals: Default::default(),
+        rules_version: crate::model::schema::RULES_VERSION.to_string(),
+        model_version: "n/a".to_string(),
+        prompt_version: "n/a".to_string(),
+        fusion_policy: policy.id.clone(),
+        schema_version: crate::model::schema::SCHEMA_VERSION.to_string(),
+        risk: None,
+      }
+    }
+  };
+
+  if out.confidence < policy.review_below && !out.needs_review {
+    out.needs_review = true;
+    out.reasons.push(format!("fusion_low_confidence:<{}", policy.review_below));
+  }
+  out.fusion_policy = policy.id.clone();
+  out
+}
+
+fn fuse_both(rule: LabelOutput, model: LabelOutput, rule_strong_hit: bool, policy: &FusionPolicy) -> LabelOutput {
+  let rule_industry = rule.industry.clone();
+  let rule_type = rule.sms_type.clone();
+  let model_industry = model.industry.clone();
+  let model_type = model.sms_type.clone();
+  // A model pair outside the taxonomy (or one normalize had to repair) is never trusted as-is.
+  let model_illegal_pair = model.has_illegal_pair();
+  let mut secondary = rule.secondary_types.clone();
+  secondary.extend(model.secondary_types.iter().cloned());
+
+  // The rule's type is checked first so a rule hit on an overridden type cannot be outvoted by
+  // the model proposing a different overridden type.
+  let overridden = [&rule_type, &model_type]
+    .into_iter()
+    .find_map(|t| policy.override_for(t).map(|s| (t.clone(), s)));
+
+  let mut reasons = vec![];
+  let mut out = if let Some((t, side)) = overridden {
+    reasons.push(format!("fusion_override:{t}={}", if side == Side::Rule { "rule" } else { "model" }));
+    if side == Side::Model && !model_illegal_pair {
+      model
+    } else {
+      rule
+    }
+  } else {
+    match policy.strategy {
+      Strategy::Default => {
+        if rule_strong_hit {
+          rule
+        } else if !model_illegal_pair && model.confidence >= rule.confidence {
+          model
+        } else {
+          rule
+        }
       }
-      out
-    }
-    (None, None) => LabelOutput {
-      industry: "其他".to_string(),
-      sms_type: "其他".to_string(),
-      entities: Default::default(),
-      confidence: 0.4,
-      needs_review: true,
-      reasons: vec!["no_rule_no_model".to_string()],
-      signals: Default::default(),
-      rules_version: crate::model::schema::RULES_VERSION.to_string(),
-      model_version: "n/a".to_string(),
-      schema_version: crate::model::schema::SCHEMA_VERSION.to_string(),
-    },
+      Strategy::RuleWins => rule,
+      Strategy::ModelWins if rule_strong_hit || model_illegal_pair => rule,
+      Strategy::ModelWins => model,
+      Strategy::Weighted if model_illegal_pair => rule,
+      Strategy::Weighted => weighted(rule, model, &policy.weights),
+    }
+  };
+
+  // Secondary types are additive: keep whatever either side proposed (max score wins).
+  out.secondary_types = secondary;
+  out.normalize_secondary_types();
+  out.reasons.extend(reasons);
+
+  let conflict = (rule_industry != model_industry) || (rule_type != model_type);
+  if conflict || model_illegal_pair {
+    policy.penalize(&mut out);
+    out.reasons.push("fusion_conflict".to_string());
+  }
+  if model_illegal_pair {
+    out
+      .reasons
+      .push(format!("fusion_conflict:disallowed_pair:{model_industry}/{model_type}"));
+  }
+  out
+}
+
+// Industry and type each go to the side with the higher `weight × confidence` (ties to the rule).
+// The label (entities, signals, confidence) comes from the side that won the type; a mixed pair
+// the taxonomy does not allow falls back to the side with the higher total.
+fn weighted(rule: LabelOutput, model: LabelOutput, weights: &FieldWeights) -> LabelOutput {
+  let industry = (
+    weights.industry.rule * rule.confidence,
+    weights.industry.model * model.confidence,
+  );
+  let sms_type = (
+    weights.sms_type.rule * rule.confidence,
+    weights.sms_type.model * model.confidence,
+  );
+  let industry_from_model = industry.1 > industry.0;
+  let type_from_model = sms_type.1 > sms_type.0;
+
+  let industry_value = if industry_from_model { &model.industry } else { &rule.industry };
+  let type_value = if type_from_model { &model.sms_type } else { &rule.sms_type };
+  if !is_allowed_pair(industry_value, type_value) {
+    return if industry.1 + sms_type.1 > industry.0 + sms_type.0 {
+      model
+    } else {
+      rule
+    };
+  }
+
+  let industry_value = industry_value.clone();
+  let mut out = if type_from_model { model } else { rule };
+  if industry_value != out.industry {
+    out.industry = industry_value;
+    let from = if industry_from_model { "model" } else { "rule" };
+    out.reasons.push(format!("fusion_weighted:industry_from_{from}"));
   }
+  out
 }
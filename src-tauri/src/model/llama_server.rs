@@ -0,0 +1,229 @@
+use std::{
+  net::TcpListener,
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+  thread,
+  time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex};
+use serde::Deserialize;
+use serde_json::json;
+
+// Loading a GGUF model can take a while on a cold disk.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What the managed `llama-server` was launched with; a different config restarts it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+  pub server_path: PathBuf,
+  pub model_path: PathBuf,
+  pub port: u16,
+}
+
+impl ServerConfig {
+  pub fn base_url(&self) -> String {
+    format!("http://127.0.0.1:{}", self.port)
+  }
+}
+
+struct Managed {
+  config: ServerConfig,
+  child: Child,
+  started_at: Instant,
+  restarts: u32,
+}
+
+enum Slot {
+  Stopped,
+  /// A worker is waiting for the model to load, without holding the lock.
+  Starting,
+  Running(Managed),
+}
+
+// One server per app: every batch worker talks to the same process.
+static SERVER: Lazy<Mutex<Slot>> = Lazy::new(|| Mutex::new(Slot::Stopped));
+// Signalled whenever a start finishes or the server is shut down.
+static STARTED: Condvar = Condvar::new();
+
+/// Base URL of a healthy server for `config`, launching it first when it is not running, has
+/// exited, or was started with another config. Callers block while the model loads.
+pub fn ensure_running(config: &ServerConfig) -> Result<String, String> {
+  let mut guard = SERVER.lock();
+  while matches!(*guard, Slot::Starting) {
+    STARTED.wait(&mut guard);
+  }
+  let mut restarts = 0;
+  if let Slot::Running(m) = &mut *guard {
+    match m.child.try_wait() {
+      Ok(None) if m.config == *config => return Ok(config.base_url()),
+      Ok(None) => stop(m),
+      // Exited on its own (crash, OOM, killed): bring it back with the same settings.
+      _ => {
+        log::warn!("llama-server exited unexpectedly, restarting");
+        if m.config == *config {
+          restarts = m.restarts + 1;
+        }
+      }
+    }
+  }
+  *guard = Slot::Starting;
+  drop(guard);
+
+  let started = spawn(config).and_then(|child| {
+    let mut managed = Managed {
+      config: config.clone(),
+      child,
+      started_at: Instant::now(),
+      restarts,
+    };
+    match wait_ready(&mut managed) {
+      Ok(()) => Ok(managed),
+      Err(e) => {
+        stop(&mut managed);
+        Err(e)
+      }
+    }
+  });
+
+  let mut guard = SERVER.lock();
+  let result = match started {
+    // `shutdown` ran while the model loaded.
+    Ok(mut managed) if !matches!(*guard, Slot::Starting) => {
+      stop(&mut managed);
+      Err("llama-server was shut down during startup".to_string())
+    }
+    Ok(managed) => {
+      *guard = Slot::Running(managed);
+      Ok(config.base_url())
+    }
+    Err(e) => {
+      *guard = Slot::Stopped;
+      Err(e)
+    }
+  };
+  STARTED.notify_all();
+  result
+}
+
+/// Stop the managed server, if any. Called when a batch ends and when the app exits.
+pub fn shutdown() {
+  let mut guard = SERVER.lock();
+  if let Slot::Running(m) = &mut *guard {
+    stop(m);
+  }
+  *guard = Slot::Stopped;
+  STARTED.notify_all();
+}
+
+pub enum ServerState {
+  Stopped,
+  /// A worker is launching the server and waiting for the model to load.
+  Starting,
+  Running { pid: u32, uptime: Duration, restarts: u32 },
+}
+
+pub fn state() -> ServerState {
+  let mut guard = SERVER.lock();
+  let m = match &mut *guard {
+    Slot::Stopped => return ServerState::Stopped,
+    Slot::Starting => return ServerState::Starting,
+    Slot::Running(m) => m,
+  };
+  match m.child.try_wait() {
+    Ok(None) => ServerState::Running {
+      pid: m.child.id(),
+      uptime: m.started_at.elapsed(),
+      restarts: m.restarts,
+    },
+    _ => ServerState::Stopped,
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+  content: String,
+}
+
+/// Run one completion on the server at `base_url`.
+pub fn complete(
+  base_url: &str,
+  prompt: &str,
+  max_tokens: i32,
+  temperature: f32,
+  timeout: Duration,
+) -> Result<String, String> {
+  let resp = ureq::post(&format!("{base_url}/completion"))
+    .timeout(timeout)
+    .send_json(json!({
+      "prompt": prompt,
+      "n_predict": max_tokens,
+      "temperature": temperature,
+      // The shared instruction prefix stays in the KV cache between messages.
+      "cache_prompt": true
+    }))
+    .map_err(|e| format!("llama-server request failed: {e}"))?;
+  let parsed: CompletionResponse = resp.into_json().map_err(|e| e.to_string())?;
+  Ok(parsed.content)
+}
+
+fn spawn(config: &ServerConfig) -> Result<Child, String> {
+  if !config.server_path.exists() {
+    return Err(format!("llama-server not found: {}", config.server_path.display()));
+  }
+  if !config.model_path.exists() {
+    return Err("model file not found".to_string());
+  }
+  if TcpListener::bind(("127.0.0.1", config.port)).is_err() {
+    return Err(format!("port {} is used by another process", config.port));
+  }
+  // Its logs are verbose and nobody reads the pipe, so they are discarded.
+  Command::new(&config.server_path)
+    .arg("-m")
+    .arg(&config.model_path)
+    .arg("--host")
+    .arg("127.0.0.1")
+    .arg("--port")
+    .arg(config.port.to_string())
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|e| format!("failed to start llama-server: {e}"))
+}
+
+// `/health` answers 503 while the model loads and 200 once it can serve completions.
+fn wait_ready(m: &mut Managed) -> Result<(), String> {
+  let url = format!("{}/health", m.config.base_url());
+  let deadline = Instant::now() + STARTUP_TIMEOUT;
+  loop {
+    if let Ok(Some(status)) = m.child.try_wait() {
+      return Err(format!("llama-server exited during startup: {status}"));
+    }
+    if ureq::get(&url).timeout(Duration::from_secs(1)).call().is_ok() {
+      // Something else may own the port: our child then fails to bind and exits.
+      return match m.child.try_wait() {
+        Ok(None) => Ok(()),
+        _ => Err(format!("port {} is used by another process", m.config.port)),
+      };
+    }
+    if Instant::now() >= deadline {
+      return Err(format!("llama-server not ready after {}s", STARTUP_TIMEOUT.as_secs()));
+    }
+    thread::sleep(Duration::from_millis(250));
+  }
+}
+
+fn stop(m: &mut Managed) {
+  let _ = m.child.kill();
+  let _ = m.child.wait();
+}
+
+/// `llama-server` next to the configured llama-cli, or in the bundled resources.
+pub fn default_server_path(llama_cli_path: Option<&Path>) -> PathBuf {
+  match llama_cli_path.and_then(|p| p.parent()) {
+    Some(dir) if !dir.as_os_str().is_empty() => dir.join("llama-server"),
+    _ => PathBuf::from("resources").join("llama-server"),
+  }
+}
@@ -1,5 +1,10 @@
 pub mod batch;
+pub mod calibration;
+pub mod composite;
+pub mod consistency;
+pub mod fewshot;
 pub mod fusion;
+pub mod llama_server;
 pub mod prompt;
 pub mod provider;
 pub mod schema;
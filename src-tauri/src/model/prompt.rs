@@ -1,41 +1,250 @@
-use crate::model::schema::{ClassifyPayload, INDUSTRIES, SMS_TYPES, RULES_VERSION, SCHEMA_VERSION};
+use std::collections::HashMap;
 
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  db::Db,
+  model::schema::{ClassifyPayload, FewShotExample, INDUSTRIES, SMS_TYPES, RULES_VERSION, SCHEMA_VERSION, TAXONOMY},
+};
+
+/// Version id of the compiled-in template, used when `ProviderSettings::prompt_version` is unset.
+pub const BUILTIN_PROMPT_VERSION: &str = "builtin-v1";
+
+/// Placeholders a template may use, written `{{name}}`.
+pub const PLACEHOLDERS: [&str; 11] = [
+  "industries",
+  "types",
+  "pairs",
+  "extra_fields",
+  "extra_schema",
+  "rules_version",
+  "schema_version",
+  "examples",
+  "content",
+  "entities",
+  "signals",
+];
+
+/// Placeholders of a batch body: the shared ones, plus `messages` (a JSON array of
+/// `message_id` / `content` / `rule_entities` / `rule_signals`) and `count` instead of the
+/// per-message `content`, `entities` and `signals`.
+pub const BATCH_PLACEHOLDERS: [&str; 10] = [
+  "industries",
+  "types",
+  "pairs",
+  "extra_fields",
+  "extra_schema",
+  "rules_version",
+  "schema_version",
+  "examples",
+  "messages",
+  "count",
+];
+
+static PLACEHOLDER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{([A-Za-z_]+)\}\}").unwrap());
+
+/// 提示词模板（内置一份，其余存放在 `prompt_templates` 表）。`version` 一经保存即不可改写，
+/// 改动需另存为新版本，这样标签上记录的 `prompt_version` 始终对应确定的文本。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+  pub version: String,
+  #[serde(default)]
+  pub name: String,
+  pub body: String,
+  /// Prompt for several messages in one model call (`Provider::classify_many`); without it the
+  /// template is only used one message at a time.
+  #[serde(default)]
+  pub batch_body: Option<String>,
+  #[serde(default)]
+  pub note: Option<String>,
+  /// The compiled-in template; cannot be saved over or deleted.
+  #[serde(default)]
+  pub builtin: bool,
+}
+
+impl PromptTemplate {
+  pub fn builtin() -> Self {
+    Self {
+      version: BUILTIN_PROMPT_VERSION.to_string(),
+      name: "内置模板".to_string(),
+      body: BUILTIN_BODY.to_string(),
+      batch_body: Some(BUILTIN_BATCH_BODY.to_string()),
+      note: None,
+      builtin: true,
+    }
+  }
+
+  /// A template must show the model the content and only use known placeholders.
+  pub fn validate(&self) -> Result<(), String> {
+    let version = self.version.trim();
+    if version.is_empty() {
+      return Err("prompt version is empty".to_string());
+    }
+    check_placeholders(version, &self.body, &PLACEHOLDERS, "content")?;
+    if let Some(batch) = self.batch_body.as_deref().filter(|b| !b.trim().is_empty()) {
+      check_placeholders(version, batch, &BATCH_PLACEHOLDERS, "messages")?;
+    }
+    Ok(())
+  }
+
+  pub fn supports_batch(&self) -> bool {
+    self.batch_body.as_deref().is_some_and(|b| !b.trim().is_empty())
+  }
+
+  pub fn render(&self, payload: &ClassifyPayload) -> String {
+    let mut values = shared_values(&payload.extra_entity_fields, payload.examples.iter());
+    values.insert("content", json_escape(&payload.content));
+    values.insert("entities", serde_json::to_string(&payload.entities).unwrap_or_else(|_| "{}".to_string()));
+    values.insert("signals", serde_json::to_string(&payload.signals).unwrap_or_else(|_| "{}".to_string()));
+    fill(&self.body, &values)
+  }
+
+  /// One prompt for all `payloads` (`None` without a batch body). The few-shot examples of the
+  /// messages are merged, taking each message's best ones in turn, and capped at the largest
+  /// single message's count so the batch stays within one message's example budget.
+  pub fn render_batch(&self, payloads: &[ClassifyPayload]) -> Option<String> {
+    let body = self.batch_body.as_deref().filter(|b| !b.trim().is_empty())?;
+    let cap = payloads.iter().map(|p| p.examples.len()).max().unwrap_or(0);
+    let mut seen = std::collections::HashSet::new();
+    let examples: Vec<&FewShotExample> = (0..cap)
+      .flat_map(|i| payloads.iter().filter_map(move |p| p.examples.get(i)))
+      .filter(|e| seen.insert(e.message_id))
+      .take(cap)
+      .collect();
+    let extra_entity_fields = payloads.first().map(|p| p.extra_entity_fields.as_slice()).unwrap_or_default();
+
+    let mut values = shared_values(extra_entity_fields, examples.into_iter());
+    let messages = payloads
+      .iter()
+      .map(|p| {
+        serde_json::json!({
+          "message_id": p.message_id,
+          "content": p.content,
+          "rule_entities": p.entities,
+          "rule_signals": p.signals,
+        })
+        .to_string()
+      })
+      .collect::<Vec<_>>()
+      .join(",\n  ");
+    values.insert("messages", format!("[\n  {messages}\n]"));
+    values.insert("count", payloads.len().to_string());
+    Some(fill(body, &values))
+  }
+
+  /// `None` (or the built-in id) is the built-in template; anything else must exist in `db`.
+  pub fn resolve(db: &Db, version: Option<&str>) -> Result<Self, String> {
+    match version.map(str::trim).filter(|v| !v.is_empty()) {
+      None | Some(BUILTIN_PROMPT_VERSION) => Ok(Self::builtin()),
+      Some(v) => db
+        .dao()
+        .prompt_template_get(v)?
+        .ok_or_else(|| format!("prompt template not found: {v}")),
+    }
+  }
+}
+
+/// The prompt for `payload` with the built-in template.
 pub fn build_prompt(payload: &ClassifyPayload) -> String {
-  // Strict JSON-only instruction.
-  // Model must output ONLY a JSON object. No extra text.
-  let industry_list = INDUSTRIES.join("、");
-  let type_list = SMS_TYPES.join("、");
+  PromptTemplate::builtin().render(payload)
+}
+
+fn check_placeholders(version: &str, body: &str, allowed: &[&str], required: &str) -> Result<(), String> {
+  if !PLACEHOLDER_RE.captures_iter(body).any(|c| &c[1] == required) {
+    return Err(format!("prompt {version}: missing {{{{{required}}}}}"));
+  }
+  if let Some(c) = PLACEHOLDER_RE.captures_iter(body).find(|c| !allowed.contains(&&c[1])) {
+    return Err(format!("prompt {version}: unknown placeholder {}", &c[0]));
+  }
+  Ok(())
+}
+
+// Fill the placeholders in one pass, so values that happen to contain `{{…}}` stay as they are.
+fn fill(body: &str, values: &HashMap<&'static str, String>) -> String {
+  PLACEHOLDER_RE
+    .replace_all(body, |c: &regex::Captures<'_>| {
+      values.get(&c[1]).cloned().unwrap_or_else(|| c[0].to_string())
+    })
+    .into_owned()
+}
 
-  let entities_json = serde_json::to_string(&payload.entities).unwrap_or_else(|_| "{}".to_string());
-  let signals_json = serde_json::to_string(&payload.signals).unwrap_or_else(|_| "{}".to_string());
+fn shared_values<'a>(
+  extra_entity_fields: &[String],
+  examples: impl Iterator<Item = &'a FewShotExample>,
+) -> HashMap<&'static str, String> {
+  let pairs = TAXONOMY
+    .iter()
+    .map(|(industry, types)| format!("  - {industry}：{}", types.join("、")))
+    .collect::<Vec<_>>()
+    .join("\n");
+  let extra_fields = if extra_entity_fields.is_empty() {
+    "无（填 {}）".to_string()
+  } else {
+    extra_entity_fields.join(", ")
+  };
+  let extra_schema = extra_entity_fields
+    .iter()
+    .map(|name| format!("{}: null", json_escape(name)))
+    .collect::<Vec<_>>()
+    .join(", ");
+  let lines = examples.map(render_example).collect::<Vec<_>>();
+  let examples = if lines.is_empty() {
+    String::new()
+  } else {
+    format!(
+      "参考样例（人工复核过的相似短信及其标签，仅供参考，不要照抄实体）：\n{}\n\n",
+      lines.join("\n")
+    )
+  };
 
-  format!(
-    r#"你是“短信智标官”的离线分类与抽取模型。
+  HashMap::from([
+    ("industries", INDUSTRIES.join("、")),
+    ("types", SMS_TYPES.join("、")),
+    ("pairs", pairs),
+    ("extra_fields", extra_fields),
+    ("extra_schema", extra_schema),
+    ("rules_version", RULES_VERSION.to_string()),
+    ("schema_version", SCHEMA_VERSION.to_string()),
+    ("examples", examples),
+  ])
+}
+
+// Strict JSON-only instruction: the model must output ONLY a JSON object, no extra text.
+const BUILTIN_BODY: &str = r#"你是“短信智标官”的离线分类与抽取模型。
 你必须严格输出 JSON（只输出一个 JSON 对象，禁止输出其他任何字符）。
 
 任务：对短信 content 做两层标签与实体抽取补全。
 
 约束：
-- industry 只能取以下枚举之一：{industry_list}
-- type 只能取以下枚举之一：{type_list}
-- entities 必须包含字段：brand, verification_code, amount, balance, account_suffix, time_text, url, phone_in_text；缺失填 null
+- industry 只能取以下枚举之一：{{industries}}
+- type 只能取以下枚举之一：{{types}}
+- industry 与 type 必须是以下合法组合之一（行业：可选类型）：
+{{pairs}}
+- entities 必须包含字段：brand, verification_code, amount, balance, account_suffix, time_text, url, phone_in_text, extra；缺失填 null
+- entities.extra 为对象，包含自定义实体字段：{{extra_fields}}；缺失填 null
+- secondary_types 为可选的次要类型数组（同一短信兼具多种类型时填写，如交易提醒+营销推广），每项 {"type": 类型枚举, "score": 0~1}；不要重复主 type；没有则填 []
+- rule_signals.brand_registry 来自本地品牌库：via 为 "signature" 表示短信【签名】命中，其 industry 可信度高，除非内容明显矛盾应沿用
+- rule_signals.lang 为短信语言（zh/en/mixed）；英文短信同样按上述中文枚举输出 industry/type，实体值保持原文写法
 - confidence 为 0~1 的小数
 - reasons 为字符串数组（简短、可解释）
 - needs_review 为 true/false
-- rules_version 固定为 {rules_version}
-- schema_version 固定为 {schema_version}
+- rules_version 固定为 {{rules_version}}
+- schema_version 固定为 {{schema_version}}
 - model_version 你可以填 "llama"（实际版本由应用覆盖）
 
-输入：
-content: {content}
-rule_entities: {entities_json}
-rule_signals: {signals_json}
+{{examples}}输入：
+content: {{content}}
+rule_entities: {{entities}}
+rule_signals: {{signals}}
 
 输出 JSON schema（示例结构，不要照抄示例值）：
-{{
+{
   "industry": "其他",
   "type": "其他",
-  "entities": {{
+  "secondary_types": [],
+  "entities": {
     "brand": null,
     "verification_code": null,
     "amount": null,
@@ -43,24 +252,82 @@ rule_signals: {signals_json}
     "account_suffix": null,
     "time_text": null,
     "url": null,
-    "phone_in_text": null
-  }},
+    "phone_in_text": null,
+    "extra": {{{extra_schema}}}
+  },
   "confidence": 0.5,
   "needs_review": true,
   "reasons": ["..."],
-  "signals": {{}},
-  "rules_version": "{rules_version}",
+  "signals": {},
+  "rules_version": "{{rules_version}}",
   "model_version": "llama",
-  "schema_version": "{schema_version}"
-}}
-"#,
-    industry_list = industry_list,
-    type_list = type_list,
-    rules_version = RULES_VERSION,
-    schema_version = SCHEMA_VERSION,
-    content = json_escape(&payload.content),
-    entities_json = entities_json,
-    signals_json = signals_json
+  "schema_version": "{{schema_version}}"
+}
+"#;
+
+// Same constraints as `BUILTIN_BODY`, for several messages answered as one JSON array.
+const BUILTIN_BATCH_BODY: &str = r#"你是“短信智标官”的离线分类与抽取模型。
+你必须严格输出 JSON（只输出一个 JSON 数组，禁止输出其他任何字符）。
+
+任务：messages 中共有 {{count}} 条短信，对每条短信的 content 分别做两层标签与实体抽取补全；每条短信输出一个对象，数组中共 {{count}} 个对象。
+
+约束：
+- 每个对象必须包含 message_id，照抄输入中该短信的 message_id；不要合并、遗漏或新增短信
+- industry 只能取以下枚举之一：{{industries}}
+- type 只能取以下枚举之一：{{types}}
+- industry 与 type 必须是以下合法组合之一（行业：可选类型）：
+{{pairs}}
+- entities 必须包含字段：brand, verification_code, amount, balance, account_suffix, time_text, url, phone_in_text, extra；缺失填 null
+- entities.extra 为对象，包含自定义实体字段：{{extra_fields}}；缺失填 null
+- secondary_types 为可选的次要类型数组（同一短信兼具多种类型时填写，如交易提醒+营销推广），每项 {"type": 类型枚举, "score": 0~1}；不要重复主 type；没有则填 []
+- rule_signals.brand_registry 来自本地品牌库：via 为 "signature" 表示短信【签名】命中，其 industry 可信度高，除非内容明显矛盾应沿用
+- rule_signals.lang 为短信语言（zh/en/mixed）；英文短信同样按上述中文枚举输出 industry/type，实体值保持原文写法
+- confidence 为 0~1 的小数
+- reasons 为字符串数组（简短、可解释）
+- needs_review 为 true/false
+- rules_version 固定为 {{rules_version}}
+- schema_version 固定为 {{schema_version}}
+- model_version 你可以填 "llama"（实际版本由应用覆盖）
+
+{{examples}}输入：
+messages: {{messages}}
+
+输出 JSON schema（示例结构，不要照抄示例值）：
+[
+  {
+    "message_id": 0,
+    "industry": "其他",
+    "type": "其他",
+    "secondary_types": [],
+    "entities": {
+      "brand": null,
+      "verification_code": null,
+      "amount": null,
+      "balance": null,
+      "account_suffix": null,
+      "time_text": null,
+      "url": null,
+      "phone_in_text": null,
+      "extra": {{{extra_schema}}}
+    },
+    "confidence": 0.5,
+    "needs_review": true,
+    "reasons": ["..."],
+    "signals": {},
+    "rules_version": "{{rules_version}}",
+    "model_version": "llama",
+    "schema_version": "{{schema_version}}"
+  }
+]
+"#;
+
+/// One few-shot line: the example's content and its reviewed industry/type.
+pub fn render_example(example: &FewShotExample) -> String {
+  format!(
+    "- content: {} → {{\"industry\": {}, \"type\": {}}}",
+    json_escape(&example.content),
+    json_escape(&example.industry),
+    json_escape(&example.sms_type)
   )
 }
 
@@ -88,3 +355,53 @@ pub fn extract_json(text: &str) -> Option<String> {
   }
   None
 }
+
+/// Split a batch answer into the objects it holds, keyed by their `message_id`. Objects without a
+/// usable id are dropped; the caller classifies the messages left over one by one.
+pub fn parse_batch_output(text: &str) -> Result<HashMap<i64, serde_json::Value>, String> {
+  let json = extract_json_array(text).ok_or_else(|| "model output has no JSON array".to_string())?;
+  let items: Vec<serde_json::Value> = serde_json::from_str(&json).map_err(|e| format!("invalid JSON: {e}"))?;
+  Ok(
+    items
+      .into_iter()
+      .filter_map(|v| {
+        let id = match v.get("message_id")? {
+          serde_json::Value::Number(n) => n.as_i64()?,
+          serde_json::Value::String(s) => s.trim().parse().ok()?,
+          _ => return None,
+        };
+        Some((id, v))
+      })
+      .collect(),
+  )
+}
+
+// Like `extract_json` for the first top-level array. Brackets inside strings are skipped, since
+// message contents often carry `【】[]`.
+fn extract_json_array(text: &str) -> Option<String> {
+  let start = text.find('[')?;
+  let (mut depth, mut in_string, mut escaped) = (0i32, false, false);
+  for (i, ch) in text[start..].char_indices() {
+    if in_string {
+      match ch {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match ch {
+      '"' => in_string = true,
+      '[' => depth += 1,
+      ']' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(text[start..start + i + 1].trim().to_string());
+        }
+      }
+      _ => {}
+    }
+  }
+  None
+}
@@ -1,9 +1,21 @@
-use std::{path::PathBuf, process::Command, time::Duration};
+use std::{
+  path::{Path, PathBuf},
+  process::Command,
+  time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{settings::AppSettings, model::schema::{ClassifyPayload, LabelOutput, RULES_VERSION, SCHEMA_VERSION}};
+use crate::{
+  model::{
+    composite::{member_settings, ChainProvider, VoteProvider},
+    llama_server::{self, ServerConfig, ServerState},
+    prompt::PromptTemplate,
+    schema::{ClassifyPayload, EntitySource, LabelOutput, RULES_VERSION, SCHEMA_VERSION},
+  },
+  settings::AppSettings,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderHealth {
@@ -16,13 +28,19 @@ pub struct ProviderHealth {
 pub enum ProviderKind {
   Mock,
   LlamaCli,
+  LlamaServer,
   Ollama,
+  Chain,
+  Vote,
 }
 
 pub fn parse_kind(kind: &str) -> ProviderKind {
   match kind {
     "llama_cli" => ProviderKind::LlamaCli,
+    "llama_server" => ProviderKind::LlamaServer,
     "ollama" => ProviderKind::Ollama,
+    "chain" => ProviderKind::Chain,
+    "vote" => ProviderKind::Vote,
     _ => ProviderKind::Mock,
   }
 }
@@ -70,6 +88,82 @@ pub fn health_check(settings: &AppSettings) -> Result<ProviderHealth, String> {
       })
     }
 
+    ProviderKind::LlamaServer => {
+      let config = match resolve_llama_server(settings) {
+        Ok(c) => c,
+        Err(e) => {
+          return Ok(ProviderHealth {
+            ok: false,
+            message: e,
+            model_version: "unknown".to_string(),
+          })
+        }
+      };
+      let model_version = gguf_version(&config.model_path);
+      // Launching loads the model, so the check only reports on a server batches started.
+      let (ok, message) = match llama_server::state() {
+        ServerState::Stopped => (true, "llama-server ready (starts with the next batch)".to_string()),
+        ServerState::Starting => (true, "llama-server starting".to_string()),
+        ServerState::Running { pid, uptime, restarts } => {
+          let health_url = format!("{}/health", config.base_url());
+          match ureq::get(&health_url).timeout(Duration::from_secs(2)).call() {
+            Ok(_) => (
+              true,
+              format!(
+                "llama-server running (pid {pid}, port {}, up {}s, {restarts} restarts)",
+                config.port,
+                uptime.as_secs()
+              ),
+            ),
+            Err(e) => (false, format!("llama-server not responding (pid {pid}): {e}")),
+          }
+        }
+      };
+      Ok(ProviderHealth {
+        ok,
+        message,
+        model_version,
+      })
+    }
+
+    ProviderKind::Chain | ProviderKind::Vote => {
+      let chain = matches!(kind, ProviderKind::Chain);
+      if settings.provider.members.is_empty() {
+        return Ok(ProviderHealth {
+          ok: false,
+          message: "no provider members configured".to_string(),
+          model_version: "unknown".to_string(),
+        });
+      }
+      let mut parts = vec![];
+      let mut versions = vec![];
+      let (mut any_ok, mut all_ok) = (false, true);
+      for member in &settings.provider.members {
+        let h = match member_settings(settings, member).and_then(|s| health_check(&s)) {
+          Ok(h) => h,
+          Err(e) => ProviderHealth {
+            ok: false,
+            message: e,
+            model_version: "unknown".to_string(),
+          },
+        };
+        any_ok |= h.ok;
+        all_ok &= h.ok;
+        parts.push(format!("{} {} ({})", member.kind, if h.ok { "ok" } else { "FAILED" }, h.message));
+        versions.push(h.model_version);
+      }
+      // A chain works while any member does; a vote missing members is not the vote configured.
+      Ok(ProviderHealth {
+        ok: if chain { any_ok } else { all_ok },
+        message: format!("{}: {}", settings.provider.kind, parts.join("; ")),
+        model_version: if chain {
+          format!("chain({})", versions.join(">"))
+        } else {
+          format!("vote({})", versions.join(","))
+        },
+      })
+    }
+
     ProviderKind::Ollama => {
       let base_url = resolve_ollama_base_url(settings);
       let model = resolve_ollama_model(settings);
@@ -110,6 +204,18 @@ pub fn health_check(settings: &AppSettings) -> Result<ProviderHealth, String> {
 pub trait Provider: Send + Sync {
   fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String>;
   fn model_version(&self) -> String;
+
+  /// Whether [`Provider::classify_many`] answers several messages with one model call.
+  fn supports_batch(&self) -> bool {
+    false
+  }
+
+  /// One result per payload, in order. The default classifies them one at a time; batching
+  /// providers send them in one prompt, and messages missing from the answer come back as errors
+  /// for the caller to retry one by one.
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    payloads.iter().map(|p| self.classify(p, timeout)).collect()
+  }
 }
 
 pub struct MockProvider;
@@ -119,14 +225,19 @@ impl Provider for MockProvider {
     Ok(LabelOutput {
       industry: "其他".to_string(),
       sms_type: "其他".to_string(),
+      secondary_types: vec![],
       entities: payload.entities.clone(),
+      entity_spans: payload.entity_spans.clone(),
       confidence: 0.55,
       needs_review: true,
       reasons: vec!["mock_provider".to_string()],
       signals: payload.signals.clone(),
       rules_version: RULES_VERSION.to_string(),
       model_version: "mock".to_string(),
+      prompt_version: "n/a".to_string(),
+      fusion_policy: "n/a".to_string(),
       schema_version: SCHEMA_VERSION.to_string(),
+      risk: None,
     })
   }
 
@@ -140,6 +251,7 @@ pub struct LlamaCliProvider {
   pub model_path: PathBuf,
   pub temperature: f32,
   pub max_tokens: i32,
+  pub prompt: PromptTemplate,
 }
 
 pub struct OllamaProvider {
@@ -147,33 +259,36 @@ pub struct OllamaProvider {
   pub model: String,
   pub temperature: f32,
   pub max_tokens: i32,
+  pub prompt: PromptTemplate,
 }
 
-impl Provider for LlamaCliProvider {
-  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
-    // NOTE: For full offline embedding, bundle llama-cli in src-tauri/resources and point settings to it.
-    // We run llama-cli with a strict prompt and parse the returned JSON.
-    let prompt = crate::model::prompt::build_prompt(payload);
-
+impl LlamaCliProvider {
+  // NOTE: For full offline embedding, bundle llama-cli in src-tauri/resources and point settings to it.
+  fn generate(&self, prompt: String, max_tokens: i32, timeout: Duration) -> Result<String, String> {
     let mut cmd = Command::new(&self.llama_cli_path);
     cmd.arg("-m")
       .arg(&self.model_path)
       .arg("-p")
       .arg(prompt)
       .arg("-n")
-      .arg(self.max_tokens.to_string())
+      .arg(max_tokens.to_string())
       .arg("--temp")
       .arg(self.temperature.to_string())
       .arg("--no-display-prompt");
 
     let output = run_with_timeout(cmd, timeout)?;
-    let text = String::from_utf8_lossy(&output).to_string();
+    Ok(String::from_utf8_lossy(&output).to_string())
+  }
+}
+
+impl Provider for LlamaCliProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    // We run llama-cli with a strict prompt and parse the returned JSON.
+    let text = self.generate(self.prompt.render(payload), self.max_tokens, timeout)?;
     let json = crate::model::prompt::extract_json(&text).ok_or_else(|| "model output has no JSON".to_string())?;
 
-    let mut parsed: LabelOutput = serde_json::from_str(&json).map_err(|e| format!("invalid JSON: {e}"))?;
-    parsed.model_version = self.model_version();
-    parsed.schema_version = SCHEMA_VERSION.to_string();
-    Ok(parsed.normalize())
+    let parsed: LabelOutput = serde_json::from_str(&json).map_err(|e| format!("invalid JSON: {e}"))?;
+    Ok(finish_model_label(parsed, payload, self.model_version(), &self.prompt))
   }
 
   fn model_version(&self) -> String {
@@ -184,6 +299,69 @@ impl Provider for LlamaCliProvider {
       .unwrap_or("gguf")
       .to_string()
   }
+
+  fn supports_batch(&self) -> bool {
+    self.prompt.supports_batch()
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    let prompt = match self.prompt.render_batch(payloads) {
+      Some(prompt) if payloads.len() > 1 => prompt,
+      _ => return payloads.iter().map(|p| self.classify(p, timeout)).collect(),
+    };
+    let text = self.generate(prompt, self.max_tokens.saturating_mul(payloads.len() as i32), timeout);
+    finish_batch(text, payloads, self.model_version(), &self.prompt)
+  }
+}
+
+/// Talks to one long-lived `llama-server` (see [`llama_server`]) so the model is loaded once per
+/// batch instead of once per message.
+pub struct LlamaServerProvider {
+  config: ServerConfig,
+  temperature: f32,
+  max_tokens: i32,
+  prompt: PromptTemplate,
+}
+
+impl LlamaServerProvider {
+  fn generate(&self, prompt: String, max_tokens: i32, timeout: Duration) -> Result<String, String> {
+    let base_url = llama_server::ensure_running(&self.config)?;
+    match llama_server::complete(&base_url, &prompt, max_tokens, self.temperature, timeout) {
+      // The server died under the request: restart it and ask again once.
+      Err(_) if !matches!(llama_server::state(), ServerState::Running { .. }) => {
+        let base_url = llama_server::ensure_running(&self.config)?;
+        llama_server::complete(&base_url, &prompt, max_tokens, self.temperature, timeout)
+      }
+      r => r,
+    }
+  }
+}
+
+impl Provider for LlamaServerProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    let text = self.generate(self.prompt.render(payload), self.max_tokens, timeout)?;
+    let json = crate::model::prompt::extract_json(&text).ok_or_else(|| "model output has no JSON".to_string())?;
+
+    let parsed: LabelOutput = serde_json::from_str(&json).map_err(|e| format!("invalid JSON: {e}"))?;
+    Ok(finish_model_label(parsed, payload, self.model_version(), &self.prompt))
+  }
+
+  fn model_version(&self) -> String {
+    gguf_version(&self.config.model_path)
+  }
+
+  fn supports_batch(&self) -> bool {
+    self.prompt.supports_batch()
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    let prompt = match self.prompt.render_batch(payloads) {
+      Some(prompt) if payloads.len() > 1 => prompt,
+      _ => return payloads.iter().map(|p| self.classify(p, timeout)).collect(),
+    };
+    let text = self.generate(prompt, self.max_tokens.saturating_mul(payloads.len() as i32), timeout);
+    finish_batch(text, payloads, self.model_version(), &self.prompt)
+  }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -191,9 +369,8 @@ struct OllamaGenerateResponse {
   response: String,
 }
 
-impl Provider for OllamaProvider {
-  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
-    let prompt = crate::model::prompt::build_prompt(payload);
+impl OllamaProvider {
+  fn generate(&self, prompt: String, max_tokens: i32, timeout: Duration) -> Result<String, String> {
     let url = format!("{}/api/generate", self.base_url.trim_end_matches('/'));
 
     let resp = ureq::post(&url)
@@ -204,27 +381,90 @@ impl Provider for OllamaProvider {
         "stream": false,
         "options": {
           "temperature": self.temperature,
-          "num_predict": self.max_tokens
+          "num_predict": max_tokens
         }
       }))
       .map_err(|e| e.to_string())?;
 
     let parsed: OllamaGenerateResponse = resp.into_json().map_err(|e| e.to_string())?;
-    let json_text = crate::model::prompt::extract_json(&parsed.response)
+    Ok(parsed.response)
+  }
+}
+
+impl Provider for OllamaProvider {
+  fn classify(&self, payload: &ClassifyPayload, timeout: Duration) -> Result<LabelOutput, String> {
+    let response = self.generate(self.prompt.render(payload), self.max_tokens, timeout)?;
+    let json_text = crate::model::prompt::extract_json(&response)
       .ok_or_else(|| "ollama output has no JSON".to_string())?;
 
-    let mut label: LabelOutput = serde_json::from_str(&json_text).map_err(|e| format!("invalid JSON: {e}"))?;
-    label.model_version = self.model_version();
-    label.schema_version = SCHEMA_VERSION.to_string();
-    Ok(label.normalize())
+    let label: LabelOutput = serde_json::from_str(&json_text).map_err(|e| format!("invalid JSON: {e}"))?;
+    Ok(finish_model_label(label, payload, self.model_version(), &self.prompt))
   }
 
   fn model_version(&self) -> String {
     self.model.clone()
   }
+
+  fn supports_batch(&self) -> bool {
+    self.prompt.supports_batch()
+  }
+
+  fn classify_many(&self, payloads: &[ClassifyPayload], timeout: Duration) -> Vec<Result<LabelOutput, String>> {
+    let prompt = match self.prompt.render_batch(payloads) {
+      Some(prompt) if payloads.len() > 1 => prompt,
+      _ => return payloads.iter().map(|p| self.classify(p, timeout)).collect(),
+    };
+    let response = self.generate(prompt, self.max_tokens.saturating_mul(payloads.len() as i32), timeout);
+    finish_batch(response, payloads, self.model_version(), &self.prompt)
+  }
+}
+
+// Match a batch answer back to its payloads by `message_id`. A failed call fails every message;
+// a message the answer leaves out or garbles fails alone.
+fn finish_batch(
+  output: Result<String, String>,
+  payloads: &[ClassifyPayload],
+  model_version: String,
+  prompt: &PromptTemplate,
+) -> Vec<Result<LabelOutput, String>> {
+  let mut items = match output.and_then(|text| crate::model::prompt::parse_batch_output(&text)) {
+    Ok(items) => items,
+    Err(e) => return payloads.iter().map(|_| Err(e.clone())).collect(),
+  };
+  payloads
+    .iter()
+    .map(|p| {
+      let item = items
+        .remove(&p.message_id)
+        .ok_or_else(|| format!("message {} missing from batch output", p.message_id))?;
+      let label: LabelOutput = serde_json::from_value(item).map_err(|e| format!("invalid JSON: {e}"))?;
+      Ok(finish_model_label(label, p, model_version.clone(), prompt))
+    })
+    .collect()
 }
 
-pub fn build_provider(settings: &AppSettings) -> Result<Box<dyn Provider>, String> {
+// Common post-processing for parsed model output: stamp versions, carry the rule spans and
+// signals over and record where the model's flat entity values sit in the content.
+fn finish_model_label(
+  mut label: LabelOutput,
+  payload: &ClassifyPayload,
+  model_version: String,
+  prompt: &PromptTemplate,
+) -> LabelOutput {
+  label.model_version = model_version;
+  label.prompt_version = prompt.version.clone();
+  label.schema_version = SCHEMA_VERSION.to_string();
+  for (k, v) in &payload.signals {
+    label.signals.entry(k.clone()).or_insert_with(|| v.clone());
+  }
+  let mut spans = payload.entity_spans.clone();
+  spans.merge_flat(&label.entities, &payload.content, EntitySource::Model);
+  label.entity_spans = spans;
+  label.normalize()
+}
+
+/// `prompt` is the template the model providers render (see `PromptTemplate::resolve`).
+pub fn build_provider(settings: &AppSettings, prompt: &PromptTemplate) -> Result<Box<dyn Provider>, String> {
   match parse_kind(&settings.provider.kind) {
     ProviderKind::Mock => Ok(Box::new(MockProvider)),
     ProviderKind::LlamaCli => {
@@ -246,14 +486,26 @@ pub fn build_provider(settings: &AppSettings) -> Result<Box<dyn Provider>, Strin
         model_path,
         temperature: settings.provider.temperature,
         max_tokens: settings.provider.max_tokens,
+        prompt: prompt.clone(),
       }))
     }
 
+    ProviderKind::LlamaServer => Ok(Box::new(LlamaServerProvider {
+      config: resolve_llama_server(settings)?,
+      temperature: settings.provider.temperature,
+      max_tokens: settings.provider.max_tokens,
+      prompt: prompt.clone(),
+    })),
+
+    ProviderKind::Chain => Ok(Box::new(ChainProvider::build(settings, prompt)?)),
+    ProviderKind::Vote => Ok(Box::new(VoteProvider::build(settings, prompt)?)),
+
     ProviderKind::Ollama => Ok(Box::new(OllamaProvider {
       base_url: resolve_ollama_base_url(settings),
       model: resolve_ollama_model(settings),
       temperature: settings.provider.temperature,
       max_tokens: settings.provider.max_tokens,
+      prompt: prompt.clone(),
     })),
   }
 }
@@ -266,6 +518,38 @@ fn resolve_llama_cli(settings: &AppSettings) -> PathBuf {
   PathBuf::from("resources").join("llama-cli")
 }
 
+fn resolve_llama_server(settings: &AppSettings) -> Result<ServerConfig, String> {
+  let model_path = settings
+    .provider
+    .model_path
+    .clone()
+    .map(PathBuf::from)
+    .ok_or_else(|| "model_path is required".to_string())?;
+  if !model_path.exists() {
+    return Err("model file not found".to_string());
+  }
+  let server_path = match settings.provider.llama_server_path.as_ref() {
+    Some(p) => PathBuf::from(p),
+    None => llama_server::default_server_path(settings.provider.llama_cli_path.as_deref().map(Path::new)),
+  };
+  if !server_path.exists() {
+    return Err(format!("llama-server not found: {}", server_path.display()));
+  }
+  Ok(ServerConfig {
+    server_path,
+    model_path,
+    port: settings.provider.llama_server_port,
+  })
+}
+
+fn gguf_version(model_path: &Path) -> String {
+  model_path
+    .file_name()
+    .and_then(|s| s.to_str())
+    .unwrap_or("gguf")
+    .to_string()
+}
+
 fn resolve_ollama_base_url(settings: &AppSettings) -> String {
   settings
     .provider
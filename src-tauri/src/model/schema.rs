@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{Deserialize, Serialize};
 
-pub const SCHEMA_VERSION: &str = "schema_v1";
+pub const SCHEMA_VERSION: &str = "schema_v3";
 pub const RULES_VERSION: &str = "rules_v1";
 
 // 一级标签：行业大类（固定枚举）
@@ -22,6 +22,113 @@ pub const SMS_TYPES: [&str; 10] = [
   "其他",
 ];
 
+// 行业 → 允许的短信类型（父子关系）。不在表内的组合视为非法。
+pub const TAXONOMY: [(&str, &[&str]); 6] = [
+  (
+    "金融",
+    &["验证码", "交易提醒", "账单催缴", "保险续保", "会员账号变更", "风险提示", "营销推广", "其他"],
+  ),
+  (
+    "通用",
+    &["验证码", "交易提醒", "账单催缴", "物流取件", "会员账号变更", "风险提示", "营销推广", "其他"],
+  ),
+  ("政务", &["验证码", "政务通知", "风险提示", "其他"]),
+  ("渠道", &["验证码", "交易提醒", "账单催缴", "会员账号变更", "营销推广", "其他"]),
+  (
+    "互联网",
+    &["验证码", "交易提醒", "账单催缴", "物流取件", "会员账号变更", "风险提示", "营销推广", "其他"],
+  ),
+  (
+    "其他",
+    &["验证码", "交易提醒", "账单催缴", "保险续保", "物流取件", "会员账号变更", "风险提示", "营销推广", "其他"],
+  ),
+];
+
+pub fn allowed_types(industry: &str) -> &'static [&'static str] {
+  TAXONOMY
+    .iter()
+    .find(|(i, _)| *i == industry)
+    .map(|(_, types)| *types)
+    .unwrap_or(&[])
+}
+
+pub fn is_allowed_pair(industry: &str, sms_type: &str) -> bool {
+  allowed_types(industry).contains(&sms_type)
+}
+
+/// Industries under which `sms_type` is allowed, in `INDUSTRIES` order.
+pub fn industries_for_type(sms_type: &str) -> Vec<&'static str> {
+  TAXONOMY
+    .iter()
+    .filter(|(_, types)| types.contains(&sms_type))
+    .map(|(i, _)| *i)
+    .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoneyDirection {
+  /// 支出（消费/扣款/转出…）
+  Debit,
+  /// 收入（入账/转入/退款…）
+  Credit,
+}
+
+/// 结构化金额：数值已换算到基本单位（`1.2万元` → 12000），`currency` 为 ISO 4217 代码。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Money {
+  pub value: f64,
+  pub currency: String,
+  pub direction: Option<MoneyDirection>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeKind {
+  /// 某个时刻（交易时间、取件时间…）
+  Point,
+  /// 截止时间（`今日20:00前`、`2月5日前还款`、`有效期至…`）
+  Deadline,
+  /// 有效期（`5分钟内有效`），`at` 为 received_at + 时长
+  Validity,
+}
+
+/// 解析后的时间表达式。`at` 为 `YYYY-MM-DD HH:MM:SS`，与 received_at 同一（本地）时区；
+/// 缺少 received_at 又无法单独确定时为 `null`。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeRef {
+  pub kind: TimeKind,
+  pub at: Option<String>,
+  /// 有效期时长（秒），仅 `validity`。
+  #[serde(default)]
+  pub duration_secs: Option<i64>,
+  /// 原文只有日期没有时刻：时刻按当天开始（截止时间按当天结束）补齐。
+  #[serde(default)]
+  pub date_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlListKind {
+  Allow,
+  Deny,
+}
+
+/// 解析后的 URL（见 `rules::url`）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlInfo {
+  /// 原文无协议（如 `t.cn/AbC`）时为 null
+  pub scheme: Option<String>,
+  pub host: String,
+  /// 可注册域名（`a.b.example.com.cn` → `example.com.cn`）；IP 主机为 IP 本身
+  pub registrable_domain: String,
+  pub path: String,
+  pub is_short_link: bool,
+  pub is_ip: bool,
+  /// 命中的本地名单
+  pub list: Option<UrlListKind>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Entities {
   pub brand: Option<String>,
@@ -32,6 +139,283 @@ pub struct Entities {
   pub time_text: Option<String>,
   pub url: Option<String>,
   pub phone_in_text: Option<String>,
+  #[serde(default)]
+  pub amount_detail: Option<Money>,
+  #[serde(default)]
+  pub balance_detail: Option<Money>,
+  /// 第一个截止类时间表达式解析出的绝对时间。
+  #[serde(default)]
+  pub deadline: Option<String>,
+  /// 第一个有效期类时间表达式解析出的失效时间。
+  #[serde(default)]
+  pub valid_until: Option<String>,
+  /// 用户自定义实体（见 `rules::custom`），键为自定义实体名。
+  #[serde(default)]
+  pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntitySource {
+  #[default]
+  Rule,
+  Model,
+  Manual,
+}
+
+/// 单个实体值及其在 content 中的位置（按字符计数的 `[start, end)`；不在原文中时为 null）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySpan {
+  pub value: serde_json::Value,
+  pub start: Option<usize>,
+  pub end: Option<usize>,
+  pub source: EntitySource,
+  /// Structured form of amount/balance values.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub money: Option<Money>,
+  /// Resolved form of time_text values.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub time: Option<TimeRef>,
+  /// Parsed form of url values.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub url_info: Option<UrlInfo>,
+}
+
+impl EntitySpan {
+  /// Locate `value` in `content` (first occurrence) and build a span for it.
+  pub fn locate(value: serde_json::Value, content: &str, source: EntitySource) -> Self {
+    let needle = match &value {
+      serde_json::Value::String(s) => s.clone(),
+      other => other.to_string(),
+    };
+    let (start, end) = match content.find(&needle).filter(|_| !needle.is_empty()) {
+      Some(byte) => {
+        let start = content[..byte].chars().count();
+        (Some(start), Some(start + needle.chars().count()))
+      }
+      None => (None, None),
+    };
+    Self {
+      value,
+      start,
+      end,
+      source,
+      money: None,
+      time: None,
+      url_info: None,
+    }
+  }
+}
+
+/// schema_v3 实体视图：每个字段是多个带位置与来源的值。
+/// `Entities` 仍保留为 v1 扁平视图（每个字段取第一个值）。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntitySpans {
+  #[serde(default)]
+  pub brand: Vec<EntitySpan>,
+  #[serde(default)]
+  pub verification_code: Vec<EntitySpan>,
+  #[serde(default)]
+  pub amount: Vec<EntitySpan>,
+  #[serde(default)]
+  pub balance: Vec<EntitySpan>,
+  #[serde(default)]
+  pub account_suffix: Vec<EntitySpan>,
+  #[serde(default)]
+  pub time_text: Vec<EntitySpan>,
+  #[serde(default)]
+  pub url: Vec<EntitySpan>,
+  #[serde(default)]
+  pub phone_in_text: Vec<EntitySpan>,
+  #[serde(default)]
+  pub extra: BTreeMap<String, Vec<EntitySpan>>,
+}
+
+impl EntitySpans {
+  fn fields_mut(&mut self) -> [(&'static str, &mut Vec<EntitySpan>); 8] {
+    [
+      ("brand", &mut self.brand),
+      ("verification_code", &mut self.verification_code),
+      ("amount", &mut self.amount),
+      ("balance", &mut self.balance),
+      ("account_suffix", &mut self.account_suffix),
+      ("time_text", &mut self.time_text),
+      ("url", &mut self.url),
+      ("phone_in_text", &mut self.phone_in_text),
+    ]
+  }
+
+  /// Every span, built-in fields and custom extractors alike.
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut EntitySpan> {
+    let Self {
+      brand,
+      verification_code,
+      amount,
+      balance,
+      account_suffix,
+      time_text,
+      url,
+      phone_in_text,
+      extra,
+    } = self;
+    [brand, verification_code, amount, balance, account_suffix, time_text, url, phone_in_text]
+      .into_iter()
+      .flat_map(|v| v.iter_mut())
+      .chain(extra.values_mut().flat_map(|v| v.iter_mut()))
+  }
+
+  /// v1 flat view: the first value of every field.
+  pub fn to_flat(&self) -> Entities {
+    let first_str = |v: &[EntitySpan]| v.first().and_then(|s| s.value.as_str()).map(|s| s.to_string());
+    let first_f64 = |v: &[EntitySpan]| v.first().and_then(|s| s.value.as_f64());
+    let mut flat = Entities {
+      brand: first_str(&self.brand),
+      verification_code: first_str(&self.verification_code),
+      amount: first_f64(&self.amount),
+      balance: first_f64(&self.balance),
+      account_suffix: first_str(&self.account_suffix),
+      time_text: first_str(&self.time_text),
+      url: first_str(&self.url),
+      phone_in_text: first_str(&self.phone_in_text),
+      extra: self
+        .extra
+        .iter()
+        .filter_map(|(k, v)| v.first().map(|s| (k.clone(), s.value.clone())))
+        .collect(),
+      ..Default::default()
+    };
+    self.fill_derived(&mut flat);
+    flat
+  }
+
+  /// Recompute the flat fields that are derived from span details
+  /// (`amount_detail`, `balance_detail`, `deadline`, `valid_until`).
+  pub fn fill_derived(&self, entities: &mut Entities) {
+    let first_at = |kind: TimeKind| {
+      self
+        .time_text
+        .iter()
+        .filter_map(|s| s.time.as_ref())
+        .find(|t| t.kind == kind && t.at.is_some())
+        .and_then(|t| t.at.clone())
+    };
+    entities.amount_detail = first_money(&self.amount);
+    entities.balance_detail = first_money(&self.balance);
+    entities.deadline = first_at(TimeKind::Deadline);
+    entities.valid_until = first_at(TimeKind::Validity);
+  }
+
+  /// Reconcile with a flat view coming from `source` (model output or a manual edit):
+  /// a flat value already present in the spans is moved to the front, a new one is
+  /// located in `content` and inserted at the front so `to_flat()` agrees with it.
+  pub fn merge_flat(&mut self, flat: &Entities, content: &str, source: EntitySource) {
+    let flat_json = serde_json::to_value(flat).unwrap_or_default();
+    for (name, spans) in self.fields_mut() {
+      let value = match flat_json.get(name) {
+        Some(v) if !v.is_null() => v.clone(),
+        _ => continue,
+      };
+      merge_value(spans, value, content, source);
+    }
+    for (name, value) in &flat.extra {
+      if value.is_null() {
+        continue;
+      }
+      let spans = self.extra.entry(name.clone()).or_default();
+      merge_value(spans, value.clone(), content, source);
+    }
+  }
+}
+
+/// Structured form of the first amount/balance span. Values typed in by a reviewer carry no
+/// parsed currency/direction, so they default to CNY with no direction.
+fn first_money(spans: &[EntitySpan]) -> Option<Money> {
+  let span = spans.first()?;
+  span.money.clone().or_else(|| {
+    span.value.as_f64().map(|value| Money {
+      value,
+      currency: "CNY".to_string(),
+      direction: None,
+    })
+  })
+}
+
+fn merge_value(spans: &mut Vec<EntitySpan>, value: serde_json::Value, content: &str, source: EntitySource) {
+  match spans.iter().position(|s| s.value == value) {
+    Some(0) => {}
+    Some(i) => {
+      let span = spans.remove(i);
+      spans.insert(0, span);
+    }
+    None => spans.insert(0, EntitySpan::locate(value, content, source)),
+  }
+}
+
+/// 次要类型及其得分（schema_v2 起）。主类型仍在 `LabelOutput.sms_type`。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeScore {
+  #[serde(rename = "type")]
+  pub sms_type: String,
+  pub score: f64,
+}
+
+/// 风险评分中的一项贡献：`weight` 为该信号单独的风险概率。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RiskContribution {
+  pub signal: String,
+  pub weight: f64,
+  #[serde(default)]
+  pub detail: Option<String>,
+}
+
+/// 钓鱼/诈骗风险评分（见 `rules::risk`）。`score = 1 - Π(1 - weight)`，取值 0~1。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RiskAssessment {
+  pub score: f64,
+  pub contributions: Vec<RiskContribution>,
+}
+
+/// A keyword or pattern that counted towards a rule. `start`/`end` are char offsets in the
+/// original content; `None` when the evidence is not in the text (sender, signature lookup).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeywordHit {
+  pub keyword: String,
+  pub start: Option<usize>,
+  pub end: Option<usize>,
+}
+
+/// Outcome of one strong rule. Near misses keep their hits (`matched: false` with hits).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleEval {
+  pub rule: String,
+  #[serde(rename = "type")]
+  pub sms_type: String,
+  pub matched: bool,
+  #[serde(default)]
+  pub industry: Option<String>,
+  #[serde(default)]
+  pub confidence: Option<f64>,
+  #[serde(default)]
+  pub reason: Option<String>,
+  #[serde(default)]
+  pub hits: Vec<KeywordHit>,
+}
+
+/// 规则评估轨迹（`label_explain`）：按顺序评估的全部强规则及最终决定。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTrace {
+  pub rules_version: String,
+  pub lang: String,
+  /// The rules matched a normalized copy of the content (see `rules::normalize`).
+  pub normalized: bool,
+  pub rules: Vec<RuleEval>,
+  /// First matching rule, which decides the label; `None` leaves the decision to the model.
+  pub winner: Option<String>,
+  pub decision: String,
+}
+
+fn not_applicable() -> String {
+  "n/a".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,14 +423,27 @@ pub struct LabelOutput {
   pub industry: String,
   #[serde(rename = "type")]
   pub sms_type: String,
+  #[serde(default)]
+  pub secondary_types: Vec<TypeScore>,
   pub entities: Entities,
+  #[serde(default)]
+  pub entity_spans: EntitySpans,
   pub confidence: f64,
   pub needs_review: bool,
   pub reasons: Vec<String>,
   pub signals: HashMap<String, serde_json::Value>,
   pub rules_version: String,
   pub model_version: String,
+  /// 生成该标签的提示词模板版本（见 `model::prompt`）；未调用模型的标签为 "n/a"。
+  #[serde(default = "not_applicable")]
+  pub prompt_version: String,
+  /// 合并规则与模型结果所用的融合策略（见 `model::fusion`）；人工标签与旧数据为 "n/a"。
+  #[serde(default = "not_applicable")]
+  pub fusion_policy: String,
   pub schema_version: String,
+  /// 规则计算的风险评分；未评分（旧数据）时为 null。
+  #[serde(default)]
+  pub risk: Option<RiskAssessment>,
 }
 
 impl LabelOutput {
@@ -62,6 +459,12 @@ impl LabelOutput {
       self.reasons.push("normalize:invalid_type".to_string());
     }
 
+    if !is_allowed_pair(&self.industry, &self.sms_type) {
+      self.repair_pair();
+    }
+
+    self.normalize_secondary_types();
+
     if !self.confidence.is_finite() {
       self.confidence = 0.5;
       self.needs_review = true;
@@ -87,22 +490,81 @@ impl LabelOutput {
     self
   }
 
+  // Repair an illegal industry/type pair when the type pins down a single industry
+  // (e.g. 政务通知 → 政务); otherwise fall back to type=其他. Always flags for review.
+  fn repair_pair(&mut self) {
+    let original = format!("{}/{}", self.industry, self.sms_type);
+    let candidates = industries_for_type(&self.sms_type);
+    if candidates.len() == 1 {
+      self.industry = candidates[0].to_string();
+    } else {
+      self.sms_type = "其他".to_string();
+    }
+    self.needs_review = true;
+    self.reasons.push(format!(
+      "normalize:illegal_pair:{original}->{}/{}",
+      self.industry, self.sms_type
+    ));
+  }
+
+  pub fn normalize_secondary_types(&mut self) {
+    let mut kept: Vec<TypeScore> = vec![];
+    for mut ts in std::mem::take(&mut self.secondary_types) {
+      if ts.sms_type == self.sms_type || !SMS_TYPES.contains(&ts.sms_type.as_str()) {
+        continue;
+      }
+      if !ts.score.is_finite() {
+        ts.score = 0.5;
+      }
+      ts.score = ts.score.clamp(0.0, 1.0);
+      match kept.iter_mut().find(|k| k.sms_type == ts.sms_type) {
+        Some(k) => k.score = k.score.max(ts.score),
+        None => kept.push(ts),
+      }
+    }
+    kept.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    self.secondary_types = kept;
+  }
+
+  /// Label produced by a strong rule alone (no model involved).
+  pub fn decided_by_rules(&self) -> bool {
+    self.model_version == "n/a" && self.reasons.iter().any(|r| r.starts_with("rule:"))
+  }
+
+  /// Primary type followed by every secondary type.
+  pub fn all_types(&self) -> Vec<&str> {
+    std::iter::once(self.sms_type.as_str())
+      .chain(self.secondary_types.iter().map(|t| t.sms_type.as_str()))
+      .collect()
+  }
+
+  pub fn has_illegal_pair(&self) -> bool {
+    !is_allowed_pair(&self.industry, &self.sms_type)
+      || self.reasons.iter().any(|r| r.starts_with("normalize:illegal_pair:"))
+  }
+
   pub fn error_fallback(
     entities: Entities,
+    entity_spans: EntitySpans,
     signals: HashMap<String, serde_json::Value>,
     err: &str,
   ) -> Self {
     LabelOutput {
       industry: "其他".to_string(),
       sms_type: "其他".to_string(),
+      secondary_types: vec![],
       entities,
+      entity_spans,
       confidence: 0.25,
       needs_review: true,
       reasons: vec![format!("model_error:{err}")],
       signals,
       rules_version: RULES_VERSION.to_string(),
       model_version: "error".to_string(),
+      prompt_version: "n/a".to_string(),
+      fusion_policy: "n/a".to_string(),
       schema_version: SCHEMA_VERSION.to_string(),
+      risk: None,
     }
   }
 }
@@ -126,7 +588,25 @@ pub struct ClassifyPayload {
   pub message_id: i64,
   pub content: String,
   pub entities: Entities,
+  #[serde(default)]
+  pub entity_spans: EntitySpans,
   pub signals: HashMap<String, serde_json::Value>,
+  /// Names of the custom entities the model should fill into `entities.extra`.
+  #[serde(default)]
+  pub extra_entity_fields: Vec<String>,
+  /// Similar reviewed messages shown to the model as few-shot examples (see `model::fewshot`).
+  #[serde(default)]
+  pub examples: Vec<FewShotExample>,
+}
+
+/// A reviewed message and its confirmed label, used as a prompt example.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotExample {
+  pub message_id: i64,
+  pub content: String,
+  pub industry: String,
+  #[serde(rename = "type")]
+  pub sms_type: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
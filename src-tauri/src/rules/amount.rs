@@ -0,0 +1,284 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::model::schema::{Money, MoneyDirection};
+
+/// One monetary amount found in a text. `start`/`end` are byte offsets into the searched text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmountMatch {
+  pub money: Money,
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Find every amount in `content`, in text order.
+///
+/// Recognizes currency prefixes (`￥128.50` / `RMB 20` / `$3.99`), unit suffixes
+/// (`725.00元` / `53,380.47元` / `1.2万元` / `20块` / `15 USD`), a bare 万/亿 right after a
+/// money word (`融资3亿` / `额度5万`), Chinese numerals (`壹佰元` / `三千五百元`) and a leading
+/// minus sign. The debit/credit direction is taken
+/// from the sign or from keywords just before the amount (支出/扣款/消费… vs 入账/转入/退款…).
+pub fn find_amounts(content: &str) -> Vec<AmountMatch> {
+  let mut found: Vec<AmountMatch> = vec![];
+  let taken = |start: usize, end: usize, found: &Vec<AmountMatch>| found.iter().any(|m| start < m.end && m.start < end);
+
+  for c in PREFIX_RE.captures_iter(content) {
+    if let Some(m) = from_prefix(content, &c) {
+      if !taken(m.start, m.end, &found) {
+        found.push(m);
+      }
+    }
+  }
+  for c in SUFFIX_RE.captures_iter(content) {
+    if let Some(m) = from_suffix(content, &c) {
+      if !taken(m.start, m.end, &found) {
+        found.push(m);
+      }
+    }
+  }
+  for c in BARE_UNIT_RE.captures_iter(content) {
+    if let Some(m) = from_bare_unit(content, &c) {
+      if !taken(m.start, m.end, &found) {
+        found.push(m);
+      }
+    }
+  }
+  for c in CN_RE.captures_iter(content) {
+    if let Some(m) = from_chinese(content, &c) {
+      if !taken(m.start, m.end, &found) {
+        found.push(m);
+      }
+    }
+  }
+
+  found.sort_by_key(|m| m.start);
+  found
+}
+
+/// Parse a bare number with optional thousands separators.
+///
+/// `53,380.47` → 53380.47 (comma groups of exactly three digits are thousands separators);
+/// `12,5` → 12.5 (a single comma followed by one or two digits is a decimal comma);
+/// anything else with a comma (e.g. `1,23,4`) is ambiguous and rejected.
+pub fn parse_amount(s: &str) -> Option<f64> {
+  let s = s.trim();
+  if !s.contains(',') {
+    return s.parse::<f64>().ok();
+  }
+  if THOUSANDS_RE.is_match(s) {
+    return s.replace(',', "").parse::<f64>().ok();
+  }
+  if DECIMAL_COMMA_RE.is_match(s) {
+    return s.replace(',', ".").parse::<f64>().ok();
+  }
+  None
+}
+
+/// Parse an amount written in any supported form (`1.2万`, `壹佰元`, `￥1,200`) or a bare number.
+pub fn parse_amount_text(s: &str) -> Option<f64> {
+  let s = s.trim();
+  if let Some(v) = parse_amount(s) {
+    return Some(v);
+  }
+  if let Some(m) = find_amounts(s).into_iter().next() {
+    return Some(m.money.value);
+  }
+  let stripped = s.trim_end_matches(['元', '块']);
+  if let Some(v) = parse_chinese_numeral(stripped) {
+    return Some(v);
+  }
+  let (num, mult) = match stripped.strip_suffix('万') {
+    Some(n) => (n, 1e4),
+    None => match stripped.strip_suffix('亿') {
+      Some(n) => (n, 1e8),
+      None => return None,
+    },
+  };
+  parse_amount(num).map(|v| v * mult)
+}
+
+/// Parse Chinese numerals, both lower-case (三千五百) and financial (壹佰贰拾), up to 亿.
+pub fn parse_chinese_numeral(s: &str) -> Option<f64> {
+  if s.is_empty() {
+    return None;
+  }
+  let mut total = 0f64; // completed 亿/万 sections
+  let mut section = 0f64; // current section below 万
+  let mut digit: Option<f64> = None;
+  for ch in s.chars() {
+    if let Some(d) = cn_digit(ch) {
+      digit = Some(d);
+      continue;
+    }
+    let unit = cn_unit(ch)?;
+    if unit < 10_000 {
+      // "十" alone means 10.
+      section += digit.take().unwrap_or(1.0) * unit as f64;
+      continue;
+    }
+    section += digit.take().unwrap_or(0.0);
+    if section == 0.0 && total == 0.0 {
+      return None;
+    }
+    if unit == 100_000_000 {
+      total = (total + section) * unit as f64;
+    } else {
+      total += section * unit as f64;
+    }
+    section = 0.0;
+  }
+  Some(total + section + digit.unwrap_or(0.0))
+}
+
+fn cn_digit(ch: char) -> Option<f64> {
+  Some(match ch {
+    '零' | '〇' => 0.0,
+    '一' | '壹' => 1.0,
+    '二' | '两' | '贰' => 2.0,
+    '三' | '叁' => 3.0,
+    '四' | '肆' => 4.0,
+    '五' | '伍' => 5.0,
+    '六' | '陆' => 6.0,
+    '七' | '柒' => 7.0,
+    '八' | '捌' => 8.0,
+    '九' | '玖' => 9.0,
+    _ => return None,
+  })
+}
+
+fn cn_unit(ch: char) -> Option<u64> {
+  Some(match ch {
+    '十' | '拾' => 10,
+    '百' | '佰' => 100,
+    '千' | '仟' => 1000,
+    '万' => 10_000,
+    '亿' => 100_000_000,
+    _ => return None,
+  })
+}
+
+// A number that starts right after a digit or comma is the tail of a longer one (`1,23,4元`).
+fn mid_number(content: &str, start: usize) -> bool {
+  content[..start].chars().next_back().is_some_and(|c| c.is_ascii_digit() || c == ',')
+}
+
+fn from_prefix(content: &str, c: &Captures<'_>) -> Option<AmountMatch> {
+  let whole = c.get(0)?;
+  let num = c.name("num").filter(|m| !mid_number(content, m.start()))?;
+  let value = parse_amount(num.as_str())? * unit_multiplier(c.name("unit").map(|m| m.as_str()));
+  let negative = c.name("neg").is_some() || c.name("neg2").is_some();
+  Some(build(content, whole.start(), whole.end(), value, currency_code(c.name("cur")?.as_str()), negative))
+}
+
+fn from_suffix(content: &str, c: &Captures<'_>) -> Option<AmountMatch> {
+  let whole = c.get(0)?;
+  let num = c.name("num").filter(|m| !mid_number(content, m.start()))?;
+  let value = parse_amount(num.as_str())? * unit_multiplier(c.name("unit").map(|m| m.as_str()));
+  let currency = c.name("cur").map(|m| currency_code(m.as_str())).unwrap_or("CNY");
+  Some(build(content, whole.start(), whole.end(), value, currency, c.name("neg").is_some()))
+}
+
+// Only the number and its unit are the amount; the money word in front is context.
+fn from_bare_unit(content: &str, c: &Captures<'_>) -> Option<AmountMatch> {
+  let (num, unit) = (c.name("num")?, c.name("unit")?);
+  if mid_number(content, num.start()) {
+    return None;
+  }
+  let value = parse_amount(num.as_str())? * unit_multiplier(Some(unit.as_str()));
+  Some(build(content, num.start(), unit.end(), value, "CNY", false))
+}
+
+fn from_chinese(content: &str, c: &Captures<'_>) -> Option<AmountMatch> {
+  let whole = c.get(0)?;
+  let value = parse_chinese_numeral(c.name("num")?.as_str())?;
+  Some(build(content, whole.start(), whole.end(), value, "CNY", false))
+}
+
+fn build(content: &str, start: usize, end: usize, value: f64, currency: &str, negative: bool) -> AmountMatch {
+  let direction = if negative {
+    Some(MoneyDirection::Debit)
+  } else {
+    direction_before(&content[..start])
+  };
+  AmountMatch {
+    money: Money {
+      value,
+      currency: currency.to_string(),
+      direction,
+    },
+    start,
+    end,
+  }
+}
+
+// Look at the few characters right before the amount for a debit/credit keyword.
+fn direction_before(before: &str) -> Option<MoneyDirection> {
+  let window: String = {
+    let chars: Vec<char> = before.chars().collect();
+    chars[chars.len().saturating_sub(10)..].iter().collect()
+  };
+  let last = |kws: &[&str]| kws.iter().filter_map(|k| window.rfind(k)).max();
+  let mut debit = last(&["支出", "扣款", "扣费", "消费", "转出", "付款", "支付", "取现", "还款"]);
+  let mut credit = last(&["入账", "转入", "收入", "退款", "到账", "存入", "收款", "返现"]);
+  if debit.is_none() && credit.is_none() {
+    // English wording needs a wider window: "debited by USD 25.00", "refund of $3.99".
+    let window: String = {
+      let chars: Vec<char> = before.chars().collect();
+      chars[chars.len().saturating_sub(24)..].iter().collect::<String>().to_ascii_lowercase()
+    };
+    let last = |kws: &[&str]| kws.iter().filter_map(|k| window.rfind(k)).max();
+    debit = last(super::lang::EN_DEBIT);
+    credit = last(super::lang::EN_CREDIT);
+  }
+  match (debit, credit) {
+    (Some(d), Some(c)) if c > d => Some(MoneyDirection::Credit),
+    (Some(_), _) => Some(MoneyDirection::Debit),
+    (None, Some(_)) => Some(MoneyDirection::Credit),
+    (None, None) => None,
+  }
+}
+
+fn unit_multiplier(unit: Option<&str>) -> f64 {
+  match unit {
+    Some(u) if u.starts_with('万') => 1e4,
+    Some(u) if u.starts_with('亿') => 1e8,
+    _ => 1.0,
+  }
+}
+
+fn currency_code(s: &str) -> &'static str {
+  match s.trim() {
+    "$" | "US$" | "USD" | "美元" | "美金" => "USD",
+    "HK$" | "HKD" | "港币" | "港元" => "HKD",
+    "€" | "EUR" | "欧元" => "EUR",
+    "£" | "GBP" | "英镑" => "GBP",
+    "JPY" | "日元" => "JPY",
+    _ => "CNY",
+  }
+}
+
+const NUM: &str = r"\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:[.,]\d+)?";
+
+static PREFIX_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?P<neg>-)?(?P<cur>￥|¥|RMB|CNY|US\$|HK\$|\$|USD|HKD|EUR|€|GBP|£|JPY)\s*(?P<neg2>-)?(?P<num>{NUM})\s*(?P<unit>万|亿)?(?:元|块)?"
+  ))
+  .unwrap()
+});
+static SUFFIX_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?P<neg>-)?(?P<num>{NUM})\s*(?:(?P<unit>万元|亿元|万|亿)?(?:元|块|人民币)|(?P<cur>美元|美金|港币|港元|欧元|英镑|日元|\s?(?:CNY|RMB|USD|HKD|EUR|GBP|JPY)(?-u:\b)))"
+  ))
+  .unwrap()
+});
+// `3亿用户` is not money: a bare 万/亿 counts only right after a money word.
+static BARE_UNIT_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?:融资|募资|贷款|借款|授信|额度|金额|资金|投资|营收|收入|补贴|奖金|赔偿|罚款)(?:为|达|约|共|高达|总计)?\s*(?P<num>{NUM})\s*(?P<unit>万|亿)"
+  ))
+  .unwrap()
+});
+static CN_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"(?P<num>[零〇一二两三四五六七八九十百千万亿壹贰叁肆伍陆柒捌玖拾佰仟]+)(?:元|块)").unwrap());
+static THOUSANDS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d{1,3}(?:,\d{3})+(?:\.\d+)?$").unwrap());
+static DECIMAL_COMMA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+,\d{1,2}$").unwrap());
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::keywords::{KeywordList, KeywordMatches};
+
+/// 品牌库条目（表 `brand_registry`）：签名或别名 → 规范品牌、行业、机构类型。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrandEntry {
+  pub alias: String,
+  pub brand: String,
+  pub industry: String,
+  pub org_type: Option<String>,
+  /// seed（内置）| manual（手工维护）| learned（从复核结果学习）
+  #[serde(default = "default_source")]
+  pub source: String,
+}
+
+fn default_source() -> String {
+  "manual".to_string()
+}
+
+/// A `【…】` signature at the head or tail of a message. `start`/`end` are char offsets of the
+/// text inside the brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+  pub text: String,
+  pub start: usize,
+  pub end: usize,
+  pub at_head: bool,
+}
+
+/// How a registry entry was matched: a signature lookup is a strong industry signal,
+/// an alias found somewhere in the text only names the brand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrandVia {
+  Signature,
+  Alias,
+}
+
+#[derive(Debug, Clone)]
+pub struct BrandHit {
+  pub entry: BrandEntry,
+  pub via: BrandVia,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BrandRegistry {
+  by_alias: HashMap<String, BrandEntry>,
+  // Aliases sorted longest first so "京东物流" wins over "京东".
+  aliases: Vec<String>,
+}
+
+impl BrandRegistry {
+  pub fn new(entries: Vec<BrandEntry>) -> Self {
+    let mut by_alias = HashMap::new();
+    for e in entries {
+      let key = normalize_alias(&e.alias);
+      if !key.is_empty() {
+        by_alias.insert(key, e);
+      }
+    }
+    let mut aliases: Vec<String> = by_alias.keys().filter(|k| k.chars().count() >= 2).cloned().collect();
+    aliases.sort_by(|a, b| b.chars().count().cmp(&a.chars().count()).then_with(|| a.cmp(b)));
+    Self { by_alias, aliases }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.by_alias.is_empty()
+  }
+
+  /// Aliases looked for in the content, longest first; compiled into the pack's keyword automaton.
+  pub fn aliases(&self) -> &[String] {
+    &self.aliases
+  }
+
+  pub fn lookup(&self, alias: &str) -> Option<&BrandEntry> {
+    self.by_alias.get(&normalize_alias(alias))
+  }
+
+  /// Match the signature first, then the longest alias found by the keyword scan.
+  pub fn match_content(&self, keywords: &KeywordMatches<'_>, signature: Option<&Signature>) -> Option<BrandHit> {
+    if let Some(e) = signature.and_then(|s| self.lookup(&s.text)) {
+      return Some(BrandHit {
+        entry: e.clone(),
+        via: BrandVia::Signature,
+      });
+    }
+    keywords
+      .first_in_list_order(KeywordList::BrandAlias)
+      .and_then(|m| self.by_alias.get(&self.aliases[m.index]))
+      .map(|e| BrandHit {
+        entry: e.clone(),
+        via: BrandVia::Alias,
+      })
+  }
+}
+
+/// Registry key: normalized like rule input, trimmed, brackets removed, ASCII lower-cased.
+pub fn normalize_alias(s: &str) -> String {
+  super::normalize::normalize_text(s)
+    .trim()
+    .trim_matches(|c| matches!(c, '【' | '】' | '[' | ']'))
+    .trim()
+    .to_ascii_lowercase()
+}
+
+/// Extract the `【…】` signature. The head signature wins when both ends carry one.
+pub fn extract_signature(content: &str) -> Option<Signature> {
+  let trimmed_start = content.len() - content.trim_start().len();
+  if let Some(c) = HEAD_SIG_RE.captures(&content[trimmed_start..]) {
+    let m = c.get(1)?;
+    return Some(signature_at(content, trimmed_start + m.start(), m.as_str(), true));
+  }
+  let c = TAIL_SIG_RE.captures(content.trim_end())?;
+  let m = c.get(1)?;
+  Some(signature_at(content, m.start(), m.as_str(), false))
+}
+
+fn signature_at(content: &str, byte_start: usize, text: &str, at_head: bool) -> Signature {
+  let start = content[..byte_start].chars().count();
+  Signature {
+    text: text.trim().to_string(),
+    start,
+    end: start + text.chars().count(),
+    at_head,
+  }
+}
+
+static HEAD_SIG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[【\[]([^【】\[\]\s]{2,20})[】\]]").unwrap());
+static TAIL_SIG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[【\[]([^【】\[\]\s]{2,20})[】\]]$").unwrap());
@@ -0,0 +1,186 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::model::schema::{EntitySource, EntitySpan};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomEntityKind {
+  #[default]
+  Text,
+  Number,
+  Date,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostProcess {
+  Trim,
+  DigitsOnly,
+  ParseAmount,
+}
+
+/// 用户自定义实体抽取器（存放在 settings.json 的 `custom_entities`）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomEntityDef {
+  pub name: String,
+  #[serde(rename = "type", default)]
+  pub kind: CustomEntityKind,
+  pub pattern: String,
+  /// Capture group to take. When unset, the first group that participated in the match is used
+  /// (whole match if the regex has no groups), so alternations can put the value in different groups.
+  #[serde(default)]
+  pub group: Option<usize>,
+  /// If non-empty, the extractor only runs when the content contains one of these keywords.
+  #[serde(default)]
+  pub context_keywords: Vec<String>,
+  #[serde(default)]
+  pub post: Vec<PostProcess>,
+}
+
+pub struct CustomExtractor {
+  pub def: CustomEntityDef,
+  re: Regex,
+}
+
+impl CustomExtractor {
+  pub fn compile(def: &CustomEntityDef) -> Result<Self, String> {
+    let name = def.name.trim();
+    if name.is_empty() {
+      return Err("custom entity name is empty".to_string());
+    }
+    if BUILTIN_FIELDS.contains(&name) {
+      return Err(format!("custom entity name clashes with a built-in field: {name}"));
+    }
+    let re = Regex::new(&def.pattern).map_err(|e| format!("custom entity {name}: invalid regex: {e}"))?;
+    if let Some(g) = def.group {
+      if g >= re.captures_len() {
+        return Err(format!("custom entity {name}: regex has no capture group {g}"));
+      }
+    }
+    Ok(Self { def: def.clone(), re })
+  }
+
+  /// Callers check `context_keywords` first (the rule pack scans for them with its keyword automaton).
+  pub fn extract(&self, content: &str) -> Vec<EntitySpan> {
+    let mut out = vec![];
+    for caps in self.re.captures_iter(content) {
+      let m = match self.def.group {
+        Some(g) => caps.get(g),
+        None => (1..caps.len()).find_map(|i| caps.get(i)).or_else(|| caps.get(0)),
+      };
+      let Some(m) = m else {
+        continue;
+      };
+      let Some(value) = self.post_process(m.as_str()) else {
+        continue;
+      };
+      let start = content[..m.start()].chars().count();
+      out.push(EntitySpan {
+        value,
+        start: Some(start),
+        end: Some(start + m.as_str().chars().count()),
+        source: EntitySource::Rule,
+        money: None,
+        time: None,
+        url_info: None,
+      });
+    }
+    out
+  }
+
+  fn post_process(&self, raw: &str) -> Option<serde_json::Value> {
+    let mut s = raw.to_string();
+    let mut number: Option<f64> = None;
+    for p in &self.def.post {
+      match p {
+        PostProcess::Trim => s = s.trim().to_string(),
+        PostProcess::DigitsOnly => s = s.chars().filter(|c| c.is_ascii_digit()).collect(),
+        PostProcess::ParseAmount => number = Some(super::amount::parse_amount_text(&s)?),
+      }
+    }
+    if s.is_empty() {
+      return None;
+    }
+    match (self.def.kind, number) {
+      (_, Some(n)) => Some(serde_json::json!(n)),
+      (CustomEntityKind::Number, None) => s.parse::<f64>().ok().map(|n| serde_json::json!(n)),
+      _ => Some(serde_json::json!(s)),
+    }
+  }
+}
+
+const BUILTIN_FIELDS: [&str; 13] = [
+  "brand",
+  "verification_code",
+  "amount",
+  "balance",
+  "account_suffix",
+  "time_text",
+  "url",
+  "phone_in_text",
+  "amount_detail",
+  "balance_detail",
+  "deadline",
+  "valid_until",
+  "extra",
+];
+
+const DATE: &str = r"\d{4}[-/.年]\d{1,2}[-/.月]\d{1,2}日?|\d{1,2}月\d{1,2}日";
+
+/// Extractors shipped with the default settings; users can edit or remove them.
+pub fn default_custom_entities() -> Vec<CustomEntityDef> {
+  let def = |name: &str, kind: CustomEntityKind, pattern: String, post: Vec<PostProcess>| CustomEntityDef {
+    name: name.to_string(),
+    kind,
+    pattern,
+    group: None,
+    context_keywords: vec![],
+    post,
+  };
+  vec![
+    def(
+      "order_no",
+      CustomEntityKind::Text,
+      r"(?:订单号|订单编号)[:：\s]*([A-Za-z0-9-]{6,32})".to_string(),
+      vec![PostProcess::Trim],
+    ),
+    def(
+      "license_plate",
+      CustomEntityKind::Text,
+      r"([京津沪渝冀豫云辽黑湘皖鲁新苏浙赣鄂桂甘晋蒙陕吉闽贵粤青藏川宁琼][A-Z][A-Z0-9]{5,6})".to_string(),
+      vec![],
+    ),
+    def(
+      "id_card_suffix",
+      CustomEntityKind::Text,
+      r"(?:身份证|证件)号?(?:码)?(?:尾号|后四位|末四位)[:：\s]*(\d{3}[\dXx])".to_string(),
+      vec![],
+    ),
+    def(
+      "tracking_no",
+      CustomEntityKind::Text,
+      r"(?:运单号|快递单号|物流单号)[:：\s]*([A-Za-z]{0,4}\d{8,20})".to_string(),
+      vec![],
+    ),
+    // Every digit run is matched whole (the second branch swallows the rest), so 95xxx is never
+    // cut out of a longer number; the run only counts when it is not an amount (元/万/decimal).
+    // Nothing is consumed before a run, so adjacent hotlines ("95588/95533") are all found.
+    CustomEntityDef {
+      group: Some(1),
+      context_keywords: ["拨打", "致电", "客服", "热线", "电话"].map(String::from).to_vec(),
+      ..def(
+        "hotline",
+        CustomEntityKind::Text,
+        r"(95\d{3}|[48]00-?\d{3}-?\d{4})(?:[^\d\-.元块万亿]|\.\D|\.?$)|\d[\d\-.]*".to_string(),
+        vec![PostProcess::DigitsOnly],
+      )
+    },
+    def(
+      "due_date",
+      CustomEntityKind::Date,
+      format!(r"(?:到期日|还款日|截止日期?|有效期至)[:：为是\s]*({DATE})|({DATE})(?:前|之前)?(?:到期|截止)"),
+      vec![],
+    ),
+  ]
+}
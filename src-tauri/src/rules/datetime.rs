@@ -0,0 +1,255 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use time::{macros::format_description, Date, Duration, Month, PrimitiveDateTime, Time};
+
+use crate::model::schema::{TimeKind, TimeRef};
+
+/// One time expression found in a text. `start`/`end` are byte offsets into the searched text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeMatch {
+  pub time: TimeRef,
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Find every time expression in `content`, in text order, resolving it against `received_at`.
+///
+/// Recognizes full dates (`2026-02-05 10:30` / `2026年2月5日`), month-day dates with Chinese or
+/// colon clock times (`2月2日10时56分` / `2月5日 下午3点`), relative days (`今日20:00前` / `明天`),
+/// bare clock times (`10:56`) and validity durations (`5分钟内有效` / `有效期30分钟`).
+/// A trailing `前`/`之前`/`截止` or a leading `截止`/`有效期至`/`到期日`… makes the expression a deadline.
+pub fn find_times(content: &str, received_at: Option<&str>) -> Vec<TimeMatch> {
+  let base = received_at.and_then(parse_received_at);
+  let mut found: Vec<TimeMatch> = vec![];
+  let taken = |start: usize, end: usize, found: &Vec<TimeMatch>| found.iter().any(|m| start < m.end && m.start < end);
+
+  let passes: [(&Lazy<Regex>, Resolver); 7] = [
+    (&FULL_DATE_RE, from_full_date),
+    (&MONTH_DAY_RE, from_month_day),
+    (&REL_DAY_RE, from_rel_day),
+    (&DURATION_RE, from_duration),
+    (&VALIDITY_RE, from_duration),
+    (&VALID_SUFFIX_RE, from_duration),
+    (&CLOCK_RE, from_clock),
+  ];
+  for (re, resolve) in passes {
+    for c in re.captures_iter(content) {
+      let Some(whole) = c.get(0) else {
+        continue;
+      };
+      if !digit_bounded(content, whole.start(), whole.end()) || taken(whole.start(), whole.end(), &found) {
+        continue;
+      }
+      let Some(time) = resolve(&c, base) else {
+        continue;
+      };
+      let time = with_context_kind(time, &content[..whole.start()]);
+      found.push(TimeMatch {
+        time,
+        start: whole.start(),
+        end: whole.end(),
+      });
+    }
+  }
+
+  found.sort_by_key(|m| m.start);
+  found
+}
+
+/// Parse a message timestamp such as `2026-02-10 09:12:00`, `2026/2/10 9:12`, `2026-02-10T09:12:00+08:00`
+/// or `2026-02-10`. Any UTC offset is ignored: times are compared as local wall-clock times.
+pub fn parse_received_at(s: &str) -> Option<PrimitiveDateTime> {
+  let c = RECEIVED_AT_RE.captures(s.trim())?;
+  let date = make_date(num(&c, "y")?, num(&c, "mo")?, num(&c, "d")?)?;
+  let time = Time::from_hms(
+    num(&c, "h").unwrap_or(0) as u8,
+    num(&c, "mi").unwrap_or(0) as u8,
+    num(&c, "s").unwrap_or(0) as u8,
+  )
+  .ok()?;
+  Some(PrimitiveDateTime::new(date, time))
+}
+
+pub fn format_datetime(dt: PrimitiveDateTime) -> String {
+  dt.format(format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"))
+    .unwrap_or_default()
+}
+
+type Resolver = fn(&Captures<'_>, Option<PrimitiveDateTime>) -> Option<TimeRef>;
+
+fn from_full_date(c: &Captures<'_>, _base: Option<PrimitiveDateTime>) -> Option<TimeRef> {
+  let date = make_date(num(c, "y")?, num(c, "mo")?, num(c, "d")?)?;
+  Some(on_date(Some(date), c))
+}
+
+fn from_month_day(c: &Captures<'_>, base: Option<PrimitiveDateTime>) -> Option<TimeRef> {
+  let (mo, d) = (num(c, "mo")?, num(c, "d")?);
+  // Validate the month/day even when there is no year to resolve against.
+  make_date(2000, mo, d)?;
+  let date = base.and_then(|b| nearest_month_day(b.date(), mo, d));
+  Some(on_date(date, c))
+}
+
+// A month-day without a year is the occurrence closest to the message: "1月5日前还款" received on
+// 12-28 is next January, "12月30日消费" received on 01-03 last December. 2月29日 falls back to the
+// nearest leap year.
+fn nearest_month_day(base: Date, mo: i32, d: i32) -> Option<Date> {
+  (base.year() - 4..=base.year() + 4)
+    .filter_map(|y| make_date(y, mo, d))
+    .min_by_key(|date| (*date - base).whole_days().abs())
+}
+
+fn from_rel_day(c: &Captures<'_>, base: Option<PrimitiveDateTime>) -> Option<TimeRef> {
+  let offset = match c.name("rel")?.as_str() {
+    "昨天" | "昨日" => -1,
+    "明天" | "明日" => 1,
+    "后天" => 2,
+    _ => 0,
+  };
+  let date = base.and_then(|b| b.date().checked_add(Duration::days(offset)));
+  Some(on_date(date, c))
+}
+
+fn from_clock(c: &Captures<'_>, base: Option<PrimitiveDateTime>) -> Option<TimeRef> {
+  // A bare `3点` / `3点半` is too often a count or a score; it needs 上午/下午/晚上….
+  if c.name("ap").is_none() && c.name("mi").is_none() && c.name("mi2").is_none() {
+    return None;
+  }
+  let time = clock(c)??;
+  Some(TimeRef {
+    kind: kind_from_suffix(c),
+    at: base.map(|b| format_datetime(PrimitiveDateTime::new(b.date(), time))),
+    duration_secs: None,
+    date_only: false,
+  })
+}
+
+fn from_duration(c: &Captures<'_>, base: Option<PrimitiveDateTime>) -> Option<TimeRef> {
+  let n = match c.name("n")?.as_str() {
+    "半" => 0.5,
+    s => s.parse::<f64>().ok().or_else(|| super::amount::parse_chinese_numeral(s))?,
+  };
+  let unit_secs = match c.name("u")?.as_str() {
+    "秒" | "秒钟" => 1.0,
+    "分钟" | "分" => 60.0,
+    "小时" | "个小时" => 3600.0,
+    _ => 86400.0,
+  };
+  let secs = (n * unit_secs).round() as i64;
+  if secs <= 0 {
+    return None;
+  }
+  Some(TimeRef {
+    kind: TimeKind::Validity,
+    // `1234565分钟内有效` is no date; checked so garbage never panics the batch.
+    at: base.and_then(|b| b.checked_add(Duration::seconds(secs))).map(format_datetime),
+    duration_secs: Some(secs),
+    date_only: false,
+  })
+}
+
+// Resolve a date plus the optional clock-time tail of the same match.
+fn on_date(date: Option<Date>, c: &Captures<'_>) -> TimeRef {
+  let kind = kind_from_suffix(c);
+  let (time, date_only) = match clock(c) {
+    Some(Some(t)) => (t, false),
+    // A date-only deadline runs until the end of that day.
+    _ if kind == TimeKind::Deadline => (Time::from_hms(23, 59, 59).unwrap_or(Time::MIDNIGHT), true),
+    _ => (Time::MIDNIGHT, true),
+  };
+  TimeRef {
+    kind,
+    at: date.map(|d| format_datetime(PrimitiveDateTime::new(d, time))),
+    duration_secs: None,
+    date_only,
+  }
+}
+
+// The clock-time tail of a match: None if absent, Some(None) if present but invalid.
+fn clock(c: &Captures<'_>) -> Option<Option<Time>> {
+  let mut h = num(c, "h")?;
+  let mi = num(c, "mi").or_else(|| num(c, "mi2")).unwrap_or(if c.name("half").is_some() { 30 } else { 0 });
+  let s = num(c, "s").or_else(|| num(c, "s2")).unwrap_or(0);
+  match c.name("ap").map(|m| m.as_str()) {
+    Some("下午" | "晚上" | "傍晚") if h < 12 => h += 12,
+    Some("中午") if h < 11 => h += 12,
+    _ => {}
+  }
+  Some(Time::from_hms(h as u8, mi as u8, s as u8).ok())
+}
+
+fn kind_from_suffix(c: &Captures<'_>) -> TimeKind {
+  if c.name("before").is_some() {
+    TimeKind::Deadline
+  } else {
+    TimeKind::Point
+  }
+}
+
+// "截止2026-02-05" / "有效期至…" / "请于…" mark a point in time as a deadline.
+fn with_context_kind(mut time: TimeRef, before: &str) -> TimeRef {
+  if time.kind != TimeKind::Point {
+    return time;
+  }
+  let window: String = {
+    let chars: Vec<char> = before.chars().collect();
+    chars[chars.len().saturating_sub(6)..].iter().collect()
+  };
+  if ["截止", "有效期至", "到期日", "还款日", "最后还款日", "请于", "请在", "务必于"]
+    .iter()
+    .any(|k| window.contains(k))
+  {
+    time.kind = TimeKind::Deadline;
+    if time.date_only {
+      if let Some(at) = time.at.as_mut() {
+        at.replace_range(11.., "23:59:59");
+      }
+    }
+  }
+  time
+}
+
+fn make_date(y: i32, mo: i32, d: i32) -> Option<Date> {
+  let month = Month::try_from(u8::try_from(mo).ok()?).ok()?;
+  Date::from_calendar_date(y, month, u8::try_from(d).ok()?).ok()
+}
+
+fn num(c: &Captures<'_>, name: &str) -> Option<i32> {
+  c.name(name)?.as_str().parse().ok()
+}
+
+// Reject matches glued to other digits (`\b` does not separate digits from CJK characters).
+fn digit_bounded(content: &str, start: usize, end: usize) -> bool {
+  let before = content[..start].chars().next_back();
+  let after = content[end..].chars().next();
+  !before.is_some_and(|c| c.is_ascii_digit()) && !after.is_some_and(|c| c.is_ascii_digit())
+}
+
+const CLOCK_TAIL: &str = r"(?:\s*(?P<ap>凌晨|早上|上午|中午|下午|傍晚|晚上)?\s*(?P<h>\d{1,2})(?:[:：](?P<mi>\d{2})(?:[:：](?P<s>\d{2}))?|[时点](?:(?P<mi2>\d{1,2})分|(?P<half>半))?(?:(?P<s2>\d{1,2})秒)?))?";
+const BEFORE: &str = r"(?P<before>之前|以前|前|截止)?";
+
+static FULL_DATE_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?P<y>\d{{4}})\s*[-/.年]\s*(?P<mo>\d{{1,2}})\s*[-/.月]\s*(?P<d>\d{{1,2}})\s*[日号]?{CLOCK_TAIL}{BEFORE}"
+  ))
+  .unwrap()
+});
+static MONTH_DAY_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(&format!(r"(?P<mo>\d{{1,2}})月(?P<d>\d{{1,2}})[日号]{CLOCK_TAIL}{BEFORE}")).unwrap());
+static REL_DAY_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(r"(?P<rel>今天|今日|当天|当日|本日|明天|明日|后天|昨天|昨日){CLOCK_TAIL}{BEFORE}")).unwrap()
+});
+static CLOCK_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?P<ap>凌晨|早上|上午|中午|下午|傍晚|晚上)?(?P<h>\d{{1,2}})(?:[:：](?P<mi>\d{{2}})(?:[:：](?P<s>\d{{2}}))?|[时点](?:(?P<mi2>\d{{1,2}})分|(?P<half>半))?(?:(?P<s2>\d{{1,2}})秒)?){BEFORE}"
+  ))
+  .unwrap()
+});
+const DURATION_NUM: &str = r"(?P<n>\d+|[一二两三四五六七八九十]+|半)\s*(?P<u>秒钟|秒|分钟|个小时|小时|天|日)";
+static DURATION_RE: Lazy<Regex> = Lazy::new(|| Regex::new(&format!(r"{DURATION_NUM}(?:以内|内)")).unwrap());
+static VALIDITY_RE: Lazy<Regex> = Lazy::new(|| Regex::new(&format!(r"有效期(?:为|是)?\s*{DURATION_NUM}")).unwrap());
+static VALID_SUFFIX_RE: Lazy<Regex> = Lazy::new(|| Regex::new(&format!(r"{DURATION_NUM}有效")).unwrap());
+static RECEIVED_AT_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r"^(?P<y>\d{4})[-/.年](?P<mo>\d{1,2})[-/.月](?P<d>\d{1,2})日?(?:[\sT]+(?P<h>\d{1,2})[:时](?P<mi>\d{1,2})(?:[:分](?P<s>\d{1,2}))?)?")
+    .unwrap()
+});
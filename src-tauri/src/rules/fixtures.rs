@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::schema::{EntitySource, EntitySpan, LabelOutput};
+
+use super::{run_rules_with, RulePack};
+
+/// 规则回归用例（JSONL，一行一条）：短信内容及期望的行业/类型/实体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub id: Option<String>,
+  pub content: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sender: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub received_at: Option<String>,
+  pub expected: FixtureExpect,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub note: Option<String>,
+}
+
+/// Only the fields present are checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FixtureExpect {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub industry: Option<String>,
+  #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+  pub sms_type: Option<String>,
+  /// `true`: a strong rule must decide the label. `false`: the rules must defer to the model.
+  /// Unset: when no strong rule fires, `industry`/`type` are not checked (the model would decide).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub strong_hit: Option<bool>,
+  /// Types that must be among the rule label's secondary types (e.g. a promotion riding on a
+  /// transaction alert).
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub secondary_types: Vec<String>,
+  /// Whether the rule label must (or must not) be flagged for review.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub needs_review: Option<bool>,
+  /// Flat entity values (`verification_code`, `amount`, `extra.order_no`…); `null` asserts absence.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub entities: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureDiff {
+  pub field: String,
+  pub expected: serde_json::Value,
+  pub actual: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureOutcome {
+  /// `id`, or `line <n>` when the fixture has none.
+  pub id: String,
+  pub content: String,
+  pub passed: bool,
+  /// No strong rule fired and the fixture allows that, so industry/type were not checked.
+  pub deferred: bool,
+  pub diffs: Vec<FixtureDiff>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FixtureReport {
+  pub total: usize,
+  pub passed: usize,
+  pub failed: usize,
+  pub deferred: usize,
+  pub outcomes: Vec<FixtureOutcome>,
+}
+
+impl FixtureReport {
+  pub fn ok(&self) -> bool {
+    self.failed == 0
+  }
+
+  /// Human-readable failure listing, one diff per line.
+  pub fn failure_lines(&self) -> Vec<String> {
+    let mut out = vec![];
+    for o in self.outcomes.iter().filter(|o| !o.passed) {
+      out.push(format!("FAIL {}: {}", o.id, o.content));
+      for d in &o.diffs {
+        out.push(format!("  {}: expected {} got {}", d.field, d.expected, d.actual));
+      }
+    }
+    out
+  }
+}
+
+impl Fixture {
+  /// A fixture asserting what a reviewer confirmed. Only entity values the rules or the reviewer
+  /// produced are kept (model-only values are not something the rules promise), and the rules
+  /// are required to decide the label only if they did so when it was labeled.
+  pub fn from_label(id: String, content: String, sender: Option<String>, received_at: Option<String>, label: &LabelOutput) -> Self {
+    let mut entities = BTreeMap::new();
+    let spans = serde_json::to_value(&label.entity_spans).unwrap_or_default();
+    let flat = serde_json::to_value(&label.entities).unwrap_or_default();
+    for (name, value) in flat.as_object().into_iter().flatten() {
+      if value.is_null() || name == "extra" || !spans.get(name).is_some_and(|v| v.is_array()) {
+        continue;
+      }
+      let field: Vec<EntitySpan> = serde_json::from_value(spans[name].clone()).unwrap_or_default();
+      if trusted(&field, value) {
+        entities.insert(name.clone(), value.clone());
+      }
+    }
+    for (name, value) in &label.entities.extra {
+      let field = label.entity_spans.extra.get(name).map(Vec::as_slice).unwrap_or_default();
+      if !value.is_null() && trusted(field, value) {
+        entities.insert(format!("extra.{name}"), value.clone());
+      }
+    }
+    Self {
+      id: Some(id),
+      content,
+      sender,
+      received_at,
+      expected: FixtureExpect {
+        industry: Some(label.industry.clone()),
+        sms_type: Some(label.sms_type.clone()),
+        strong_hit: label.decided_by_rules().then_some(true),
+        secondary_types: vec![],
+        needs_review: None,
+        entities,
+      },
+      note: None,
+    }
+  }
+}
+
+fn trusted(spans: &[EntitySpan], value: &serde_json::Value) -> bool {
+  spans
+    .iter()
+    .find(|s| values_equal(&s.value, value))
+    .is_some_and(|s| s.source != EntitySource::Model)
+}
+
+/// Parse JSONL fixtures. Blank lines and lines starting with `#` are skipped.
+pub fn parse_fixtures(text: &str) -> Result<Vec<(usize, Fixture)>, String> {
+  let mut out = vec![];
+  for (i, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let f: Fixture = serde_json::from_str(line).map_err(|e| format!("fixture line {}: {e}", i + 1))?;
+    out.push((i + 1, f));
+  }
+  Ok(out)
+}
+
+pub fn run_fixtures(pack: &RulePack, fixtures: &[(usize, Fixture)]) -> FixtureReport {
+  let mut report = FixtureReport::default();
+  for (line, f) in fixtures {
+    let outcome = run_one(pack, *line, f);
+    report.total += 1;
+    if outcome.passed {
+      report.passed += 1;
+    } else {
+      report.failed += 1;
+    }
+    if outcome.deferred {
+      report.deferred += 1;
+    }
+    report.outcomes.push(outcome);
+  }
+  report
+}
+
+fn run_one(pack: &RulePack, line: usize, f: &Fixture) -> FixtureOutcome {
+  let r = run_rules_with(pack, &f.content, f.sender.as_deref(), f.received_at.as_deref());
+  let exp = &f.expected;
+  let mut diffs = vec![];
+  let mut diff = |field: &str, expected: serde_json::Value, actual: serde_json::Value| {
+    diffs.push(FixtureDiff {
+      field: field.to_string(),
+      expected,
+      actual,
+    });
+  };
+
+  if let Some(want) = exp.strong_hit {
+    if want != r.strong_hit {
+      diff("strong_hit", want.into(), r.strong_hit.into());
+    }
+  }
+  let deferred = r.label.is_none() && exp.strong_hit.is_none();
+  if !deferred {
+    let industry = r.label.as_ref().map(|l| l.industry.clone());
+    let sms_type = r.label.as_ref().map(|l| l.sms_type.clone());
+    if let Some(want) = &exp.industry {
+      if industry.as_ref() != Some(want) {
+        diff("industry", want.as_str().into(), industry.into());
+      }
+    }
+    if let Some(want) = &exp.sms_type {
+      if sms_type.as_ref() != Some(want) {
+        diff("type", want.as_str().into(), sms_type.into());
+      }
+    }
+    let secondary: Vec<&str> = r
+      .label
+      .iter()
+      .flat_map(|l| l.secondary_types.iter().map(|t| t.sms_type.as_str()))
+      .collect();
+    if let Some(want) = exp.needs_review {
+      let got = r.label.as_ref().map(|l| l.needs_review);
+      if got != Some(want) {
+        diff("needs_review", want.into(), got.into());
+      }
+    }
+    for want in &exp.secondary_types {
+      if !secondary.contains(&want.as_str()) {
+        diff("secondary_types", want.as_str().into(), secondary.clone().into());
+      }
+    }
+  }
+
+  let flat = serde_json::to_value(&r.entities).unwrap_or_default();
+  for (name, want) in &exp.entities {
+    let got = match name.strip_prefix("extra.") {
+      Some(extra) => flat.get("extra").and_then(|e| e.get(extra)),
+      None => flat.get(name),
+    }
+    .cloned()
+    .unwrap_or(serde_json::Value::Null);
+    if !values_equal(want, &got) {
+      diff(&format!("entities.{name}"), want.clone(), got);
+    }
+  }
+
+  FixtureOutcome {
+    id: f.id.clone().unwrap_or_else(|| format!("line {line}")),
+    content: f.content.clone(),
+    passed: diffs.is_empty(),
+    deferred,
+    diffs,
+  }
+}
+
+// `725` and `725.0` are the same amount.
+fn values_equal(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+  match (a.as_f64(), b.as_f64()) {
+    (Some(x), Some(y)) => (x - y).abs() < 1e-9,
+    _ => a == b,
+  }
+}
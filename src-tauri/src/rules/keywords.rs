@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+
+use super::{lang, risk, strong};
+
+/// A keyword list the rules consult. Every list of a rule pack is compiled into one automaton
+/// ([`KeywordIndex`]) so a message is scanned once, whatever the number of lists and entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeywordList {
+  Code,
+  Logistics,
+  Gov,
+  Finance,
+  /// Secondary-type cues.
+  Risk,
+  Marketing,
+  Bill,
+  /// Marks the amount right after it as a balance.
+  Balance,
+  /// Brand names looked for when there is neither a sender nor a signature.
+  BrandFallback,
+  Urgency,
+  Sensitive,
+  EnCode,
+  EnLogistics,
+  EnTransaction,
+  EnRisk,
+  EnMarketing,
+  EnBill,
+  EnUrgency,
+  /// Brand registry aliases, lower-cased; see `BrandRegistry::aliases`.
+  BrandAlias,
+  /// `context_keywords` of the custom extractor at this index.
+  Context(usize),
+}
+
+impl KeywordList {
+  const BUILTIN: [KeywordList; 18] = [
+    KeywordList::Code,
+    KeywordList::Logistics,
+    KeywordList::Gov,
+    KeywordList::Finance,
+    KeywordList::Risk,
+    KeywordList::Marketing,
+    KeywordList::Bill,
+    KeywordList::Balance,
+    KeywordList::BrandFallback,
+    KeywordList::Urgency,
+    KeywordList::Sensitive,
+    KeywordList::EnCode,
+    KeywordList::EnLogistics,
+    KeywordList::EnTransaction,
+    KeywordList::EnRisk,
+    KeywordList::EnMarketing,
+    KeywordList::EnBill,
+    KeywordList::EnUrgency,
+  ];
+
+  fn builtin_words(self) -> &'static [&'static str] {
+    match self {
+      KeywordList::Code => strong::CODE_KEYWORDS,
+      KeywordList::Logistics => strong::LOGISTICS_KEYWORDS,
+      KeywordList::Gov => strong::GOV_KEYWORDS,
+      KeywordList::Finance => strong::FINANCE_KEYWORDS,
+      KeywordList::Risk => super::RISK_CUE_KEYWORDS,
+      KeywordList::Marketing => super::MARKETING_CUE_KEYWORDS,
+      KeywordList::Bill => super::BILL_CUE_KEYWORDS,
+      KeywordList::Balance => super::BALANCE_KEYWORDS,
+      KeywordList::BrandFallback => super::FALLBACK_BRANDS,
+      KeywordList::Urgency => risk::URGENCY_WORDS,
+      KeywordList::Sensitive => risk::SENSITIVE_WORDS,
+      KeywordList::EnCode => lang::EN_CODE,
+      KeywordList::EnLogistics => lang::EN_LOGISTICS,
+      KeywordList::EnTransaction => lang::EN_TRANSACTION,
+      KeywordList::EnRisk => lang::EN_RISK,
+      KeywordList::EnMarketing => lang::EN_MARKETING,
+      KeywordList::EnBill => lang::EN_BILL,
+      KeywordList::EnUrgency => lang::EN_URGENCY,
+      KeywordList::BrandAlias | KeywordList::Context(_) => &[],
+    }
+  }
+
+  fn matching(self) -> Matching {
+    match self {
+      KeywordList::EnCode
+      | KeywordList::EnLogistics
+      | KeywordList::EnTransaction
+      | KeywordList::EnRisk
+      | KeywordList::EnMarketing
+      | KeywordList::EnBill
+      | KeywordList::EnUrgency => Matching::Word,
+      KeywordList::BrandAlias => Matching::IgnoreAsciiCase,
+      _ => Matching::Exact,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Matching {
+  /// Case-sensitive substring (`OTP` must be upper case).
+  Exact,
+  IgnoreAsciiCase,
+  /// ASCII-case-insensitive whole words, as `lang::find_words`.
+  Word,
+}
+
+/// One occurrence of a list entry. `start`/`end` are byte offsets into the scanned text.
+#[derive(Debug, Clone, Copy)]
+pub struct KeywordMatch<'a> {
+  pub list: KeywordList,
+  /// Position of the keyword in its list.
+  pub index: usize,
+  pub keyword: &'a str,
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Every keyword occurrence in a text, overlapping ones included, ordered by position.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordMatches<'a> {
+  found: Vec<KeywordMatch<'a>>,
+}
+
+impl<'a> KeywordMatches<'a> {
+  pub fn len(&self) -> usize {
+    self.found.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.found.is_empty()
+  }
+
+  pub fn of(&self, list: KeywordList) -> impl Iterator<Item = &KeywordMatch<'a>> + '_ {
+    self.found.iter().filter(move |m| m.list == list)
+  }
+
+  pub fn any(&self, list: KeywordList) -> bool {
+    self.of(list).next().is_some()
+  }
+
+  /// Distinct keywords of `list` that occur, in list order.
+  pub fn words(&self, list: KeywordList) -> Vec<&'a str> {
+    let mut hits: Vec<(usize, &'a str)> = self.of(list).map(|m| (m.index, m.keyword)).collect();
+    hits.sort();
+    hits.dedup();
+    hits.into_iter().map(|(_, k)| k).collect()
+  }
+
+  /// The found entry of `list` that comes first in the list (not in the text).
+  pub fn first_in_list_order(&self, list: KeywordList) -> Option<&KeywordMatch<'a>> {
+    self.of(list).min_by_key(|m| m.index)
+  }
+}
+
+struct Pattern {
+  text: String,
+  owners: Vec<(KeywordList, usize)>,
+}
+
+/// All keyword lists of a rule pack in one Aho-Corasick automaton.
+#[derive(Clone)]
+pub struct KeywordIndex {
+  ac: AhoCorasick,
+  patterns: std::sync::Arc<Vec<Pattern>>,
+}
+
+impl Default for KeywordIndex {
+  fn default() -> Self {
+    Self::new(&[], &[])
+  }
+}
+
+impl KeywordIndex {
+  /// The built-in lists plus the registry `aliases` and each custom extractor's `contexts`.
+  pub fn new(aliases: &[String], contexts: &[Vec<String>]) -> Self {
+    let mut by_text: HashMap<String, usize> = HashMap::new();
+    let mut patterns: Vec<Pattern> = vec![];
+    let mut add = |text: &str, list: KeywordList, index: usize| {
+      if text.is_empty() {
+        return;
+      }
+      let slot = *by_text.entry(text.to_string()).or_insert_with(|| {
+        patterns.push(Pattern {
+          text: text.to_string(),
+          owners: vec![],
+        });
+        patterns.len() - 1
+      });
+      patterns[slot].owners.push((list, index));
+    };
+    for list in KeywordList::BUILTIN {
+      for (i, w) in list.builtin_words().iter().enumerate() {
+        add(w, list, i);
+      }
+    }
+    for (i, a) in aliases.iter().enumerate() {
+      add(a, KeywordList::BrandAlias, i);
+    }
+    for (x, words) in contexts.iter().enumerate() {
+      for (i, w) in words.iter().enumerate() {
+        add(w, KeywordList::Context(x), i);
+      }
+    }
+
+    // Case is checked per list after matching, so one automaton serves every list.
+    let ac = AhoCorasickBuilder::new()
+      .match_kind(MatchKind::Standard)
+      .ascii_case_insensitive(true)
+      .build(patterns.iter().map(|p| p.text.as_str()))
+      .expect("keyword automaton");
+    Self {
+      ac,
+      patterns: std::sync::Arc::new(patterns),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.patterns.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.patterns.is_empty()
+  }
+
+  /// One pass over `text`.
+  pub fn scan(&self, text: &str) -> KeywordMatches<'_> {
+    let mut found = vec![];
+    for m in self.ac.find_overlapping_iter(text) {
+      let p = &self.patterns[m.pattern().as_usize()];
+      let (start, end) = (m.start(), m.end());
+      let exact = text.as_bytes()[start..end] == *p.text.as_bytes();
+      let word = lang::is_word_at(text, &p.text, start, end);
+      for &(list, index) in &p.owners {
+        let ok = match list.matching() {
+          Matching::Exact => exact,
+          Matching::IgnoreAsciiCase => true,
+          Matching::Word => word,
+        };
+        if ok {
+          found.push(KeywordMatch {
+            list,
+            index,
+            keyword: &p.text,
+            start,
+            end,
+          });
+        }
+      }
+    }
+    found.sort_by_key(|m| (m.start, m.end));
+    KeywordMatches { found }
+  }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use super::url::{find_urls, UrlLists};
+
+/// Message language, stored as `signals.lang`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+  Zh,
+  En,
+  Mixed,
+}
+
+impl Lang {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Lang::Zh => "zh",
+      Lang::En => "en",
+      Lang::Mixed => "mixed",
+    }
+  }
+
+  /// English keyword sets apply to English and mixed messages.
+  pub fn has_english(self) -> bool {
+    self != Lang::Zh
+  }
+}
+
+/// Classify by the share of Latin letters among Han characters and Latin letters. URLs are left
+/// out, and so are stray tokens (`OTP`, `APP`, a brand) in otherwise Chinese text: a message
+/// needs at least three English words before it counts as mixed. Texts without either script
+/// (digits only) count as Chinese.
+pub fn detect(content: &str) -> Lang {
+  let mut text = content.to_string();
+  for m in find_urls(content, &UrlLists::default()).iter().rev() {
+    text.replace_range(m.start..m.end, " ");
+  }
+
+  let han = text.chars().filter(|c| is_han(*c)).count();
+  let (mut letters, mut words, mut run) = (0usize, 0usize, 0usize);
+  for c in text.chars().chain([' ']) {
+    if c.is_ascii_alphabetic() {
+      letters += 1;
+      run += 1;
+    } else {
+      if run >= 2 {
+        words += 1;
+      }
+      run = 0;
+    }
+  }
+
+  if han == 0 {
+    return if words > 0 { Lang::En } else { Lang::Zh };
+  }
+  let latin_share = letters as f64 / (letters + han) as f64;
+  if words < 3 || latin_share < 0.2 {
+    Lang::Zh
+  } else if latin_share >= 0.85 {
+    Lang::En
+  } else {
+    Lang::Mixed
+  }
+}
+
+/// ASCII-case-insensitive search for whole words/phrases (`pin` does not match `shopping`).
+pub fn contains_word(content: &str, words: &[&str]) -> bool {
+  !find_words(content, words).is_empty()
+}
+
+/// Every whole-word occurrence of `words` as `(word, start, end)` byte ranges into `content`.
+pub fn find_words<'w>(content: &str, words: &[&'w str]) -> Vec<(&'w str, usize, usize)> {
+  // ASCII lower-casing keeps byte offsets intact.
+  let lower = content.to_ascii_lowercase();
+  let mut out = vec![];
+  for w in words {
+    for (i, _) in lower.match_indices(w) {
+      if is_word_at(&lower, w, i, i + w.len()) {
+        out.push((*w, i, i + w.len()));
+      }
+    }
+  }
+  out
+}
+
+/// Whether `word`, found at `start..end` of `text`, is not part of a longer word. An edge of the
+/// word that is itself punctuation (`% off` after `20`) needs no boundary there.
+pub fn is_word_at(text: &str, word: &str, start: usize, end: usize) -> bool {
+  let bounded = |edge: Option<char>, next: Option<char>| {
+    !edge.is_some_and(|c| c.is_ascii_alphanumeric()) || !next.is_some_and(|c| c.is_ascii_alphanumeric())
+  };
+  bounded(word.chars().next(), text[..start].chars().next_back())
+    && bounded(word.chars().next_back(), text[end..].chars().next())
+}
+
+fn is_han(c: char) -> bool {
+  matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+// English keyword sets, lower-case, used alongside the Chinese lists in `rules`.
+
+pub const EN_CODE: &[&str] = &[
+  "verification code",
+  "security code",
+  "login code",
+  "confirmation code",
+  "authentication code",
+  "access code",
+  "passcode",
+  "one-time password",
+  "one time password",
+  "one-time code",
+  "otp",
+  "your code",
+  "code is",
+];
+
+pub const EN_LOGISTICS: &[&str] = &[
+  "out for delivery",
+  "delivery",
+  "delivered",
+  "parcel",
+  "package",
+  "shipment",
+  "shipped",
+  "tracking number",
+  "pick up",
+  "pickup",
+  "courier",
+  "dhl",
+  "fedex",
+  "usps",
+];
+
+pub const EN_TRANSACTION: &[&str] = &[
+  "debited",
+  "credited",
+  "withdrawal",
+  "withdrawn",
+  "purchase",
+  "transaction",
+  "spent",
+  "payment of",
+  "card ending",
+  "account ending",
+  "a/c",
+  "available balance",
+  "transfer",
+  "deposited",
+];
+
+pub const EN_MARKETING: &[&str] = &[
+  "sale",
+  "% off",
+  "discount",
+  "promo",
+  "offer",
+  "deal",
+  "coupon",
+  "limited time",
+  "unsubscribe",
+  "reply stop",
+  "txt stop",
+  "opt out",
+];
+
+pub const EN_RISK: &[&str] = &["do not share", "never share", "don't share", "fraud", "scam", "if this wasn't you", "if this was not you"];
+
+pub const EN_BILL: &[&str] = &["bill", "overdue", "past due", "due date", "minimum payment", "amount due"];
+
+pub const EN_URGENCY: &[&str] = &[
+  "suspended",
+  "locked",
+  "immediately",
+  "urgent",
+  "expire",
+  "expires today",
+  "final notice",
+  "verify your account",
+  "within 24 hours",
+];
+
+pub const EN_DEBIT: &[&str] = &["debited", "spent", "paid", "withdrawn", "charged", "purchase of", "payment of"];
+
+pub const EN_CREDIT: &[&str] = &["credited", "received", "refund", "refunded", "deposited", "deposit of"];
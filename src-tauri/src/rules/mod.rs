@@ -1,253 +1,670 @@
+pub mod amount;
+pub mod brand;
+pub mod custom;
+pub mod datetime;
+pub mod fixtures;
+pub mod keywords;
+pub mod lang;
+pub mod normalize;
+pub mod pack;
+pub mod preview;
+pub mod risk;
+pub mod script;
+pub mod stats;
+pub mod strong;
+pub mod url;
+
 use std::collections::HashMap;
 
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-use crate::model::schema::{Entities, LabelOutput, RULES_VERSION, SCHEMA_VERSION};
+pub use pack::RulePack;
+
+use crate::model::schema::{
+  is_allowed_pair, Entities, EntitySource, EntitySpan, EntitySpans, LabelOutput, RiskAssessment, RuleEval, RuleTrace,
+  TypeScore, UrlInfo, UrlListKind, INDUSTRIES, RULES_VERSION, SCHEMA_VERSION, SMS_TYPES,
+};
+
+use brand::{BrandHit, BrandVia, Signature};
+use keywords::{KeywordList, KeywordMatches};
+use lang::Lang;
 
 #[derive(Debug, Clone)]
 pub struct RuleResult {
   pub label: Option<LabelOutput>,
   pub entities: Entities,
+  pub entity_spans: EntitySpans,
   pub signals: HashMap<String, serde_json::Value>,
   pub strong_hit: bool,
+  pub risk: RiskAssessment,
+  /// Only filled by [`run_rules_traced`].
+  pub trace: Option<RuleTrace>,
+  /// `script_error:<name>: …` for each script that failed; also in the label's `reasons` when
+  /// there is one.
+  pub script_errors: Vec<String>,
 }
 
+/// Run the built-in rules only (no custom extractors).
 pub fn run_rules(content: &str, sender: Option<&str>) -> RuleResult {
+  run_rules_with(&RulePack::default(), content, sender, None)
+}
+
+/// `received_at` anchors relative time expressions (`今日20:00前`, `5分钟内有效`).
+/// Rules match a normalized copy of `content` (see [`normalize::normalize`]); span offsets are
+/// mapped back to the original text before returning.
+pub fn run_rules_with(pack: &RulePack, content: &str, sender: Option<&str>, received_at: Option<&str>) -> RuleResult {
+  evaluate(pack, content, sender, received_at, false)
+}
+
+/// Like [`run_rules_with`], but evaluates every strong rule and returns the full [`RuleTrace`].
+pub fn run_rules_traced(pack: &RulePack, content: &str, sender: Option<&str>, received_at: Option<&str>) -> RuleResult {
+  evaluate(pack, content, sender, received_at, true)
+}
+
+fn evaluate(pack: &RulePack, content: &str, sender: Option<&str>, received_at: Option<&str>, trace: bool) -> RuleResult {
   let mut signals: HashMap<String, serde_json::Value> = HashMap::new();
-  let entities = extract_entities(content, sender, &mut signals);
-
-  // Strong patterns first
-  if let Some(code) = entities.verification_code.clone() {
-    if contains_any(content, &["验证码", "校验码", "动态码", "OTP"]) {
-      signals.insert("rule".to_string(), serde_json::json!("verification_code"));
-      return RuleResult {
-        label: Some(LabelOutput {
-          industry: guess_industry_from_sender(sender).unwrap_or_else(|| "通用".to_string()),
-          sms_type: "验证码".to_string(),
-          entities: entities.clone(),
-          confidence: 0.98,
-          needs_review: false,
-          reasons: vec![format!("rule: verification_code={code}")],
-          signals: signals.clone(),
-          rules_version: RULES_VERSION.to_string(),
-          model_version: "n/a".to_string(),
-          schema_version: SCHEMA_VERSION.to_string(),
-        }),
-        entities,
-        signals,
-        strong_hit: true,
-      };
-    }
+  let normalized = normalize::normalize(content);
+  if normalized.changed() {
+    signals.insert("normalized".to_string(), serde_json::json!(true));
   }
+  let content = normalized.text.as_str();
+  let sender = sender.map(normalize::normalize_text);
+  let sender = sender.as_deref();
+  let lang = lang::detect(content);
+  signals.insert("lang".to_string(), serde_json::json!(lang.as_str()));
+  // Every keyword list, brand alias and custom context keyword in one pass.
+  let keywords = pack.keywords.scan(content);
 
-  if contains_any(content, &["取件码", "快递", "驿站", "柜", "丰巢", "菜鸟", "中通", "圆通", "申通", "韵达", "顺丰", "京东物流"]) {
-    signals.insert("rule".to_string(), serde_json::json!("logistics_pickup"));
-    return RuleResult {
-      label: Some(LabelOutput {
-        industry: "通用".to_string(),
-        sms_type: "物流取件".to_string(),
-        entities: entities.clone(),
-        confidence: 0.92,
-        needs_review: false,
-        reasons: vec!["rule: logistics_pickup".to_string()],
-        signals: signals.clone(),
-        rules_version: RULES_VERSION.to_string(),
-        model_version: "n/a".to_string(),
-        schema_version: SCHEMA_VERSION.to_string(),
+  let signature = brand::extract_signature(content);
+  let brand_hit = pack.brands.match_content(&keywords, signature.as_ref());
+  if let Some(s) = &signature {
+    signals.insert("signature".to_string(), serde_json::json!(s.text));
+  }
+  if let Some(h) = &brand_hit {
+    signals.insert(
+      "brand_registry".to_string(),
+      serde_json::json!({
+        "alias": h.entry.alias,
+        "brand": h.entry.brand,
+        "industry": h.entry.industry,
+        "org_type": h.entry.org_type,
+        "via": match h.via {
+          BrandVia::Signature => "signature",
+          BrandVia::Alias => "alias",
+        },
       }),
-      entities,
-      signals,
-      strong_hit: true,
-    };
+    );
   }
+  // Only a registered signature is trusted as an industry signal.
+  let brand_industry = brand_hit
+    .as_ref()
+    .filter(|h| h.via == BrandVia::Signature)
+    .map(|h| h.entry.industry.as_str());
+  let (entities, mut entity_spans) = extract_entities(
+    pack,
+    content,
+    &keywords,
+    sender,
+    received_at,
+    lang,
+    signature.as_ref(),
+    brand_hit.as_ref(),
+    &mut signals,
+  );
 
-  if contains_any(content, &["公安", "税务", "社保", "公积金", "政府", "政务", "人民法院", "检察院", "交警", "医保"]) {
-    signals.insert("rule".to_string(), serde_json::json!("gov_notice"));
-    return RuleResult {
-      label: Some(LabelOutput {
-        industry: "政务".to_string(),
-        sms_type: "政务通知".to_string(),
-        entities: entities.clone(),
-        confidence: 0.93,
-        needs_review: false,
-        reasons: vec!["rule: gov_org_keyword".to_string()],
-        signals: signals.clone(),
-        rules_version: RULES_VERSION.to_string(),
-        model_version: "n/a".to_string(),
-        schema_version: SCHEMA_VERSION.to_string(),
-      }),
-      entities,
-      signals,
-      strong_hit: true,
-    };
+  let risk = risk::score(content, &keywords, sender, lang, &entity_spans, &signals);
+
+  // Strong patterns, first match wins. With tracing on, the remaining rules are still evaluated.
+  let ctx = strong::RuleCtx {
+    content,
+    keywords: &keywords,
+    sender,
+    lang,
+    brand_industry,
+    entities: &entities,
+    entity_spans: &entity_spans,
+  };
+  let mut evals: Vec<RuleEval> = vec![];
+  let mut winner: Option<RuleEval> = None;
+  for def in &strong::STRONG_RULES {
+    let eval = def.evaluate(&ctx);
+    if eval.matched && winner.is_none() {
+      winner = Some(eval.clone());
+    }
+    if trace {
+      evals.push(eval);
+    } else if winner.is_some() {
+      break;
+    }
   }
 
-  if is_financial_transaction_like(content, sender) {
-    signals.insert("rule".to_string(), serde_json::json!("financial_transaction"));
-    return RuleResult {
-      label: Some(LabelOutput {
-        industry: "金融".to_string(),
-        sms_type: "交易提醒".to_string(),
-        entities: entities.clone(),
-        confidence: 0.90,
-        needs_review: false,
-        reasons: vec!["rule: financial_transaction".to_string()],
-        signals: signals.clone(),
-        rules_version: RULES_VERSION.to_string(),
-        model_version: "n/a".to_string(),
-        schema_version: SCHEMA_VERSION.to_string(),
-      }),
-      entities,
-      signals,
-      strong_hit: true,
+  let label = winner.as_ref().map(|w| {
+    signals.insert("rule".to_string(), serde_json::json!(w.rule));
+    let mut label = strong_label(
+      &keywords,
+      lang,
+      w.industry.as_deref().unwrap_or("通用"),
+      &w.sms_type,
+      w.confidence.unwrap_or_default(),
+      w.reason.clone().unwrap_or_default(),
+      &entities,
+      &entity_spans,
+      &signals,
+    );
+    label.risk = Some(risk.clone());
+    label
+  });
+
+  // Scripts run last and may set or change the candidate; their errors end up in `reasons`.
+  let mut label = label;
+  let mut script_errors = vec![];
+  for script in &pack.scripts {
+    let signals_json = serde_json::json!(signals);
+    let input = script::ScriptInput {
+      content,
+      sender,
+      received_at,
+      lang: lang.as_str(),
+      brand_industry,
+      entities: &entities,
+      signals: &signals_json,
+      risk: &risk,
+      label: label.as_ref(),
+    };
+    let name = format!("script:{}", script.def.name);
+    let outcome = script.run(&input).and_then(|verdict| {
+      verdict
+        .map(|v| apply_script_verdict(&mut label, v, &name, brand_industry, &keywords, lang, &entities, &entity_spans, &signals))
+        .transpose()
+    });
+    let eval = match outcome {
+      Ok(None) => RuleEval {
+        rule: name,
+        sms_type: String::new(),
+        matched: false,
+        industry: None,
+        confidence: None,
+        reason: None,
+        hits: vec![],
+      },
+      Ok(Some(eval)) => {
+        signals.insert("script".to_string(), serde_json::json!(script.def.name));
+        if let Some(l) = label.as_mut() {
+          l.signals.insert("script".to_string(), serde_json::json!(script.def.name));
+          l.risk = Some(risk.clone());
+        }
+        winner = Some(eval.clone());
+        eval
+      }
+      Err(e) => {
+        let reason = format!("script_error:{}: {e}", script.def.name);
+        if let Some(l) = label.as_mut() {
+          l.reasons.push(reason.clone());
+        }
+        script_errors.push(reason.clone());
+        RuleEval {
+          rule: name,
+          sms_type: String::new(),
+          matched: false,
+          industry: None,
+          confidence: None,
+          reason: Some(reason),
+          hits: vec![],
+        }
+      }
     };
+    if trace {
+      evals.push(eval);
+    }
   }
 
-  // No strong hit: only return entities+signals; model will decide.
+  // Offsets so far point into the normalized text.
+  normalized.remap_spans(&mut entity_spans);
+  let label = label.map(|mut l| {
+    normalized.remap_spans(&mut l.entity_spans);
+    risk::flag_high_risk(&mut l);
+    l
+  });
+  let trace = trace.then(|| {
+    for hit in evals.iter_mut().flat_map(|e| e.hits.iter_mut()) {
+      if let (Some(start), Some(end)) = (hit.start, hit.end) {
+        let (start, end) = normalized.to_original(start, end);
+        hit.start = Some(start);
+        hit.end = Some(end);
+      }
+    }
+    let decision = match &winner {
+      Some(w) => format!(
+        "{} → {}/{} ({:.2})",
+        w.rule,
+        w.industry.as_deref().unwrap_or_default(),
+        w.sms_type,
+        w.confidence.unwrap_or_default()
+      ),
+      None => "no strong rule matched; deferred to the model".to_string(),
+    };
+    RuleTrace {
+      rules_version: RULES_VERSION.to_string(),
+      lang: lang.as_str().to_string(),
+      normalized: normalized.changed(),
+      rules: evals,
+      winner: winner.as_ref().map(|w| w.rule.clone()),
+      decision,
+    }
+  });
+
+  // Without a strong hit only entities+signals are returned; the model will decide.
   RuleResult {
-    label: None,
+    strong_hit: label.is_some(),
+    label,
     entities,
+    entity_spans,
     signals,
-    strong_hit: false,
+    risk,
+    trace,
+    script_errors,
   }
 }
 
-fn extract_entities(content: &str, sender: Option<&str>, signals: &mut HashMap<String, serde_json::Value>) -> Entities {
-  let mut out = Entities::default();
+// Sets or updates the candidate label from a script's verdict; returns the script's trace entry.
+#[allow(clippy::too_many_arguments)]
+fn apply_script_verdict(
+  label: &mut Option<LabelOutput>,
+  verdict: script::ScriptVerdict,
+  name: &str,
+  brand_industry: Option<&str>,
+  keywords: &KeywordMatches<'_>,
+  lang: Lang,
+  entities: &Entities,
+  entity_spans: &EntitySpans,
+  signals: &HashMap<String, serde_json::Value>,
+) -> Result<RuleEval, String> {
+  let sms_type = verdict
+    .sms_type
+    .or_else(|| label.as_ref().map(|l| l.sms_type.clone()))
+    .ok_or("result without a candidate label needs a type")?;
+  let industry = verdict
+    .industry
+    .or_else(|| label.as_ref().map(|l| l.industry.clone()))
+    .unwrap_or_else(|| industry_for(brand_industry, &sms_type, "通用"));
+  if !SMS_TYPES.contains(&sms_type.as_str()) {
+    return Err(format!("unknown type {sms_type}"));
+  }
+  if !INDUSTRIES.contains(&industry.as_str()) {
+    return Err(format!("unknown industry {industry}"));
+  }
+  if !is_allowed_pair(&industry, &sms_type) {
+    return Err(format!("disallowed pair {industry}/{sms_type}"));
+  }
+  let confidence = verdict
+    .confidence
+    .or_else(|| label.as_ref().map(|l| l.confidence))
+    .unwrap_or(0.8)
+    .clamp(0.0, 1.0);
+  let reason = match &verdict.reason {
+    Some(r) => format!("rule: {name}: {r}"),
+    None => format!("rule: {name}"),
+  };
 
-  out.brand = extract_brand(content, sender);
-  if let Some(b) = out.brand.as_ref() {
-    signals.insert("brand".to_string(), serde_json::json!(b));
+  match label {
+    Some(l) => {
+      l.industry = industry.clone();
+      l.sms_type = sms_type.clone();
+      l.secondary_types = secondary_types(keywords, lang, &sms_type);
+      l.confidence = confidence;
+      l.reasons.push(reason.clone());
+    }
+    None => {
+      *label = Some(strong_label(
+        keywords,
+        lang,
+        &industry,
+        &sms_type,
+        confidence,
+        reason.clone(),
+        entities,
+        entity_spans,
+        signals,
+      ));
+    }
+  }
+  if let (Some(l), Some(r)) = (label.as_mut(), verdict.needs_review) {
+    l.needs_review = r;
   }
 
-  out.url = URL_RE
-    .find(content)
-    .map(|m| m.as_str().to_string());
-  if out.url.is_some() {
-    signals.insert("has_url".to_string(), serde_json::json!(true));
+  Ok(RuleEval {
+    rule: name.to_string(),
+    sms_type,
+    matched: true,
+    industry: Some(industry),
+    confidence: Some(confidence),
+    reason: Some(reason),
+    hits: vec![],
+  })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn strong_label(
+  keywords: &KeywordMatches<'_>,
+  lang: Lang,
+  industry: &str,
+  sms_type: &str,
+  confidence: f64,
+  reason: String,
+  entities: &Entities,
+  entity_spans: &EntitySpans,
+  signals: &HashMap<String, serde_json::Value>,
+) -> LabelOutput {
+  LabelOutput {
+    industry: industry.to_string(),
+    sms_type: sms_type.to_string(),
+    secondary_types: secondary_types(keywords, lang, sms_type),
+    entities: entities.clone(),
+    entity_spans: entity_spans.clone(),
+    confidence,
+    needs_review: false,
+    reasons: vec![reason],
+    signals: signals.clone(),
+    rules_version: RULES_VERSION.to_string(),
+    model_version: "n/a".to_string(),
+    prompt_version: "n/a".to_string(),
+    fusion_policy: "n/a".to_string(),
+    schema_version: SCHEMA_VERSION.to_string(),
+    risk: None,
   }
+}
 
-  out.phone_in_text = PHONE_RE
-    .find(content)
-    .map(|m| m.as_str().to_string());
+// A registered signature's industry overrides a rule's default industry when the pair is legal.
+fn industry_for(brand_industry: Option<&str>, sms_type: &str, fallback: &str) -> String {
+  match brand_industry {
+    Some(i) if is_allowed_pair(i, sms_type) => i.to_string(),
+    _ => fallback.to_string(),
+  }
+}
 
-  out.verification_code = extract_verification_code(content);
-  if out.verification_code.is_some() {
+// Secondary types are cues that commonly ride along a primary type,
+// e.g. a transaction alert that also carries a fraud warning or a promotion.
+fn secondary_types(keywords: &KeywordMatches<'_>, lang: Lang, primary: &str) -> Vec<TypeScore> {
+  let cue = |zh: KeywordList, en: KeywordList| keywords.any(zh) || (lang.has_english() && keywords.any(en));
+  let mut out = vec![];
+  if primary != "风险提示" && cue(KeywordList::Risk, KeywordList::EnRisk) {
+    out.push(TypeScore { sms_type: "风险提示".to_string(), score: 0.7 });
+  }
+  if primary != "营销推广" && cue(KeywordList::Marketing, KeywordList::EnMarketing) {
+    out.push(TypeScore { sms_type: "营销推广".to_string(), score: 0.6 });
+  }
+  if primary != "账单催缴" && cue(KeywordList::Bill, KeywordList::EnBill) {
+    out.push(TypeScore { sms_type: "账单催缴".to_string(), score: 0.6 });
+  }
+  out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn extract_entities(
+  pack: &RulePack,
+  content: &str,
+  keywords: &KeywordMatches<'_>,
+  sender: Option<&str>,
+  received_at: Option<&str>,
+  lang: Lang,
+  signature: Option<&Signature>,
+  brand_hit: Option<&BrandHit>,
+  signals: &mut HashMap<String, serde_json::Value>,
+) -> (Entities, EntitySpans) {
+  let mut spans = EntitySpans::default();
+
+  if let Some(h) = brand_hit {
+    // Canonical brand, positioned on the signature (or the alias) it was matched from.
+    let mut span = EntitySpan::locate(serde_json::json!(h.entry.brand), content, EntitySource::Rule);
+    let matched = match (h.via, signature) {
+      (BrandVia::Signature, Some(s)) => Some((s.start, s.end)),
+      _ => locate_alias(content, &h.entry.alias),
+    };
+    if let Some((start, end)) = matched {
+      span.start = Some(start);
+      span.end = Some(end);
+    }
+    signals.insert("brand".to_string(), serde_json::json!(h.entry.brand));
+    spans.brand.push(span);
+  } else if let Some(b) = extract_brand(keywords, sender, signature) {
+    signals.insert("brand".to_string(), serde_json::json!(b));
+    spans.brand.push(EntitySpan::locate(serde_json::json!(b), content, EntitySource::Rule));
+  }
+
+  spans.url = extract_urls(pack, content, signals);
+
+  spans.phone_in_text = PHONE_RE
+    .find_iter(content)
+    .map(|m| match_span(content, m, serde_json::json!(m.as_str())))
+    .collect();
+
+  spans.verification_code = extract_verification_codes(content, keywords, lang);
+  if !spans.verification_code.is_empty() {
     signals.insert("has_verification_code".to_string(), serde_json::json!(true));
   }
 
-  out.amount = extract_amount(content, &["金额", "支付", "扣款", "消费", "入账", "转入", "转出", "还款", "退款"]);
-  if out.amount.is_some() {
+  let (amounts, balances) = extract_amounts(content, keywords);
+  spans.amount = amounts;
+  spans.balance = balances;
+  if !spans.amount.is_empty() {
     signals.insert("has_amount".to_string(), serde_json::json!(true));
   }
 
-  out.balance = extract_amount(content, &["余额", "可用余额", "账户余额"]);
+  spans.account_suffix = ACCOUNT_SUFFIX_RE
+    .captures_iter(content)
+    .chain(EN_ACCOUNT_SUFFIX_RE.captures_iter(content).filter(|_| lang.has_english()))
+    .filter_map(|c| c.get(1))
+    .map(|m| match_span(content, m, serde_json::json!(m.as_str())))
+    .collect();
 
-  out.account_suffix = ACCOUNT_SUFFIX_RE
-    .captures(content)
-    .and_then(|c| c.get(1))
-    .map(|m| m.as_str().to_string());
+  spans.time_text = extract_time_texts(content, received_at);
 
-  out.time_text = extract_time_text(content);
+  for (i, x) in pack.custom_extractors.iter().enumerate() {
+    if !x.def.context_keywords.is_empty() && !keywords.any(KeywordList::Context(i)) {
+      continue;
+    }
+    let found = x.extract(content);
+    if !found.is_empty() {
+      spans.extra.insert(x.def.name.clone(), found);
+    }
+  }
 
-  out
+  (spans.to_flat(), spans)
 }
 
-fn extract_brand(content: &str, sender: Option<&str>) -> Option<String> {
+fn match_span(content: &str, m: regex::Match<'_>, value: serde_json::Value) -> EntitySpan {
+  let start = content[..m.start()].chars().count();
+  EntitySpan {
+    value,
+    start: Some(start),
+    end: Some(start + m.as_str().chars().count()),
+    source: EntitySource::Rule,
+    money: None,
+    time: None,
+    url_info: None,
+  }
+}
+
+// Char range of `alias` in `content`, ignoring ASCII case.
+fn locate_alias(content: &str, alias: &str) -> Option<(usize, usize)> {
+  let lower = content.to_ascii_lowercase();
+  let byte = lower.find(&alias.trim().to_ascii_lowercase())?;
+  let start = content[..byte].chars().count();
+  Some((start, start + alias.trim().chars().count()))
+}
+
+fn extract_brand(keywords: &KeywordMatches<'_>, sender: Option<&str>, signature: Option<&Signature>) -> Option<String> {
   if let Some(s) = sender {
     let s = s.trim();
-    if !s.is_empty() {
+    // Service numbers (106…, 95588) and phone numbers name no brand.
+    if !s.is_empty() && !s.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | ' ')) {
       return Some(s.to_string());
     }
   }
-
-  for kw in ["中国银行", "工商银行", "建设银行", "农业银行", "招商银行", "交通银行", "邮储银行", "平安银行", "兴业银行", "中信银行", "浦发银行", "光大银行", "民生银行", "支付宝", "微信", "京东", "美团", "饿了么", "拼多多", "顺丰", "京东物流"] {
-    if content.contains(kw) {
-      return Some(kw.to_string());
-    }
+  if let Some(s) = signature {
+    return Some(s.text.clone());
   }
-  None
+
+  keywords
+    .first_in_list_order(KeywordList::BrandFallback)
+    .map(|m| m.keyword.to_string())
 }
 
-fn extract_verification_code(content: &str) -> Option<String> {
-  // Common: "验证码123456" / "验证码：123456" / "code is 123456"
-  if let Some(c) = CODE_NEAR_KEYWORD_RE
-    .captures(content)
-    .and_then(|c| c.get(1))
-  {
-    return Some(c.as_str().to_string());
+fn extract_urls(pack: &RulePack, content: &str, signals: &mut HashMap<String, serde_json::Value>) -> Vec<EntitySpan> {
+  let found = url::find_urls(content, &pack.url_lists);
+  if found.is_empty() {
+    return vec![];
   }
-
-  // fallback: first 4-8 digit token if message indicates verification
-  if contains_any(content, &["验证码", "校验码", "动态码", "OTP"]) {
-    if let Some(m) = DIGITS_RE.find(content) {
-      return Some(m.as_str().to_string());
-    }
+  let infos: Vec<&UrlInfo> = found.iter().map(|m| &m.info).collect();
+  let domains = |pred: &dyn Fn(&UrlInfo) -> bool| -> Vec<String> {
+    let mut v: Vec<String> = infos.iter().filter(|i| pred(i)).map(|i| i.registrable_domain.clone()).collect();
+    v.sort();
+    v.dedup();
+    v
+  };
+  signals.insert("has_url".to_string(), serde_json::json!(true));
+  signals.insert("url_domains".to_string(), serde_json::json!(domains(&|_| true)));
+  if infos.iter().any(|i| i.is_short_link) {
+    signals.insert("url_short_link".to_string(), serde_json::json!(true));
   }
-  None
-}
-
-fn extract_amount(content: &str, ctx_keywords: &[&str]) -> Option<f64> {
-  if !ctx_keywords.iter().any(|k| content.contains(k)) {
-    // still allow if explicit currency symbol exists
-    if !content.contains('¥') && !content.contains('￥') {
-      return None;
-    }
+  if infos.iter().any(|i| i.is_ip) {
+    signals.insert("url_ip_host".to_string(), serde_json::json!(true));
+  }
+  let denied = domains(&|i| i.list == Some(UrlListKind::Deny));
+  if !denied.is_empty() {
+    signals.insert("url_denylisted".to_string(), serde_json::json!(denied));
+  }
+  if infos.iter().all(|i| i.list == Some(UrlListKind::Allow)) {
+    signals.insert("url_allowlisted".to_string(), serde_json::json!(true));
   }
 
-  AMOUNT_RE
-    .captures(content)
-    .and_then(|c| c.get(2))
-    .and_then(|m| parse_amount(m.as_str()))
-}
-
-fn parse_amount(s: &str) -> Option<f64> {
-  let s = s.replace(',', "").replace('，', "");
-  s.parse::<f64>().ok()
+  found
+    .into_iter()
+    .map(|m| {
+      let start = content[..m.start].chars().count();
+      EntitySpan {
+        start: Some(start),
+        end: Some(start + m.text.chars().count()),
+        value: serde_json::json!(m.text),
+        source: EntitySource::Rule,
+        money: None,
+        time: None,
+        url_info: Some(m.info),
+      }
+    })
+    .collect()
 }
 
-fn extract_time_text(content: &str) -> Option<String> {
-  // Keep original time substring if found.
-  TIME_RE.find(content).map(|m| m.as_str().to_string())
-}
+fn extract_verification_codes(content: &str, keywords: &KeywordMatches<'_>, lang: Lang) -> Vec<EntitySpan> {
+  // Common: "验证码123456" / "验证码：123456"; English: "Your code is 123456" / "G-123456 is your Google verification code"
+  let near: Vec<EntitySpan> = CODE_NEAR_KEYWORD_RE
+    .captures_iter(content)
+    .chain(EN_CODE_RE.captures_iter(content).filter(|_| lang.has_english()))
+    .filter_map(|c| c.get(1).or_else(|| c.get(2)))
+    .map(|m| match_span(content, m, serde_json::json!(m.as_str())))
+    .collect();
+  if !near.is_empty() {
+    return near;
+  }
 
-fn guess_industry_from_sender(sender: Option<&str>) -> Option<String> {
-  let s = sender?.to_lowercase();
-  if contains_any(&s, &["bank", "银行", "证券", "保险"]) {
-    return Some("金融".to_string());
+  // fallback: first 4-8 digit token if message indicates verification, other than an account
+  // suffix (尾号4321) or an amount (5000.00元)
+  if is_code_message(keywords, lang) {
+    let taken: Vec<(usize, usize)> = ACCOUNT_SUFFIX_RE
+      .captures_iter(content)
+      .chain(EN_ACCOUNT_SUFFIX_RE.captures_iter(content))
+      .filter_map(|c| c.get(1))
+      .map(|m| (m.start(), m.end()))
+      .chain(amount::find_amounts(content).into_iter().map(|m| (m.start, m.end)))
+      .collect();
+    let free = |m: &regex::Match<'_>| !taken.iter().any(|&(start, end)| m.start() < end && start < m.end());
+    if let Some(m) = DIGITS_RE.find_iter(content).find(free) {
+      return vec![match_span(content, m, serde_json::json!(m.as_str()))];
+    }
   }
-  None
+  vec![]
 }
 
-fn is_financial_transaction_like(content: &str, sender: Option<&str>) -> bool {
-  if contains_any(content, &["银行", "证券", "保险", "信用卡", "贷款", "还款", "入账", "扣款", "消费", "交易", "转账", "转入", "转出"]) {
-    return true;
-  }
-  if let Some(s) = sender {
-    if contains_any(s, &["银行", "证券", "保险"]) {
-      return true;
+// Returns (amounts, balances). A match preceded closely by a balance keyword is a balance;
+// anything else counts as a transaction amount.
+fn extract_amounts(content: &str, keywords: &KeywordMatches<'_>) -> (Vec<EntitySpan>, Vec<EntitySpan>) {
+  let mut amounts = vec![];
+  let mut balances = vec![];
+  let mut prev_end = 0usize;
+  for m in amount::find_amounts(content) {
+    let before = &content[prev_end..m.start];
+    prev_end = m.end;
+    // The 8 chars before the amount, not reaching back past the previous one.
+    let window_start = before.char_indices().rev().nth(7).map_or(m.start - before.len(), |(i, _)| m.start - before.len() + i);
+    let start = content[..m.start].chars().count();
+    let mut span = EntitySpan {
+      value: serde_json::json!(m.money.value),
+      start: Some(start),
+      end: Some(start + content[m.start..m.end].chars().count()),
+      source: EntitySource::Rule,
+      money: Some(m.money),
+      time: None,
+      url_info: None,
+    };
+    if keywords.of(KeywordList::Balance).any(|k| k.start >= window_start && k.end <= m.start) {
+      // A balance is a state, not a movement of money.
+      if let Some(money) = span.money.as_mut() {
+        money.direction = None;
+      }
+      balances.push(span);
+    } else {
+      amounts.push(span);
     }
   }
-  false
+  (amounts, balances)
 }
 
-fn contains_any(s: &str, kws: &[&str]) -> bool {
-  kws.iter().any(|k| s.contains(k))
+// time_text keeps the original substring; the resolved datetime goes into the span.
+fn extract_time_texts(content: &str, received_at: Option<&str>) -> Vec<EntitySpan> {
+  datetime::find_times(content, received_at)
+    .into_iter()
+    .map(|m| {
+      let start = content[..m.start].chars().count();
+      let text = &content[m.start..m.end];
+      EntitySpan {
+        value: serde_json::json!(text),
+        start: Some(start),
+        end: Some(start + text.chars().count()),
+        source: EntitySource::Rule,
+        money: None,
+        time: Some(m.time),
+        url_info: None,
+      }
+    })
+    .collect()
 }
 
-static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://\S+|www\.[^\s]+\.[^\s]+" ).unwrap());
-static PHONE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b1\d{10}\b" ).unwrap());
-static DIGITS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d{4,8}\b" ).unwrap());
+fn is_code_message(keywords: &KeywordMatches<'_>, lang: Lang) -> bool {
+  keywords.any(KeywordList::Code) || (lang.has_english() && keywords.any(KeywordList::EnCode))
+}
+
+// Keyword lists compiled into the pack's automaton (see `keywords::KeywordList`).
+const RISK_CUE_KEYWORDS: &[&str] = &["如非本人", "若非本人", "谨防", "诈骗", "请勿泄露", "切勿"];
+const MARKETING_CUE_KEYWORDS: &[&str] = &["优惠", "立减", "限时", "抢购", "领取", "退订"];
+const BILL_CUE_KEYWORDS: &[&str] = &["账单", "逾期", "欠费", "最低还款"];
+const BALANCE_KEYWORDS: &[&str] = &["余额", "可用余额", "账户余额"];
+const FALLBACK_BRANDS: &[&str] = &[
+  "中国银行", "工商银行", "建设银行", "农业银行", "招商银行", "交通银行", "邮储银行", "平安银行", "兴业银行", "中信银行",
+  "浦发银行", "光大银行", "民生银行", "支付宝", "微信", "京东", "美团", "饿了么", "拼多多", "顺丰", "京东物流",
+];
+
+static PHONE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?-u:\b)1[3-9]\d{9}(?-u:\b)").unwrap());
+static DIGITS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?-u:\b)\d{4,8}(?-u:\b)").unwrap());
 static CODE_NEAR_KEYWORD_RE: Lazy<Regex> = Lazy::new(|| {
   Regex::new(r"(?:验证码|校验码|动态码|OTP)\D{0,6}(\d{4,8})" ).unwrap()
 });
-static AMOUNT_RE: Lazy<Regex> = Lazy::new(|| {
-  Regex::new(r"((￥|¥|RMB|CNY)\s*)(\d+(?:[\.,]\d+)?)" ).unwrap()
+static EN_CODE_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r"(?i)(?-u:\b)(?:code|passcode|otp|pin)(?:\s+is)?\s*[:：]?\s*(?:G-)?(\d{4,8})(?-u:\b)|(?-u:\b)(?:G-)?(\d{4,8})\s+is\s+your(?-u:\b)").unwrap()
+});
+static EN_ACCOUNT_SUFFIX_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r"(?i)(?:ending(?:\s+in)?|a/c\s*(?:no\.?)?|acct|card\s*[x*]+)\s*[x*]*\s*(\d{3,6})(?-u:\b)").unwrap()
 });
 static ACCOUNT_SUFFIX_RE: Lazy<Regex> = Lazy::new(|| {
   Regex::new(r"(?:尾号|末四位|后四位)\D{0,4}(\d{3,6})" ).unwrap()
 });
-static TIME_RE: Lazy<Regex> = Lazy::new(|| {
-  Regex::new(r"\b\d{4}[-/.年]\d{1,2}[-/.月]\d{1,2}(?:日)?(?:\s*\d{1,2}:\d{2}(?::\d{2})?)?\b|\b\d{1,2}:\d{2}(?::\d{2})?\b" ).unwrap()
-});
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+use crate::model::schema::EntitySpans;
+
+/// Canonical matching text for the rules plus a map from its characters back to the original.
+///
+/// Full-width ASCII (`１２３４５６`, `ＯＴＰ`, `ｗｗｗ．ａ．ｃｏｍ`) becomes half-width, traditional
+/// characters become simplified (`驗證碼` → `验证码`) and zero-width/invisible characters are dropped.
+/// Chinese punctuation (`，。：！（）`) is left alone; the rules already expect it.
+/// Every kept character maps to exactly one original character, so offsets translate one-to-one.
+#[derive(Debug, Clone)]
+pub struct Normalized {
+  pub text: String,
+  // Original char index of each char in `text`.
+  origin: Vec<usize>,
+  original_len: usize,
+  changed: bool,
+}
+
+impl Normalized {
+  /// True if `text` differs from the original content.
+  pub fn changed(&self) -> bool {
+    self.changed
+  }
+
+  /// Translate a char range `[start, end)` of `text` to the original content.
+  pub fn to_original(&self, start: usize, end: usize) -> (usize, usize) {
+    let start_orig = self.origin.get(start).copied().unwrap_or(self.original_len);
+    let end_orig = match end {
+      0 => 0,
+      e => self.origin.get(e - 1).map(|i| i + 1).unwrap_or(self.original_len),
+    };
+    (start_orig, end_orig.max(start_orig))
+  }
+
+  /// Rewrite span offsets found in `text` so they point into the original content.
+  pub fn remap_spans(&self, spans: &mut EntitySpans) {
+    if !self.changed {
+      return;
+    }
+    for span in spans.iter_mut() {
+      if let (Some(start), Some(end)) = (span.start, span.end) {
+        let (start, end) = self.to_original(start, end);
+        span.start = Some(start);
+        span.end = Some(end);
+      }
+    }
+  }
+}
+
+pub fn normalize(content: &str) -> Normalized {
+  let mut text = String::with_capacity(content.len());
+  let mut origin = Vec::with_capacity(content.len());
+  let mut changed = false;
+  let mut original_len = 0;
+  for (i, c) in content.chars().enumerate() {
+    original_len = i + 1;
+    match normalize_char(c) {
+      Some(n) => {
+        changed |= n != c;
+        text.push(n);
+        origin.push(i);
+      }
+      None => changed = true,
+    }
+  }
+  Normalized {
+    text,
+    origin,
+    original_len,
+    changed,
+  }
+}
+
+/// Normalize text that needs no offset map (sender, flags).
+pub fn normalize_text(s: &str) -> String {
+  s.chars().filter_map(normalize_char).collect()
+}
+
+fn normalize_char(c: char) -> Option<char> {
+  match c {
+    // Zero-width space/joiners, direction marks, word joiner, BOM, soft hyphen, Mongolian vowel separator.
+    '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}' | '\u{00AD}' | '\u{180E}' => None,
+    '\u{3000}' => Some(' '),
+    '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' | '．' | '／' | '＠' | '＃' | '％' | '＆' | '＋' | '－' | '＝' | '＿' | '～' => {
+      char::from_u32(c as u32 - 0xFEE0)
+    }
+    _ => Some(TRAD_TO_SIMP.get(&c).copied().unwrap_or(c)),
+  }
+}
+
+static TRAD_TO_SIMP: Lazy<HashMap<char, char>> = Lazy::new(|| {
+  let chars: Vec<char> = TRAD_SIMP_PAIRS.concat().chars().collect();
+  chars.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+});
+
+// Traditional → simplified pairs for characters common in SMS (banking, payments, logistics,
+// government, scam wording). Not a full conversion table.
+const TRAD_SIMP_PAIRS: &[&str] = &[
+  "並并亂乱佈布佔占併并來来係系倉仓個个們们側侧偵侦傑杰備备傳传債债傷伤僅仅價价儀仪億亿儘尽優优儲储兌兑兒儿內内凍冻剛刚創创",
+  "劃划劍剑劑剂動动務务勝胜勞劳勢势勵励匯汇區区協协卻却厲厉參参員员問问啟启喚唤單单嗎吗嘆叹噸吨嚮向嚴严國国圍围園园圖图團团",
+  "執执堅坚報报場场塊块墊垫壇坛壓压壞坏壯壮壽寿夠够夢梦夾夹奪夺奮奋妝妆娛娱婦妇學学實实寧宁審审寫写寵宠寶宝將将專专尋寻對对",
+  "導导屆届層层屬属島岛師师帳账帶带幣币幫帮幾几庫库廠厂廢废廣广廳厅張张強强彈弹彎弯彙汇後后徑径從从復复徵征徹彻恆恒惡恶態态",
+  "慣惯慮虑慶庆憑凭憲宪憶忆應应懷怀戰战戲戏戶户捨舍掃扫揚扬換换損损摺折撥拨撲扑擁拥擇择擊击擋挡擔担據据擬拟擴扩擾扰攔拦攜携",
+  "攝摄攤摊敗败數数斷断於于時时暈晕暫暂曆历書书會会東东條条棄弃業业極极榮荣構构槍枪樂乐樓楼標标樣样機机檔档檢检權权歐欧歡欢",
+  "歲岁歷历歸归殘残殺杀毀毁氣气氫氢決决沒没況况淨净減减測测準准溝沟溫温溼湿滅灭滬沪滯滞滿满漁渔漢汉漲涨潔洁濕湿濟济濱滨濾滤",
+  "瀏浏灑洒灣湾災灾為为烏乌無无煙烟煩烦熱热燈灯燒烧營营爐炉爭争牆墙牽牵狀状猶犹獎奖獨独獲获獵猎獻献現现瑪玛環环產产畝亩畢毕",
+  "畫画異异當当疊叠瘋疯療疗發发盜盗盡尽監监盤盘眾众碩硕確确碼码礎础礙碍禍祸禪禅禮礼稅税種种稱称積积穩稳窩窝窮穷竄窜竊窃競竞",
+  "筆笔節节範范築筑簡简簽签籌筹糧粮糾纠紀纪約约紅红紋纹納纳紙纸級级紛纷紮扎細细終终組组結结絕绝絡络絲丝綁绑經经綜综綠绿維维",
+  "網网緊紧緒绪線线編编緩缓練练縣县縮缩縱纵總总績绩繩绳繫系繳缴繼继續续罰罚罵骂罷罢羅罗義义習习聖圣聞闻聯联聰聪聲声職职聽听",
+  "脅胁脫脱腦脑膚肤膽胆臉脸臨临臺台與与興兴舉举舊旧艦舰艱艰莊庄華华蓋盖薦荐藍蓝藝艺藥药蘇苏蘋苹蘭兰處处號号虧亏蝦虾蟲虫衆众",
+  "術术衛卫衝冲裏里補补裝装裡里製制複复襲袭見见規规視视親亲覺觉覽览觀观觸触訂订計计訊讯訓训訖讫託托記记訪访設设許许訴诉診诊",
+  "註注詐诈評评詞词詢询試试話话該该詳详誇夸誌志認认誕诞誘诱語语誠诚誤误說说誰谁課课調调談谈請请諒谅論论諮咨諸诸諾诺謀谋謂谓",
+  "謊谎講讲謝谢謹谨證证識识譯译議议護护讀读變变讓让讚赞豐丰貓猫貝贝負负財财貢贡貧贫貨货販贩貪贪貫贯貴贵買买貸贷費费貼贴貿贸",
+  "賀贺資资賓宾賠赔賣卖賤贱賦赋質质賬账賭赌賴赖賺赚購购賽赛贈赠贊赞贏赢贖赎趕赶趨趋跡迹蹟迹蹤踪躍跃車车軌轨軍军軟软較较載载",
+  "輔辅輕轻輛辆輪轮輯辑輸输轉转轟轰辦办辭辞辯辩農农這这週周進进遊游運运過过達达違违遙遥遞递遠远適适遲迟遷迁選选遺遗還还邊边",
+  "邏逻郵邮鄉乡鄧邓鄭郑鄰邻醫医醬酱釋释針针釣钓鈔钞鈴铃鉅巨銀银銷销鋒锋鋪铺鋼钢錄录錢钱錦锦錯错鍋锅鍵键鎖锁鎮镇鏈链鏡镜鐘钟",
+  "鐵铁鑰钥長长門门閃闪閉闭開开閒闲間间閣阁閱阅闆板闊阔闖闯關关陣阵陳陈陸陆陽阳隊队階阶際际隨随險险隱隐隻只雖虽雙双雜杂雞鸡",
+  "離离難难雲云電电霧雾靈灵靜静韓韩韻韵響响頁页頂顶項项順顺須须預预頒颁頓顿頗颇領领頭头頸颈頻频顆颗題题額额顏颜願愿類类顧顾",
+  "顯显風风颱台飄飘飛飞飯饭飲饮飼饲餅饼養养餓饿餘余館馆馬马駐驻駕驾騎骑騙骗騰腾驅驱驗验驚惊驟骤髒脏體体髮发鬆松鬍胡鬥斗鬧闹",
+  "魚鱼鳥鸟鳳凤鴨鸭鹽盐麗丽麥麦麵面麼么黃黄點点黨党黴霉齊齐齒齿齡龄龍龙龜龟",
+];
@@ -0,0 +1,80 @@
+use crate::{db::Db, settings::AppSettings};
+
+use super::{
+  brand::{BrandEntry, BrandRegistry},
+  custom::CustomExtractor,
+  keywords::KeywordIndex,
+  script::ScriptRule,
+  url::{UrlListEntry, UrlLists},
+};
+
+/// Compiled, user-configurable part of the rule engine. Built once per batch and shared by workers.
+#[derive(Default)]
+pub struct RulePack {
+  pub custom_extractors: Vec<CustomExtractor>,
+  pub brands: BrandRegistry,
+  pub url_lists: UrlLists,
+  /// Every keyword list of the pack (built-in, brand aliases, custom contexts) in one automaton.
+  /// Rebuilt whenever brands or extractors change.
+  pub keywords: KeywordIndex,
+  /// Enabled `scripts` of the settings, run in order after the strong rules.
+  pub scripts: Vec<ScriptRule>,
+}
+
+impl RulePack {
+  pub fn from_settings(settings: &AppSettings) -> Result<Self, String> {
+    let custom_extractors = settings
+      .custom_entities
+      .iter()
+      .map(CustomExtractor::compile)
+      .collect::<Result<Vec<_>, _>>()?;
+    let scripts = settings
+      .scripts
+      .iter()
+      .filter(|s| s.enabled)
+      .map(ScriptRule::compile)
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(
+      Self {
+        custom_extractors,
+        brands: BrandRegistry::default(),
+        url_lists: UrlLists::default(),
+        keywords: KeywordIndex::default(),
+        scripts,
+      }
+      .with_keywords(),
+    )
+  }
+
+  /// Settings plus the brand registry and URL lists stored in `db`.
+  pub fn load(settings: &AppSettings, db: &Db) -> Result<Self, String> {
+    Ok(
+      Self::from_settings(settings)?
+        .with_brands(db.dao().brands_list()?)
+        .with_url_lists(db.dao().url_lists_list()?),
+    )
+  }
+
+  /// Attach the brand registry (stored in the database, not in settings).
+  pub fn with_brands(mut self, entries: Vec<BrandEntry>) -> Self {
+    self.brands = BrandRegistry::new(entries);
+    self.with_keywords()
+  }
+
+  /// Attach the URL allow/deny lists (stored in the database, not in settings).
+  pub fn with_url_lists(mut self, entries: Vec<UrlListEntry>) -> Self {
+    self.url_lists = UrlLists::new(entries);
+    self
+  }
+
+  fn with_keywords(mut self) -> Self {
+    let contexts: Vec<Vec<String>> = self.custom_extractors.iter().map(|x| x.def.context_keywords.clone()).collect();
+    self.keywords = KeywordIndex::new(self.brands.aliases(), &contexts);
+    self
+  }
+
+  /// Names of the custom entities, in definition order.
+  pub fn extra_entity_fields(&self) -> Vec<String> {
+    self.custom_extractors.iter().map(|x| x.def.name.clone()).collect()
+  }
+}
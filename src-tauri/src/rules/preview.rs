@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  db::Db,
+  model::schema::{LabelOutput, RULES_VERSION},
+};
+
+use super::{run_rules_with, RulePack};
+
+/// Dry run of a rule pack over stored messages. Nothing is written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewOptions {
+  pub mode: String, // all | unlabeled | needs_review, as for batches
+  #[serde(default)]
+  pub id_min: Option<i64>,
+  #[serde(default)]
+  pub id_max: Option<i64>,
+  /// Evaluate an evenly spaced subset of this many candidates instead of all of them.
+  #[serde(default)]
+  pub sample: Option<usize>,
+  #[serde(default = "default_examples")]
+  pub examples: usize,
+}
+
+fn default_examples() -> usize {
+  3
+}
+
+impl Default for PreviewOptions {
+  fn default() -> Self {
+    Self {
+      mode: "all".to_string(),
+      id_min: None,
+      id_max: None,
+      sample: None,
+      examples: default_examples(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewExample {
+  pub message_id: i64,
+  pub content: String,
+  /// Reason of the rule that produced the new label, if one did.
+  pub reason: Option<String>,
+}
+
+/// One cell of the old→new matrix. Labels are written `industry/type`; see [`UNLABELED`] and [`TO_MODEL`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewTransition {
+  pub from: String,
+  pub to: String,
+  pub count: i64,
+  pub examples: Vec<PreviewExample>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewReport {
+  pub rules_version: String,
+  pub candidates: i64,
+  pub scanned: i64,
+  pub changed: i64,
+  pub unchanged: i64,
+  /// Reviewer labels (`is_manual=1`): never changed, left out of the matrix.
+  pub manual_kept: i64,
+  /// Of those, how many the rules would now label differently.
+  pub manual_disagree: i64,
+  /// Changed cells first, then by count.
+  pub transitions: Vec<PreviewTransition>,
+}
+
+/// No label stored yet.
+pub const UNLABELED: &str = "(unlabeled)";
+/// No strong rule fires, so the model would decide.
+pub const TO_MODEL: &str = "(model)";
+
+pub fn preview(db: &Db, pack: &RulePack, options: &PreviewOptions) -> Result<PreviewReport, String> {
+  let ids = db
+    .dao()
+    .fetch_batch_candidates(&options.mode, i64::MAX, options.id_min, options.id_max)?;
+  let candidates = ids.len() as i64;
+  let ids = match options.sample {
+    Some(n) if n > 0 && n < ids.len() => (0..n).map(|i| ids[i * ids.len() / n]).collect(),
+    _ => ids,
+  };
+
+  let mut report = PreviewReport {
+    rules_version: RULES_VERSION.to_string(),
+    candidates,
+    scanned: 0,
+    changed: 0,
+    unchanged: 0,
+    manual_kept: 0,
+    manual_disagree: 0,
+    transitions: vec![],
+  };
+  let mut cells: HashMap<(String, String), usize> = HashMap::new();
+
+  for id in ids {
+    let content = db.dao().get_message_content(id)?;
+    let (sender, received_at) = db.dao().get_message_meta(id)?;
+    let old = db.dao().get_label(id)?;
+    let rule = run_rules_with(pack, &content, sender.as_deref(), received_at.as_deref());
+    report.scanned += 1;
+
+    let from = old.as_ref().map(key).unwrap_or_else(|| UNLABELED.to_string());
+    let to = match (&rule.label, &old) {
+      (Some(new), _) => key(new),
+      // The rules never overrode a model answer; only labels they made themselves fall back to the model.
+      (None, Some(old)) if !old.decided_by_rules() => from.clone(),
+      (None, _) => TO_MODEL.to_string(),
+    };
+
+    if db.dao().label_is_manual(id)? {
+      report.manual_kept += 1;
+      if rule.label.is_some() && to != from {
+        report.manual_disagree += 1;
+      }
+      continue;
+    }
+    if to == from {
+      report.unchanged += 1;
+    } else {
+      report.changed += 1;
+    }
+
+    let slot = *cells.entry((from.clone(), to.clone())).or_insert_with(|| {
+      report.transitions.push(PreviewTransition {
+        from,
+        to,
+        count: 0,
+        examples: vec![],
+      });
+      report.transitions.len() - 1
+    });
+    let cell = &mut report.transitions[slot];
+    cell.count += 1;
+    if cell.examples.len() < options.examples {
+      cell.examples.push(PreviewExample {
+        message_id: id,
+        content,
+        reason: rule.label.as_ref().and_then(|l| l.reasons.first().cloned()),
+      });
+    }
+  }
+
+  report
+    .transitions
+    .sort_by(|a, b| (a.from == a.to).cmp(&(b.from == b.to)).then(b.count.cmp(&a.count)));
+  Ok(report)
+}
+
+fn key(label: &LabelOutput) -> String {
+  format!("{}/{}", label.industry, label.sms_type)
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::model::schema::{EntitySpans, LabelOutput, RiskAssessment, RiskContribution, UrlListKind};
+
+use super::{
+  keywords::{KeywordList, KeywordMatches},
+  lang::Lang,
+};
+
+/// Score an SMS's phishing/fraud risk from the extracted entities and rule signals.
+///
+/// Each cue contributes an independent probability `weight`; they are combined as
+/// `1 - Π(1 - weight)` so no single weak cue dominates and the score stays in 0–1.
+pub fn score(
+  content: &str,
+  keywords: &KeywordMatches<'_>,
+  sender: Option<&str>,
+  lang: Lang,
+  spans: &EntitySpans,
+  signals: &HashMap<String, serde_json::Value>,
+) -> RiskAssessment {
+  let mut out: Vec<RiskContribution> = vec![];
+  let mut add = |signal: &str, weight: f64, detail: Option<String>| {
+    out.push(RiskContribution {
+      signal: signal.to_string(),
+      weight,
+      detail,
+    });
+  };
+
+  let claimed = signals.get("brand_registry");
+  let claimed_brand = claimed.and_then(|b| b.get("brand")).and_then(|v| v.as_str());
+  let claimed_industry = claimed.and_then(|b| b.get("industry")).and_then(|v| v.as_str());
+  let high_value = matches!(claimed_industry, Some("金融" | "政务"));
+
+  // Brand claimed in the text but sent from somewhere else.
+  if let (Some(brand), Some(sender)) = (claimed_brand, sender.map(str::trim).filter(|s| !s.is_empty())) {
+    let alias = claimed.and_then(|b| b.get("alias")).and_then(|v| v.as_str()).unwrap_or(brand);
+    if MOBILE_RE.is_match(sender) {
+      add("brand_sender_mismatch", 0.45, Some(format!("{brand} via personal number {sender}")));
+    } else if !sender.chars().all(|c| c.is_ascii_digit() || c == '+' || c == '-')
+      && !sender.contains(brand)
+      && !sender.contains(alias)
+    {
+      add("brand_sender_mismatch", 0.3, Some(format!("{brand} vs sender {sender}")));
+    }
+  }
+
+  let urls: Vec<_> = spans.url.iter().filter_map(|s| s.url_info.as_ref()).collect();
+  if let Some(u) = urls.iter().find(|u| u.list == Some(UrlListKind::Deny)) {
+    add("url_denylisted", 0.9, Some(u.registrable_domain.clone()));
+  }
+  if let Some(u) = urls.iter().find(|u| u.is_ip) {
+    add("url_ip_host", 0.5, Some(u.host.clone()));
+  }
+  if let Some(u) = urls.iter().find(|u| u.is_short_link) {
+    add("url_short_link", 0.15, Some(u.host.clone()));
+  }
+  if claimed_brand.is_some() {
+    if let Some(u) = urls.iter().find(|u| u.list.is_none()) {
+      let weight = if high_value { 0.35 } else { 0.15 };
+      add("url_not_allowlisted", weight, Some(u.registrable_domain.clone()));
+    }
+  }
+
+  let mut urgent = keywords.words(KeywordList::Urgency);
+  if lang.has_english() {
+    urgent.extend(keywords.words(KeywordList::EnUrgency));
+  }
+  if !urgent.is_empty() {
+    add("urgency_words", (0.1 + 0.05 * urgent.len() as f64).min(0.3), Some(urgent.join(",")));
+  }
+
+  let asks_for_code = CODE_REQUEST_RE.is_match(content);
+  if !urls.is_empty() && (asks_for_code || !spans.verification_code.is_empty()) {
+    add(
+      "code_with_url",
+      if asks_for_code { 0.45 } else { 0.25 },
+      Some(if asks_for_code { "asks for a code" } else { "code next to a link" }.to_string()),
+    );
+  } else if asks_for_code {
+    add("code_request", 0.3, None);
+  }
+
+  let sensitive = keywords.words(KeywordList::Sensitive);
+  if !sensitive.is_empty() {
+    add("sensitive_request", 0.35, Some(sensitive.join(",")));
+  }
+
+  let phones: Vec<&str> = spans.phone_in_text.iter().filter_map(|s| s.value.as_str()).collect();
+  if !phones.is_empty() {
+    add(
+      "personal_phone_in_text",
+      if claimed_brand.is_some() { 0.3 } else { 0.15 },
+      Some(phones.join(",")),
+    );
+  }
+
+  let score = 1.0 - out.iter().fold(1.0, |acc, c| acc * (1.0 - c.weight.clamp(0.0, 1.0)));
+  RiskAssessment {
+    score: (score * 10000.0).round() / 10000.0,
+    contributions: out,
+  }
+}
+
+/// From this score on, a label that is not already 风险提示 is sent to review.
+pub const REVIEW_THRESHOLD: f64 = 0.6;
+
+/// Flag `label` for review when its risk is high but its type does not say so (e.g. a "bank"
+/// transaction alert sent from a personal number asking for a code).
+pub fn flag_high_risk(label: &mut LabelOutput) {
+  let Some(score) = label.risk.as_ref().map(|r| r.score) else {
+    return;
+  };
+  if score >= REVIEW_THRESHOLD && label.sms_type != "风险提示" {
+    label.needs_review = true;
+    let reason = format!("risk_high:{score:.2}");
+    if !label.reasons.contains(&reason) {
+      label.reasons.push(reason);
+    }
+  }
+}
+
+pub(crate) const URGENCY_WORDS: &[&str] = &[
+  "冻结", "逾期", "立即", "立刻", "马上", "尽快", "停用", "注销", "限时", "过期", "否则", "影响征信", "司法", "最后一天",
+];
+
+// Handing over secrets or money, not just mentioning them.
+pub(crate) const SENSITIVE_WORDS: &[&str] = &["安全账户", "身份证号", "银行卡号", "密码", "转账至", "汇款至", "屏幕共享", "下载APP"];
+
+static MOBILE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:\+?86)?1[3-9]\d{9}$").unwrap());
+static CODE_REQUEST_RE: Lazy<Regex> =
+  Lazy::new(|| Regex::new(r"(?:提供|告知|回复|输入|填写|发送|报)[^，。,.]{0,6}(?:验证码|校验码|动态码)").unwrap());
@@ -0,0 +1,180 @@
+use std::{
+  cell::Cell,
+  fs,
+  time::{Duration, Instant},
+};
+
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+use crate::model::schema::{Entities, LabelOutput, RiskAssessment};
+
+/// 脚本规则（存放在 settings.json 的 `scripts`），用 Rhai 编写，在强规则之后按顺序运行。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptRuleDef {
+  pub name: String,
+  /// Script text. Either this or `path` (a `.rhai` file read when the rule pack is built).
+  #[serde(default)]
+  pub source: Option<String>,
+  #[serde(default)]
+  pub path: Option<String>,
+  #[serde(default = "default_enabled")]
+  pub enabled: bool,
+  /// Wall-clock budget per message.
+  #[serde(default = "default_timeout_ms")]
+  pub timeout_ms: u64,
+  /// Rhai operation budget per message; stops runaway loops even on a slow clock.
+  #[serde(default = "default_max_operations")]
+  pub max_operations: u64,
+}
+
+fn default_enabled() -> bool {
+  true
+}
+
+fn default_timeout_ms() -> u64 {
+  20
+}
+
+fn default_max_operations() -> u64 {
+  100_000
+}
+
+/// What a script returns to set or change the candidate label. Missing fields keep the
+/// candidate's value; without a candidate, `type` is required.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScriptVerdict {
+  #[serde(default)]
+  pub industry: Option<String>,
+  #[serde(rename = "type", default)]
+  pub sms_type: Option<String>,
+  #[serde(default)]
+  pub confidence: Option<f64>,
+  #[serde(default)]
+  pub needs_review: Option<bool>,
+  #[serde(default)]
+  pub reason: Option<String>,
+}
+
+/// Everything a script can read. Offsets in `entity_spans` are not exposed; `content` is the
+/// normalized text the rules matched.
+pub struct ScriptInput<'a> {
+  pub content: &'a str,
+  pub sender: Option<&'a str>,
+  pub received_at: Option<&'a str>,
+  pub lang: &'a str,
+  pub brand_industry: Option<&'a str>,
+  pub entities: &'a Entities,
+  pub signals: &'a serde_json::Value,
+  pub risk: &'a RiskAssessment,
+  pub label: Option<&'a LabelOutput>,
+}
+
+pub struct ScriptRule {
+  pub def: ScriptRuleDef,
+  engine: Engine,
+  ast: AST,
+}
+
+thread_local! {
+  // Deadline of the script running on this thread; read by the engine's progress callback.
+  static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+impl ScriptRule {
+  pub fn compile(def: &ScriptRuleDef) -> Result<Self, String> {
+    let name = def.name.trim();
+    if name.is_empty() {
+      return Err("script name is empty".to_string());
+    }
+    let source = match (&def.source, &def.path) {
+      (Some(s), _) => s.clone(),
+      (None, Some(p)) => fs::read_to_string(p).map_err(|e| format!("script {name}: {p}: {e}"))?,
+      (None, None) => return Err(format!("script {name}: needs `source` or `path`")),
+    };
+    let engine = sandboxed_engine(def.max_operations);
+    let ast = engine
+      .compile(&source)
+      .map_err(|e| format!("script {name}: {e}"))?;
+    Ok(Self {
+      def: def.clone(),
+      engine,
+      ast,
+    })
+  }
+
+  /// `Ok(None)` when the script returns `()` (no opinion).
+  pub fn run(&self, input: &ScriptInput<'_>) -> Result<Option<ScriptVerdict>, String> {
+    let mut scope = Scope::new();
+    scope.push_constant("content", input.content.to_string());
+    scope.push_constant("sender", optional(input.sender));
+    scope.push_constant("received_at", optional(input.received_at));
+    scope.push_constant("lang", input.lang.to_string());
+    scope.push_constant("brand_industry", optional(input.brand_industry));
+    scope.push_constant("entities", dynamic(input.entities)?);
+    scope.push_constant("signals", dynamic(input.signals)?);
+    scope.push_constant("risk", dynamic(input.risk)?);
+    let label = match input.label {
+      Some(l) => dynamic(&serde_json::json!({
+        "industry": l.industry,
+        "type": l.sms_type,
+        "confidence": l.confidence,
+        "needs_review": l.needs_review,
+        "reasons": l.reasons,
+      }))?,
+      None => Dynamic::UNIT,
+    };
+    scope.push_constant("label", label);
+
+    DEADLINE.with(|d| d.set(Some(Instant::now() + Duration::from_millis(self.def.timeout_ms))));
+    let result = self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast);
+    DEADLINE.with(|d| d.set(None));
+
+    let value = result.map_err(|e| match *e {
+      EvalAltResult::ErrorTerminated(..) => format!("timed out after {} ms", self.def.timeout_ms),
+      EvalAltResult::ErrorTooManyOperations(..) => format!("exceeded {} operations", self.def.max_operations),
+      e => e.to_string(),
+    })?;
+    if value.is_unit() {
+      return Ok(None);
+    }
+    if !value.is_map() {
+      return Err(format!("must return () or a map, got {}", value.type_name()));
+    }
+    rhai::serde::from_dynamic::<ScriptVerdict>(&value)
+      .map(Some)
+      .map_err(|e| format!("invalid result: {e}"))
+  }
+}
+
+// No imports, printing or `eval`; bounded recursion, nesting and data sizes. Time is checked
+// every few hundred operations against the thread's deadline.
+fn sandboxed_engine(max_operations: u64) -> Engine {
+  let mut engine = Engine::new();
+  engine.set_module_resolver(DummyModuleResolver::new());
+  engine.disable_symbol("eval");
+  engine.on_print(|_| {});
+  engine.on_debug(|_, _, _| {});
+  engine.set_max_operations(max_operations);
+  engine.set_max_call_levels(32);
+  engine.set_max_expr_depths(64, 32);
+  engine.set_max_string_size(64 * 1024);
+  engine.set_max_array_size(10_000);
+  engine.set_max_map_size(10_000);
+  engine.on_progress(|ops| {
+    if ops % 256 != 0 {
+      return None;
+    }
+    let expired = DEADLINE.with(|d| d.get().is_some_and(|t| Instant::now() >= t));
+    expired.then_some(Dynamic::UNIT)
+  });
+  engine
+}
+
+fn optional(v: Option<&str>) -> Dynamic {
+  v.map_or(Dynamic::UNIT, |s| Dynamic::from(s.to_string()))
+}
+
+fn dynamic<T: Serialize>(value: &T) -> Result<Dynamic, String> {
+  rhai::serde::to_dynamic(value).map_err(|e| e.to_string())
+}
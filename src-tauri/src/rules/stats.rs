@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::Db;
+
+use super::{normalize::normalize_text, strong::STRONG_RULES};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsOptions {
+  /// N-gram lengths, in tokens (a Han character or an ASCII word each).
+  #[serde(default = "default_ngram_min")]
+  pub ngram_min: usize,
+  #[serde(default = "default_ngram_max")]
+  pub ngram_max: usize,
+  #[serde(default = "default_top")]
+  pub top: usize,
+}
+
+fn default_ngram_min() -> usize {
+  2
+}
+
+fn default_ngram_max() -> usize {
+  4
+}
+
+fn default_top() -> usize {
+  30
+}
+
+impl Default for StatsOptions {
+  fn default() -> Self {
+    Self {
+      ngram_min: default_ngram_min(),
+      ngram_max: default_ngram_max(),
+      top: default_top(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleHitStats {
+  pub rule: String,
+  #[serde(rename = "type")]
+  pub sms_type: String,
+  /// Traced messages the rule matched, winning or not.
+  pub matched: i64,
+  /// Messages whose label the rule decided (traces plus `signals.rule` of untraced labels).
+  pub won: i64,
+  /// Won messages a reviewer has confirmed or corrected since.
+  pub reviewed: i64,
+  /// Of those, how many kept the rule's type / industry and type.
+  pub type_agreed: i64,
+  pub agreed: i64,
+  /// `type_agreed / reviewed`; `None` until something was reviewed.
+  pub precision: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NgramCount {
+  pub ngram: String,
+  /// Fell-through messages containing it (each message counts once).
+  pub messages: i64,
+  pub share: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleStats {
+  pub traced: i64,
+  /// Labels stored before traces existed; only their winning rule is known.
+  pub untraced: i64,
+  /// Traced messages no strong rule matched, left to the model.
+  pub fell_through: i64,
+  /// In [`STRONG_RULES`] order.
+  pub rules: Vec<RuleHitStats>,
+  /// Rules with no match in any trace and no win in any label.
+  pub never_fired: Vec<String>,
+  pub top_unmatched_ngrams: Vec<NgramCount>,
+}
+
+pub fn rule_stats(db: &Db, options: &StatsOptions) -> Result<RuleStats, String> {
+  let mut rules: Vec<RuleHitStats> = STRONG_RULES
+    .iter()
+    .map(|r| RuleHitStats {
+      rule: r.name.to_string(),
+      sms_type: r.sms_type.to_string(),
+      matched: 0,
+      won: 0,
+      reviewed: 0,
+      type_agreed: 0,
+      agreed: 0,
+      precision: None,
+    })
+    .collect();
+  let index: HashMap<String, usize> = rules.iter().enumerate().map(|(i, r)| (r.rule.clone(), i)).collect();
+
+  let (mut traced, mut fell_through) = (0i64, 0i64);
+  let mut ngrams: HashMap<String, i64> = HashMap::new();
+  db.dao().rule_traces_scan(|t| {
+    traced += 1;
+    for eval in t.trace.rules.iter().filter(|e| e.matched) {
+      if let Some(&i) = index.get(&eval.rule) {
+        rules[i].matched += 1;
+      }
+    }
+    let winner = t.trace.winner.as_ref().and_then(|w| t.trace.rules.iter().find(|e| &e.rule == w));
+    let Some(eval) = winner else {
+      fell_through += 1;
+      for g in distinct_ngrams(&normalize_text(&t.content), options.ngram_min, options.ngram_max) {
+        *ngrams.entry(g).or_default() += 1;
+      }
+      return;
+    };
+    let Some(&i) = index.get(&eval.rule) else {
+      return;
+    };
+    let stats = &mut rules[i];
+    stats.won += 1;
+    if t.reviewed {
+      stats.reviewed += 1;
+      let type_ok = t.sms_type.as_deref() == Some(eval.sms_type.as_str());
+      if type_ok {
+        stats.type_agreed += 1;
+      }
+      if type_ok && t.industry.is_some() && t.industry == eval.industry {
+        stats.agreed += 1;
+      }
+    }
+  })?;
+
+  let mut untraced = 0i64;
+  for (rule, count) in db.dao().untraced_rule_counts()? {
+    untraced += count;
+    if let Some(&i) = rule.as_ref().and_then(|r| index.get(r)) {
+      rules[i].won += count;
+    }
+  }
+
+  for r in &mut rules {
+    if r.reviewed > 0 {
+      r.precision = Some((r.type_agreed as f64 / r.reviewed as f64 * 10000.0).round() / 10000.0);
+    }
+  }
+  let never_fired = rules
+    .iter()
+    .filter(|r| r.matched == 0 && r.won == 0)
+    .map(|r| r.rule.clone())
+    .collect();
+
+  let mut top: Vec<(String, i64)> = ngrams.into_iter().collect();
+  top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+  let top_unmatched_ngrams = top
+    .into_iter()
+    .take(options.top)
+    .map(|(ngram, messages)| NgramCount {
+      ngram,
+      messages,
+      share: (messages as f64 / fell_through.max(1) as f64 * 10000.0).round() / 10000.0,
+    })
+    .collect();
+
+  Ok(RuleStats {
+    traced,
+    untraced,
+    fell_through,
+    rules,
+    never_fired,
+    top_unmatched_ngrams,
+  })
+}
+
+// Tokens are single Han characters and lower-cased ASCII words; digits, punctuation and other
+// scripts break the sequence, so codes, amounts and dates never make up an n-gram.
+fn distinct_ngrams(text: &str, min: usize, max: usize) -> HashSet<String> {
+  let mut out = HashSet::new();
+  let mut run: Vec<String> = vec![];
+  let mut word = String::new();
+  let mut flush = |run: &mut Vec<String>| {
+    for n in min.max(1)..=max {
+      for w in run.windows(n) {
+        // Chinese tokens join without spaces, English ones with.
+        out.insert(w.iter().skip(1).fold(w[0].clone(), |mut acc, t| {
+          if t.is_ascii() || acc.ends_with(|c: char| c.is_ascii()) {
+            acc.push(' ');
+          }
+          acc.push_str(t);
+          acc
+        }));
+      }
+    }
+    run.clear();
+  };
+  for c in text.chars().chain([' ']) {
+    if c.is_ascii_alphabetic() {
+      word.push(c.to_ascii_lowercase());
+      continue;
+    }
+    if !word.is_empty() {
+      run.push(std::mem::take(&mut word));
+    }
+    if matches!(c, '\u{4E00}'..='\u{9FFF}') {
+      run.push(c.to_string());
+    } else if c != ' ' || run.last().is_some_and(|t| !t.is_ascii()) {
+      // A space only separates English words; anything else ends the run.
+      flush(&mut run);
+    }
+  }
+  flush(&mut run);
+  out
+}
@@ -0,0 +1,250 @@
+use crate::model::schema::{Entities, EntitySpans, KeywordHit, RuleEval, UrlListKind};
+
+use super::{
+  industry_for,
+  keywords::{KeywordList, KeywordMatches},
+  lang::Lang,
+};
+
+/// What a strong rule sees: the normalized content plus everything extracted from it.
+pub struct RuleCtx<'a> {
+  pub content: &'a str,
+  /// The rule pack's keyword scan of `content`.
+  pub keywords: &'a KeywordMatches<'a>,
+  pub sender: Option<&'a str>,
+  pub lang: Lang,
+  /// Industry of a registered signature, if any.
+  pub brand_industry: Option<&'a str>,
+  pub entities: &'a Entities,
+  pub entity_spans: &'a EntitySpans,
+}
+
+/// A rule's verdict. `hits` are kept on a miss too, to show near misses in the trace.
+pub struct Verdict {
+  pub matched: Option<Matched>,
+  pub hits: Vec<KeywordHit>,
+}
+
+pub struct Matched {
+  pub industry: String,
+  pub confidence: f64,
+  pub reason: String,
+}
+
+/// One strong rule. Rules are evaluated in [`STRONG_RULES`] order; the first match decides the label.
+pub struct RuleDef {
+  pub name: &'static str,
+  pub sms_type: &'static str,
+  pub eval: fn(&RuleCtx<'_>) -> Verdict,
+}
+
+impl RuleDef {
+  pub fn evaluate(&self, ctx: &RuleCtx<'_>) -> RuleEval {
+    let v = (self.eval)(ctx);
+    RuleEval {
+      rule: self.name.to_string(),
+      sms_type: self.sms_type.to_string(),
+      matched: v.matched.is_some(),
+      industry: v.matched.as_ref().map(|m| m.industry.clone()),
+      confidence: v.matched.as_ref().map(|m| m.confidence),
+      reason: v.matched.map(|m| m.reason),
+      hits: v.hits,
+    }
+  }
+}
+
+pub static STRONG_RULES: [RuleDef; 5] = [
+  RuleDef {
+    name: "url_risk",
+    sms_type: "风险提示",
+    eval: url_risk,
+  },
+  RuleDef {
+    name: "verification_code",
+    sms_type: "验证码",
+    eval: verification_code,
+  },
+  RuleDef {
+    name: "logistics_pickup",
+    sms_type: "物流取件",
+    eval: logistics_pickup,
+  },
+  RuleDef {
+    name: "gov_notice",
+    sms_type: "政务通知",
+    eval: gov_notice,
+  },
+  RuleDef {
+    name: "financial_transaction",
+    sms_type: "交易提醒",
+    eval: financial_transaction,
+  },
+];
+
+pub const CODE_KEYWORDS: &[&str] = &["验证码", "校验码", "动态码", "OTP"];
+pub(crate) const LOGISTICS_KEYWORDS: &[&str] =
+  &["取件码", "快递", "驿站", "柜", "丰巢", "菜鸟", "中通", "圆通", "申通", "韵达", "顺丰", "京东物流"];
+pub(crate) const GOV_KEYWORDS: &[&str] = &["公安", "税务", "社保", "公积金", "政府", "政务", "人民法院", "检察院", "交警", "医保"];
+pub(crate) const FINANCE_KEYWORDS: &[&str] =
+  &["银行", "证券", "保险", "信用卡", "贷款", "还款", "入账", "扣款", "消费", "交易", "转账", "转入", "转出"];
+const FINANCE_SENDER_KEYWORDS: &[&str] = &["银行", "证券", "保险"];
+
+fn url_risk(ctx: &RuleCtx<'_>) -> Verdict {
+  let urls: Vec<_> = ctx.entity_spans.url.iter().filter_map(|s| Some((s, s.url_info.as_ref()?))).collect();
+  let found = if let Some((s, i)) = urls.iter().find(|(_, i)| i.list == Some(UrlListKind::Deny)) {
+    Some((s, 0.95, format!("rule: url_denylist={}", i.registrable_domain)))
+  } else if let Some((s, i)) = urls.iter().find(|(_, i)| i.is_ip) {
+    Some((s, 0.85, format!("rule: url_ip_host={}", i.host)))
+  } else if matches!(ctx.brand_industry, Some("金融" | "政务")) {
+    // A bank or government signature linking to a short link or an unlisted domain is a classic lure;
+    // a short link hides where it goes, so it weighs more.
+    let unlisted = || urls.iter().filter(|(_, i)| i.list.is_none());
+    if let Some((s, i)) = unlisted().find(|(_, i)| i.is_short_link) {
+      Some((s, 0.8, format!("rule: url_short_link_impersonation={}", i.host)))
+    } else {
+      unlisted()
+        .next()
+        .map(|(s, i)| (s, 0.75, format!("rule: url_unlisted_impersonation={}", i.registrable_domain)))
+    }
+  } else {
+    None
+  };
+  match found {
+    Some((span, confidence, reason)) => Verdict {
+      matched: Some(Matched {
+        industry: industry_for(ctx.brand_industry, "风险提示", "通用"),
+        confidence,
+        reason,
+      }),
+      hits: vec![KeywordHit {
+        keyword: span.value.as_str().unwrap_or_default().to_string(),
+        start: span.start,
+        end: span.end,
+      }],
+    },
+    None => miss(vec![]),
+  }
+}
+
+fn verification_code(ctx: &RuleCtx<'_>) -> Verdict {
+  let hits = keyword_hits(ctx, KeywordList::Code, KeywordList::EnCode);
+  match &ctx.entities.verification_code {
+    Some(code) if !hits.is_empty() => {
+      let fallback = guess_industry_from_sender(ctx.sender).unwrap_or_else(|| "通用".to_string());
+      hit(
+        Matched {
+          industry: industry_for(ctx.brand_industry, "验证码", &fallback),
+          confidence: 0.98,
+          reason: format!("rule: verification_code={code}"),
+        },
+        hits,
+      )
+    }
+    _ => miss(hits),
+  }
+}
+
+fn logistics_pickup(ctx: &RuleCtx<'_>) -> Verdict {
+  let hits = keyword_hits(ctx, KeywordList::Logistics, KeywordList::EnLogistics);
+  if hits.is_empty() {
+    return miss(hits);
+  }
+  hit(
+    Matched {
+      industry: industry_for(ctx.brand_industry, "物流取件", "通用"),
+      confidence: 0.92,
+      reason: "rule: logistics_pickup".to_string(),
+    },
+    hits,
+  )
+}
+
+fn gov_notice(ctx: &RuleCtx<'_>) -> Verdict {
+  let mut hits = keyword_hits(ctx, KeywordList::Gov, None);
+  let reason = if !hits.is_empty() {
+    "rule: gov_org_keyword"
+  } else if ctx.brand_industry == Some("政务") {
+    hits.push(off_text("signature:政务"));
+    "rule: gov_brand_signature"
+  } else {
+    return miss(hits);
+  };
+  hit(
+    Matched {
+      industry: "政务".to_string(),
+      confidence: 0.93,
+      reason: reason.to_string(),
+    },
+    hits,
+  )
+}
+
+fn financial_transaction(ctx: &RuleCtx<'_>) -> Verdict {
+  let mut hits = keyword_hits(ctx, KeywordList::Finance, KeywordList::EnTransaction);
+  if let Some(s) = ctx.sender {
+    hits.extend(
+      FINANCE_SENDER_KEYWORDS
+        .iter()
+        .filter(|k| s.contains(*k))
+        .map(|k| off_text(&format!("sender:{k}"))),
+    );
+  }
+  if hits.is_empty() {
+    return miss(hits);
+  }
+  hit(
+    Matched {
+      industry: industry_for(ctx.brand_industry, "交易提醒", "金融"),
+      confidence: 0.90,
+      reason: "rule: financial_transaction".to_string(),
+    },
+    hits,
+  )
+}
+
+fn guess_industry_from_sender(sender: Option<&str>) -> Option<String> {
+  let s = sender?.to_lowercase();
+  if ["bank", "银行", "证券", "保险"].iter().any(|k| s.contains(k)) {
+    return Some("金融".to_string());
+  }
+  None
+}
+
+fn hit(matched: Matched, hits: Vec<KeywordHit>) -> Verdict {
+  Verdict {
+    matched: Some(matched),
+    hits,
+  }
+}
+
+fn miss(hits: Vec<KeywordHit>) -> Verdict {
+  Verdict { matched: None, hits }
+}
+
+fn off_text(keyword: &str) -> KeywordHit {
+  KeywordHit {
+    keyword: keyword.to_string(),
+    start: None,
+    end: None,
+  }
+}
+
+// Every occurrence of the Chinese keywords, plus the English ones for en/mixed messages,
+// in text order with char offsets into the (normalized) content.
+fn keyword_hits(ctx: &RuleCtx<'_>, zh: KeywordList, en: impl Into<Option<KeywordList>>) -> Vec<KeywordHit> {
+  let en = en.into().filter(|_| ctx.lang.has_english());
+  let content = ctx.content;
+  let mut found: Vec<_> = ctx.keywords.of(zh).chain(en.into_iter().flat_map(|l| ctx.keywords.of(l))).collect();
+  found.sort_by_key(|m| (m.start, m.end));
+  found
+    .into_iter()
+    .map(|m| {
+      let start = content[..m.start].chars().count();
+      KeywordHit {
+        keyword: m.keyword.to_string(),
+        start: Some(start),
+        end: Some(start + content[m.start..m.end].chars().count()),
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::model::schema::{UrlInfo, UrlListKind};
+
+/// 域名名单条目（表 `url_lists`）：`allow` 为官方域名，`deny` 为已知钓鱼域名。
+/// `domain` 同时匹配其所有子域名。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlListEntry {
+  pub domain: String,
+  pub list: UrlListKind,
+  #[serde(default)]
+  pub note: Option<String>,
+  /// seed（内置）| manual（手工维护）
+  #[serde(default = "default_source")]
+  pub source: String,
+}
+
+fn default_source() -> String {
+  "manual".to_string()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UrlLists {
+  by_domain: HashMap<String, UrlListKind>,
+}
+
+impl UrlLists {
+  pub fn new(entries: Vec<UrlListEntry>) -> Self {
+    let by_domain = entries
+      .into_iter()
+      .map(|e| (normalize_domain(&e.domain), e.list))
+      .filter(|(d, _)| !d.is_empty())
+      .collect();
+    Self { by_domain }
+  }
+
+  /// The most specific listed domain covering `host` wins (`evil.bank.com` deny beats `bank.com` allow).
+  pub fn classify(&self, host: &str) -> Option<UrlListKind> {
+    let mut rest = host;
+    loop {
+      if let Some(kind) = self.by_domain.get(rest) {
+        return Some(*kind);
+      }
+      rest = rest.split_once('.')?.1;
+    }
+  }
+}
+
+/// One URL found in a text. `start`/`end` are byte offsets into the searched text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlMatch {
+  pub text: String,
+  pub info: UrlInfo,
+  pub start: usize,
+  pub end: usize,
+}
+
+/// Find URLs with or without a scheme (`https://a.com/x`, `www.a.com`, `t.cn/AbC`) and parse each one.
+/// URL text stops at the first non-ASCII character, so trailing Chinese punctuation is not swallowed.
+pub fn find_urls(content: &str, lists: &UrlLists) -> Vec<UrlMatch> {
+  URL_RE
+    .find_iter(content)
+    .filter(|m| {
+      // A bare domain glued to ASCII letters/digits is part of something else (an e-mail, a token…).
+      let before = content[..m.start()].chars().next_back();
+      !before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '@' || c == '.')
+    })
+    .filter_map(|m| {
+      let text = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"']);
+      let info = parse_url(text, lists)?;
+      Some(UrlMatch {
+        text: text.to_string(),
+        info,
+        start: m.start(),
+        end: m.start() + text.len(),
+      })
+    })
+    .collect()
+}
+
+pub fn parse_url(text: &str, lists: &UrlLists) -> Option<UrlInfo> {
+  let (scheme, rest) = match text.split_once("://") {
+    Some((s, r)) => (Some(s.to_ascii_lowercase()), r),
+    None => (None, text),
+  };
+  let (authority, path) = match rest.find(['/', '?', '#']) {
+    Some(i) => (&rest[..i], &rest[i..]),
+    None => (rest, ""),
+  };
+  let host_port = authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority);
+  let host = match host_port.strip_prefix('[') {
+    // [v6]:port
+    Some(v6) => v6.split(']').next().unwrap_or(v6),
+    None => host_port.split(':').next().unwrap_or(host_port),
+  }
+  .trim_end_matches('.')
+  .to_ascii_lowercase();
+  if host.is_empty() {
+    return None;
+  }
+
+  let is_ip = is_ip_literal(&host);
+  let registrable_domain = if is_ip { host.clone() } else { registrable_domain(&host) };
+  Some(UrlInfo {
+    scheme,
+    is_short_link: !is_ip && is_short_link(&host),
+    list: lists.classify(&host),
+    registrable_domain,
+    path: path.to_string(),
+    host,
+    is_ip,
+  })
+}
+
+/// `a.b.example.com.cn` → `example.com.cn`. Uses a small built-in table of two-level public
+/// suffixes instead of the full Public Suffix List so it works offline.
+pub fn registrable_domain(host: &str) -> String {
+  let labels: Vec<&str> = host.split('.').collect();
+  if labels.len() <= 2 {
+    return host.to_string();
+  }
+  let last_two = labels[labels.len() - 2..].join(".");
+  let keep = if MULTI_LEVEL_SUFFIXES.contains(&last_two.as_str()) { 3 } else { 2 };
+  labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+pub fn normalize_domain(s: &str) -> String {
+  let s = s.trim().to_ascii_lowercase();
+  let s = s.split_once("://").map(|(_, r)| r.to_string()).unwrap_or(s);
+  let s = s.split(['/', '?', '#', ':']).next().unwrap_or_default();
+  s.trim_start_matches("*.").trim_matches('.').to_string()
+}
+
+fn is_ip_literal(host: &str) -> bool {
+  host.parse::<std::net::IpAddr>().is_ok()
+}
+
+fn is_short_link(host: &str) -> bool {
+  SHORT_LINK_HOSTS.iter().any(|d| host == *d || host.ends_with(&format!(".{d}")))
+}
+
+const MULTI_LEVEL_SUFFIXES: &[&str] = &[
+  "com.cn", "net.cn", "org.cn", "gov.cn", "edu.cn", "ac.cn", "mil.cn", "com.hk", "org.hk", "net.hk", "gov.hk",
+  "com.tw", "org.tw", "com.mo", "co.uk", "org.uk", "ac.uk", "gov.uk", "co.jp", "ne.jp", "or.jp", "com.au",
+  "net.au", "org.au", "com.sg", "com.my", "co.kr",
+];
+
+const SHORT_LINK_HOSTS: &[&str] = &[
+  "t.cn", "dwz.cn", "url.cn", "w.url.cn", "suo.im", "suo.nz", "mrw.so", "rrd.me", "sourl.cn", "kurl.cn",
+  "3.cn", "u.jd.com", "tb.cn", "m.tb.cn", "c.tb.cn", "s.tb.cn", "b23.tv", "v.douyin.com", "xhslink.com",
+  "bit.ly", "tinyurl.com", "goo.gl", "t.co", "ow.ly", "is.gd", "j.mp", "rebrand.ly", "cutt.ly", "shorturl.at",
+  "0x3.me", "6du.in", "sohu.gg", "dwz.date", "tny.im",
+];
+
+const TLDS: &str = "com|cn|net|org|gov|edu|top|xyz|cc|vip|me|io|info|shop|club|site|online|ltd|tk|ml|ga|cf|gq|\
+  work|link|live|fun|icu|pw|ws|tv|co|app|hk|tw|mo|uk|jp|kr|sg|us|biz|asia|wang|ren|xin|store|tech|win|bid|loan|red";
+
+static URL_RE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(&format!(
+    r"(?i)[a-z][a-z0-9+.\-]*://[A-Za-z0-9\-._~:/?#\[\]@!$&'()*+,;=%]+|(?:[a-z0-9](?:[a-z0-9\-]*[a-z0-9])?\.)+(?:{TLDS})(?-u:\b)(?::\d{{1,5}})?(?:[/?#][A-Za-z0-9\-._~:/?#\[\]@!$&'()*+,;=%]*)?|(?:\d{{1,3}}\.){{3}}\d{{1,3}}(?::\d{{1,5}})?[/?#][A-Za-z0-9\-._~:/?#\[\]@!$&'()*+,;=%]*"
+  ))
+  .unwrap()
+});
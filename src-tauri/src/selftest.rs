@@ -8,14 +8,18 @@ use std::{
 use parking_lot::Mutex;
 
 use crate::{
-  db::Db,
+  db::{dao::ListQuery, Db},
   exporter::{self, ExportOptions},
   model::{
-    fusion::{self, FusionInput},
+    fusion::{self, FusionInput, FusionPolicy},
     provider::{MockProvider, Provider},
     schema::ClassifyPayload,
   },
-  rules,
+  rules::{
+    self,
+    fixtures::{self, FixtureReport},
+  },
+  settings::AppSettings,
   status::SelftestStatus,
 };
 
@@ -28,6 +32,7 @@ pub struct SelftestRunResult {
   pub db_path: PathBuf,
   pub jsonl_path: PathBuf,
   pub csv_path: PathBuf,
+  pub fixtures: FixtureReport,
 }
 
 pub struct SelftestRunner {
@@ -73,11 +78,20 @@ impl SelftestRunner {
       s.running = false;
       s.finished_at_ms = Some(now_ms());
       match result {
+        Ok(r) if !r.fixtures.ok() => {
+          s.ok = Some(false);
+          s.message = format!(
+            "failed: rule fixtures {}/{} passed; {}",
+            r.fixtures.passed,
+            r.fixtures.total,
+            r.fixtures.failure_lines().join("; ")
+          );
+        }
         Ok(r) => {
           s.ok = Some(true);
           s.message = format!(
-            "ok: inserted={}, labeled={}, jsonl={}, csv={}",
-            r.inserted, r.labeled, r.written_jsonl, r.written_csv
+            "ok: inserted={}, labeled={}, jsonl={}, csv={}, fixtures={}/{}",
+            r.inserted, r.labeled, r.written_jsonl, r.written_csv, r.fixtures.passed, r.fixtures.total
           );
         }
         Err(e) => {
@@ -91,6 +105,9 @@ impl SelftestRunner {
   }
 }
 
+/// The rule regression fixtures shipped with the app (`src-tauri/fixtures/rules.jsonl`).
+pub const BUNDLED_FIXTURES: &str = include_str!("../fixtures/rules.jsonl");
+
 pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
   fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
 
@@ -103,9 +120,10 @@ pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
   db.migrate()?;
 
   // Fully offline: generate a few deterministic sample messages.
-  let samples: [&str; 6] = [
+  let samples: [&str; 7] = [
     "【银行】您尾号1234的信用卡本期账单已出，最低还款200元，点击查看。",
     "验证码：839204（5分钟内有效），请勿泄露。",
+    "【银行】验证码123456，5分钟内有效。",
     "【快递】您的包裹已到驿站，请凭取件码A1234领取。",
     "【外卖】骑手已到达，请保持电话畅通。",
     "【政务】您有一条新的政务通知，请登录查看。",
@@ -118,21 +136,50 @@ pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
     inserted += 1;
   }
 
+  // The import flag must see a code written right after a CJK word.
+  let coded = db.dao().messages_list(ListQuery {
+    industry: None,
+    sms_type: None,
+    needs_review: None,
+    conf_min: None,
+    conf_max: None,
+    risk_min: None,
+    risk_max: None,
+    has_url: None,
+    has_verification_code: Some(true),
+    has_amount: None,
+    lang: None,
+    q: None,
+    limit: 100,
+    offset: 0,
+  })?;
+  if !coded.rows.iter().any(|r| r.content == samples[2]) {
+    return Err(format!("has_verification_code not set for: {}", samples[2]));
+  }
+
   let ids = db
     .dao()
     .fetch_batch_candidates("all", 100000, None, None)?;
   let provider = MockProvider;
+  let pack = rules::RulePack::load(&AppSettings::default(), &db)?;
 
   let mut labeled = 0i64;
   for id in ids {
     let content = db.dao().get_message_content(id)?;
+    let (sender, received_at) = db.dao().get_message_meta(id)?;
 
-    let rule = rules::run_rules(&content, None);
+    let rule = rules::run_rules_traced(&pack, &content, sender.as_deref(), received_at.as_deref());
+    if let Some(trace) = &rule.trace {
+      db.dao().rule_trace_upsert(id, trace)?;
+    }
     let payload = ClassifyPayload {
       message_id: id,
       content: content.clone(),
       entities: rule.entities.clone(),
+      entity_spans: rule.entity_spans.clone(),
       signals: rule.signals.clone(),
+      extra_entity_fields: pack.extra_entity_fields(),
+      examples: vec![],
     };
 
     let model_label = if rule.strong_hit {
@@ -141,11 +188,16 @@ pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
       Some(provider.classify(&payload, Duration::from_secs(2))?)
     };
 
-    let fused = fusion::fuse(FusionInput {
-      rule: rule.label,
-      model: model_label,
-      rule_strong_hit: rule.strong_hit,
-    });
+    let mut fused = fusion::fuse(
+      FusionInput {
+        rule: rule.label,
+        model: model_label,
+        rule_strong_hit: rule.strong_hit,
+      },
+      &FusionPolicy::default(),
+    );
+    fused.risk = Some(rule.risk);
+    rules::risk::flag_high_risk(&mut fused);
 
     db.dao().upsert_label_auto(id, &fused.normalize())?;
     labeled += 1;
@@ -172,6 +224,9 @@ pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
     },
   )?;
 
+  let bundled = fixtures::parse_fixtures(BUNDLED_FIXTURES)?;
+  let fixtures = fixtures::run_fixtures(&pack, &bundled);
+
   Ok(SelftestRunResult {
     inserted,
     labeled,
@@ -180,6 +235,7 @@ pub fn run(out_dir: PathBuf) -> Result<SelftestRunResult, String> {
     db_path,
     jsonl_path,
     csv_path,
+    fixtures,
   })
 }
 
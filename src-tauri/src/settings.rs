@@ -3,22 +3,86 @@ use std::{fs, path::PathBuf};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+  model::{calibration::CalibrationSettings, fusion::FusionSettings},
+  rules::{
+    custom::{default_custom_entities, CustomEntityDef},
+    script::ScriptRuleDef,
+  },
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderSettings {
   pub kind: String,
   pub model_path: Option<String>,
   pub llama_cli_path: Option<String>,
+  /// `llama-server` binary for the managed-server provider; unset looks next to llama-cli.
+  #[serde(default)]
+  pub llama_server_path: Option<String>,
+  /// Local port the managed `llama-server` listens on.
+  #[serde(default = "default_llama_server_port")]
+  pub llama_server_port: u16,
   #[serde(default)]
   pub ollama_base_url: Option<String>,
   #[serde(default)]
   pub ollama_model: Option<String>,
   pub temperature: f32,
   pub max_tokens: i32,
+  /// Reviewed messages shown to the model as few-shot examples; 0 sends a zero-shot prompt.
+  #[serde(default = "default_few_shot_k")]
+  pub few_shot_k: usize,
+  /// Estimated tokens the examples of one prompt may take together.
+  #[serde(default = "default_few_shot_token_budget")]
+  pub few_shot_token_budget: usize,
+  /// Active prompt template version; unset uses the built-in template.
+  #[serde(default)]
+  pub prompt_version: Option<String>,
+  /// Members of a `chain` (failover, in order) or `vote` provider.
+  #[serde(default)]
+  pub members: Vec<ProviderMember>,
+  /// How `vote` combines answers: `majority` or `confidence`.
+  #[serde(default = "default_vote_strategy")]
+  pub vote_strategy: String,
+}
+
+/// One provider inside a composite. Everything not overridden here comes from the enclosing
+/// [`ProviderSettings`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMember {
+  pub kind: String,
+  #[serde(default)]
+  pub model_path: Option<String>,
+  #[serde(default)]
+  pub ollama_model: Option<String>,
+}
+
+fn default_vote_strategy() -> String {
+  "majority".to_string()
+}
+
+fn default_llama_server_port() -> u16 {
+  8089
+}
+
+fn default_few_shot_k() -> usize {
+  3
+}
+
+fn default_few_shot_token_budget() -> usize {
+  400
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
   pub provider: ProviderSettings,
+  #[serde(default = "default_custom_entities")]
+  pub custom_entities: Vec<CustomEntityDef>,
+  #[serde(default)]
+  pub scripts: Vec<ScriptRuleDef>,
+  #[serde(default)]
+  pub calibration: CalibrationSettings,
+  #[serde(default)]
+  pub fusion: FusionSettings,
 }
 
 impl Default for AppSettings {
@@ -28,11 +92,22 @@ impl Default for AppSettings {
         kind: "ollama".to_string(),
         model_path: None,
         llama_cli_path: None,
+        llama_server_path: None,
+        llama_server_port: default_llama_server_port(),
         ollama_base_url: Some("http://127.0.0.1:11434".to_string()),
         ollama_model: Some("llama3.2:1b".to_string()),
         temperature: 0.1,
         max_tokens: 512,
+        few_shot_k: default_few_shot_k(),
+        few_shot_token_budget: default_few_shot_token_budget(),
+        prompt_version: None,
+        members: vec![],
+        vote_strategy: default_vote_strategy(),
       },
+      custom_entities: default_custom_entities(),
+      scripts: vec![],
+      calibration: CalibrationSettings::default(),
+      fusion: FusionSettings::default(),
     }
   }
 }
@@ -30,6 +30,8 @@ pub struct ProviderInfo {
   pub kind: String,
   pub model_path: Option<String>,
   pub llama_cli_path: Option<String>,
+  pub llama_server_path: Option<String>,
+  pub llama_server_port: u16,
   pub ollama_base_url: Option<String>,
   pub ollama_model: Option<String>,
   pub temperature: f32,
@@ -12,6 +12,34 @@ export type SmsType =
   | '营销推广'
   | '其他'
 
+export type TypeScore = {
+  type: SmsType
+  score: number
+}
+
+export type Money = {
+  value: number
+  currency: string
+  direction: 'debit' | 'credit' | null
+}
+
+export type TimeRef = {
+  kind: 'point' | 'deadline' | 'validity'
+  at: string | null
+  duration_secs?: number | null
+  date_only?: boolean
+}
+
+export type UrlInfo = {
+  scheme: string | null
+  host: string
+  registrable_domain: string
+  path: string
+  is_short_link: boolean
+  is_ip: boolean
+  list: 'allow' | 'deny' | null
+}
+
 export type Entities = {
   brand: string | null
   verification_code: string | null
@@ -21,19 +49,86 @@ export type Entities = {
   time_text: string | null
   url: string | null
   phone_in_text: string | null
+  amount_detail?: Money | null
+  balance_detail?: Money | null
+  deadline?: string | null
+  valid_until?: string | null
+  extra?: Record<string, string | number | null>
+}
+
+export type EntitySpan = {
+  value: string | number
+  start: number | null
+  end: number | null
+  source: 'rule' | 'model' | 'manual'
+  money?: Money
+  time?: TimeRef
+  url_info?: UrlInfo
+}
+
+export type EntitySpans = { [K in Exclude<keyof Entities, 'extra' | 'amount_detail' | 'balance_detail' | 'deadline' | 'valid_until'>]?: EntitySpan[] } & {
+  extra?: Record<string, EntitySpan[]>
+}
+
+export type RiskContribution = {
+  signal: string
+  weight: number
+  detail?: string | null
+}
+
+export type RiskAssessment = {
+  score: number
+  contributions: RiskContribution[]
+}
+
+export type KeywordHit = {
+  keyword: string
+  start: number | null
+  end: number | null
+}
+
+export type RuleEval = {
+  rule: string
+  type: SmsType
+  matched: boolean
+  industry?: Industry | null
+  confidence?: number | null
+  reason?: string | null
+  hits: KeywordHit[]
+}
+
+export type RuleTrace = {
+  rules_version: string
+  lang: string
+  normalized: boolean
+  rules: RuleEval[]
+  winner: string | null
+  decision: string
+}
+
+export type LabelExplain = {
+  message_id: number
+  content: string
+  label: LabelOutput | null
+  trace: RuleTrace | null
 }
 
 export type LabelOutput = {
   industry: Industry
   type: SmsType
+  secondary_types?: TypeScore[]
   entities: Entities
+  entity_spans?: EntitySpans
   confidence: number
   needs_review: boolean
   reasons: string[]
   signals?: Record<string, any>
   rules_version: string
   model_version: string
+  prompt_version?: string
+  fusion_policy?: string
   schema_version: string
+  risk?: RiskAssessment | null
 }
 
 export type MessageRow = {
@@ -48,3 +143,110 @@ export type MessageRow = {
   has_verification_code: boolean
   label: LabelOutput | null
 }
+
+export type BrandEntry = {
+  alias: string
+  brand: string
+  industry: Industry
+  org_type: string | null
+  source: 'seed' | 'manual' | 'learned'
+}
+
+export type UrlListEntry = {
+  domain: string
+  list: 'allow' | 'deny'
+  note: string | null
+  source: 'seed' | 'manual'
+}
+
+export type PromptTemplate = {
+  version: string
+  name: string
+  body: string
+  batch_body: string | null
+  note: string | null
+  builtin?: boolean
+}
+
+export type PreviewOptions = {
+  mode: 'all' | 'unlabeled' | 'needs_review'
+  id_min?: number
+  id_max?: number
+  sample?: number | null
+  examples?: number
+}
+
+export type PreviewExample = {
+  message_id: number
+  content: string
+  reason: string | null
+}
+
+export type PreviewTransition = {
+  from: string
+  to: string
+  count: number
+  examples: PreviewExample[]
+}
+
+export type PreviewReport = {
+  rules_version: string
+  candidates: number
+  scanned: number
+  changed: number
+  unchanged: number
+  manual_kept: number
+  manual_disagree: number
+  transitions: PreviewTransition[]
+}
+
+export type RuleHitStats = {
+  rule: string
+  type: SmsType
+  matched: number
+  won: number
+  reviewed: number
+  type_agreed: number
+  agreed: number
+  precision: number | null
+}
+
+export type NgramCount = {
+  ngram: string
+  messages: number
+  share: number
+}
+
+export type ReliabilityBin = {
+  lo: number
+  hi: number
+  count: number
+  mean_confidence: number
+  accuracy: number
+}
+
+export type SourceCalibration = {
+  source: string
+  samples: number
+  accuracy: number
+  mean_confidence: number
+  ece: number
+  bins: ReliabilityBin[]
+  method: 'isotonic' | 'platt' | null
+  calibrated_ece: number | null
+  calibrated_bins: ReliabilityBin[]
+}
+
+export type CalibrationReport = {
+  bins: number
+  sources: SourceCalibration[]
+}
+
+export type RuleStats = {
+  traced: number
+  untraced: number
+  fell_through: number
+  rules: RuleHitStats[]
+  never_fired: string[]
+  top_unmatched_ngrams: NgramCount[]
+}